@@ -0,0 +1,41 @@
+//! Checks that `tnetstr` builds for a `#![no_std]` crate, with and without its `alloc` feature:
+//!
+//! ```text
+//! cargo build --no-default-features
+//! cargo build --features alloc
+//! ```
+#![no_std]
+
+#[cfg(feature = "alloc")]
+extern crate alloc;
+
+use tnetstr::{
+    find_path,
+    validate,
+    FixedEventParser,
+    TNetEvent,
+    TNetStrError
+};
+
+/// Counts the booleans in a message that are true, without allocating
+pub fn count_true(message: &[u8]) -> Result<usize, TNetStrError> {
+    validate(message)?;
+    let mut count = 0;
+    for event in FixedEventParser::<8>::new(message) {
+        if let TNetEvent::Boolean(true) = event? {
+            count += 1;
+        }
+    }
+    Ok(count)
+}
+
+/// The data at `key` in a dictionary message, without allocating
+pub fn field<'a>(message: &'a [u8], key: &[u8]) -> Result<Option<&'a [u8]>, TNetStrError> {
+    find_path(message, &[key])
+}
+
+/// Parses a message and encodes it again
+#[cfg(feature = "alloc")]
+pub fn reencode(message: &[u8]) -> Result<alloc::vec::Vec<u8>, TNetStrError> {
+    Ok(tnetstr::encode(&tnetstr::parse(message)?))
+}
@@ -0,0 +1,96 @@
+use bytes::{BufMut, BytesMut};
+use serde::Serialize;
+use std::marker::PhantomData;
+use tnetstr::TNetStrError;
+use tnetstr::codec::DEFAULT_MAX_FRAME_LENGTH;
+use tokio_util::codec::Encoder;
+use super::{Result, SerdeTNetError};
+use super::ser::to_writer;
+
+/// A `tokio_util` encoder that writes values of `T` as tnetstrings using the serde `Serializer`,
+/// for use with `FramedWrite`.
+///
+/// Only encoding is implemented, as there is no serde `Deserializer` yet: read frames with
+/// `tnetstr::TNetCodec`, which decodes them as `TNetEntry` values. Frames longer than the maximum
+/// frame length are rejected with `TNetStrError::FrameTooLong` and leave the buffer unchanged.
+pub struct SerdeTNetCodec<T> {
+    max_frame_length: usize,
+    marker: PhantomData<fn(T)>
+}
+
+impl<T> SerdeTNetCodec<T> {
+
+    pub fn new() -> SerdeTNetCodec<T> {
+        SerdeTNetCodec::with_max_frame_length(DEFAULT_MAX_FRAME_LENGTH)
+    }
+
+    pub fn with_max_frame_length(max_frame_length: usize) -> SerdeTNetCodec<T> {
+        SerdeTNetCodec {
+            max_frame_length,
+            marker: PhantomData
+        }
+    }
+
+    pub fn max_frame_length(&self) -> usize {
+        self.max_frame_length
+    }
+}
+
+impl<T> Default for SerdeTNetCodec<T> {
+    fn default() -> SerdeTNetCodec<T> {
+        SerdeTNetCodec::new()
+    }
+}
+
+impl<T: Serialize> Encoder<T> for SerdeTNetCodec<T> {
+    type Error = SerdeTNetError;
+
+    fn encode(&mut self, item: T, dst: &mut BytesMut) -> Result<()> {
+        let start = dst.len();
+        let written = to_writer(dst.writer(), &item);
+        let frame_length = dst.len() - start;
+        if written.is_err() || frame_length > self.max_frame_length {
+            dst.truncate(start);
+        }
+        written?;
+        if frame_length > self.max_frame_length {
+            return Err(SerdeTNetError(TNetStrError::FrameTooLong(frame_length, self.max_frame_length)))
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+    use tnetstr::{TNetCodec, TNetData};
+    use tokio_util::codec::Decoder;
+
+    #[derive(Serialize)]
+    struct Message {
+        id: u32,
+        tags: Vec<&'static str>
+    }
+
+    #[test]
+    fn encodes_serialized_values() {
+        let mut codec = SerdeTNetCodec::new();
+        let mut buffer = BytesMut::new();
+        codec.encode(Message { id: 1, tags: vec!["a", "b"] }, &mut buffer).unwrap();
+        assert_eq!(&buffer[..], &b"27:2:id,1:1#4:tags,8:1:a,1:b,]}"[..]);
+
+        let entry = TNetCodec::new().decode(&mut buffer).unwrap().unwrap();
+        let dict = entry.data.as_dictionary().unwrap();
+        assert_eq!(dict.get(b"id")[0].data, TNetData::Integer(1));
+    }
+
+    #[test]
+    fn rejects_long_frames() {
+        let mut codec = SerdeTNetCodec::with_max_frame_length(4);
+        let mut buffer = BytesMut::from(&b"0:~"[..]);
+        let error = codec.encode("hello", &mut buffer).unwrap_err();
+        assert_eq!(error.0, TNetStrError::FrameTooLong(8, 4));
+        assert_eq!(&buffer[..], &b"0:~"[..]);
+    }
+}
@@ -1,5 +1,4 @@
 use tnetstr::TNetStrError;
-use serde;
 use std::fmt::{Formatter, Error as FmtError};
 
 #[derive(Debug)]
@@ -22,4 +21,16 @@ impl serde::ser::Error for SerdeTNetError {
     }
 }
 
-pub type Result<T> = std::result::Result<T, SerdeTNetError>;
\ No newline at end of file
+pub type Result<T> = std::result::Result<T, SerdeTNetError>;
+
+impl From<std::io::Error> for SerdeTNetError {
+    fn from(e: std::io::Error) -> Self {
+        SerdeTNetError(TNetStrError::from(e))
+    }
+}
+
+impl From<TNetStrError> for SerdeTNetError {
+    fn from(e: TNetStrError) -> Self {
+        SerdeTNetError(e)
+    }
+}
@@ -10,7 +10,11 @@ extern crate serde_derive;
 mod de;
 mod error;
 mod ser;
+#[cfg(feature = "tokio")]
+mod codec;
 
 //pub use de::{from_str, Deserializer};
 pub use error::{Result, SerdeTNetError};
-pub use ser::{to_string, Serializer};
+pub use ser::{to_bytes, to_string, to_writer, Serializer};
+#[cfg(feature = "tokio")]
+pub use codec::SerdeTNetCodec;
@@ -1,39 +1,39 @@
 use std::fmt::Display;
+use std::io::Write;
 use std::str;
 use serde::ser::{self, Serialize};
-use tnetstr::TNetStrError;
+use tnetstr::{frame_len, DataType, IoSink, TNetStrError, TNetWriter};
 use super::{Result, SerdeTNetError};
-use std::fmt::Error;
-use std::fmt::Formatter;
 
-const TRUE: [u8; 7] = [b'4', b':', b't', b'r', b'u', b'e', b'!'];
-const FALSE: [u8; 8] = [b'4', b':', b'f', b'a', b'l', b's', b'e', b'!'];
-const NULL: [u8; 3] = [b'0', b':', b'~'];
+/// The two passes the serializer makes over a value
+enum Pass<W: Write> {
+    /// Counting the bytes that will be written, and recording the data length of each container
+    Sizing {
+        count: usize,
+        /// The index in `sizes` and the count at the start of each open container
+        open: Vec<(usize, usize)>
+    },
+    /// Writing to the sink, using the container lengths recorded by the sizing pass
+    Writing {
+        writer: TNetWriter<IoSink<W>>,
+        next: usize
+    }
+}
 
-pub struct Serializer {
-    stack: Vec<Vec<u8>>
+/// Serializes values as tnetstrings by making two passes over them. The first calculates the
+/// length of every list and dictionary, so that the second can write everything straight to the
+/// sink without buffering containers to find their length.
+pub struct Serializer<W: Write> {
+    sizes: Vec<usize>,
+    pass: Pass<W>
 }
 
 fn byte_string(bytes: &[u8]) -> String {
     bytes.iter()
-        .map(|b| if b.is_ascii() { b.clone() as char} else { '.' })
+        .map(|b| if b.is_ascii() { *b as char} else { '.' })
         .collect()
 }
 
-impl Display for Serializer {
-
-    fn fmt(&self, f: &mut Formatter) -> std::result::Result<(), Error> {
-        for i in (0..self.stack.len()).rev() {
-            if self.stack[i].is_empty() {
-                writeln!(f, "{} -> EMPTY", i)?;
-            } else {
-                writeln!(f, "{} -> [{}]", i, byte_string(&self.stack[i]))?;
-            }
-        }
-        Ok(())
-    }
-}
-
 pub fn to_string<T>(value: &T) -> Result<String>
     where
         T: Serialize,
@@ -45,45 +45,96 @@ pub fn to_string<T>(value: &T) -> Result<String>
 pub fn to_bytes<T>(value: &T) -> Result<Vec<u8>>
     where
         T: Serialize, {
+    let mut bytes = vec![];
+    to_writer(&mut bytes, value)?;
+    Ok(bytes)
+}
+
+/// Serializes a value as a tnetstring directly into a sink.
+/// The value is serialized twice, so its `Serialize` implementation must be deterministic.
+pub fn to_writer<W, T>(writer: W, value: &T) -> Result<()>
+    where
+        W: Write,
+        T: Serialize, {
     let mut serializer = Serializer {
-        stack: vec![vec![]]
+        sizes: vec![],
+        pass: Pass::Sizing {
+            count: 0,
+            open: vec![]
+        }
     };
     value.serialize(&mut serializer)?;
-    Ok(serializer.stack.pop().unwrap())
+    serializer.pass = Pass::Writing {
+        writer: TNetWriter::new(IoSink(writer)),
+        next: 0
+    };
+    value.serialize(&mut serializer)
 }
 
-impl Serializer {
+impl<W: Write> Serializer<W> {
 
-    fn append_bytes(&mut self, bytes: &[u8]) {
-        let mut appendable: Vec<u8> = bytes.iter().map(|b|b.clone()).collect();
-        let mut frame = self.stack.pop().unwrap();
-        frame.append(&mut appendable);
-        self.stack.push(frame);
+    fn append_scalar(&mut self, data_type: DataType, data: &[u8]) -> Result<()> {
+        match &mut self.pass {
+            Pass::Sizing { count, .. } => *count += frame_len(data.len()),
+            Pass::Writing { writer, .. } => writer.write_scalar(data_type, data)?
+        };
+        Ok(())
     }
 
-    fn end_seq(&mut self, type_char: u8) -> Result<()> {
-        match self.stack.pop() {
-            None => Err(SerdeTNetError(TNetStrError::SerializationError("Attempt to end sequence without start".to_string()))),
-            Some(seq) => {
-                let start = format!("{}:", seq.len());
-                self.append_bytes(start.as_bytes());
-                self.append_bytes(&seq);
-                self.append_bytes(&vec![type_char]);
-                Ok(())
+    fn start_seq(&mut self, data_type: DataType) -> Result<()> {
+        match &mut self.pass {
+            Pass::Sizing { count, open } => {
+                open.push((self.sizes.len(), *count));
+                self.sizes.push(0);
+            },
+            Pass::Writing { writer, next } => {
+                let len = match self.sizes.get(*next) {
+                    None => return Err(SerdeTNetError(TNetStrError::SerializationError("Value changed between serialization passes".to_string()))),
+                    Some(len) => *len
+                };
+                *next += 1;
+                match data_type {
+                    DataType::Dictionary => writer.start_dictionary(len)?,
+                    _ => writer.start_list(len)?
+                }
             }
-        }
+        };
+        Ok(())
+    }
+
+    fn end_seq(&mut self) -> Result<()> {
+        match &mut self.pass {
+            Pass::Sizing { count, open } => match open.pop() {
+                None => return Err(SerdeTNetError(TNetStrError::SerializationError("Attempt to end sequence without start".to_string()))),
+                Some((index, start)) => {
+                    let len = *count - start;
+                    self.sizes[index] = len;
+                    *count = start + frame_len(len);
+                }
+            },
+            Pass::Writing { writer, .. } => writer.end()?
+        };
+        Ok(())
+    }
+
+    fn start_list(&mut self) -> Result<()> {
+        self.start_seq(DataType::List)
+    }
+
+    fn start_map(&mut self) -> Result<()> {
+        self.start_seq(DataType::Dictionary)
     }
 
     fn end_list(&mut self) -> Result<()> {
-        self.end_seq(b']')
+        self.end_seq()
     }
 
     fn end_map(&mut self) -> Result<()> {
-        self.end_seq(b'}')
+        self.end_seq()
     }
 }
 
-impl<'a> ser::Serializer for &'a mut Serializer {
+impl<W: Write> ser::Serializer for &mut Serializer<W> {
 
     type Ok = ();
 
@@ -98,15 +149,11 @@ impl<'a> ser::Serializer for &'a mut Serializer {
     type SerializeStructVariant = Self;
 
     fn serialize_bool(self, v: bool) -> Result<()> {
-        self.append_bytes(if v { &TRUE } else { &FALSE });
-        Ok(())
+        self.append_scalar(DataType::Boolean, if v { b"true" } else { b"false" })
     }
 
     fn serialize_i64(self, v: i64) -> Result<()> {
-        let s = format!("{}", v);
-        let f = format!("{}:{}#", s.len(), s);
-        self.append_bytes(&f.as_bytes());
-        Ok(())
+        self.append_scalar(DataType::Integer, v.to_string().as_bytes())
     }
 
     fn serialize_i8(self, v: i8) -> Result<()> {
@@ -134,10 +181,7 @@ impl<'a> ser::Serializer for &'a mut Serializer {
     }
 
     fn serialize_u64(self, v: u64) -> Result<()> {
-        let s = format!("{}", v);
-        let f = format!("{}:{}#", s.len(), s);
-        self.append_bytes(&f.as_bytes());
-        Ok(())
+        self.append_scalar(DataType::Integer, v.to_string().as_bytes())
     }
 
     fn serialize_f32(self, v: f32) -> Result<()> {
@@ -146,9 +190,7 @@ impl<'a> ser::Serializer for &'a mut Serializer {
 
     fn serialize_f64(self, v: f64) -> Result<()> {
         let s = format!("{:.32}", v);
-        let f = format!("{}:{}^", s.len(), s);
-        self.append_bytes(&f.as_bytes());
-        Ok(())
+        self.append_scalar(DataType::Float, s.as_bytes())
     }
 
     fn serialize_char(self, v: char) -> Result<()> {
@@ -156,28 +198,11 @@ impl<'a> ser::Serializer for &'a mut Serializer {
     }
 
     fn serialize_str(self, v: &str) -> Result<()> {
-        self.serialize_bytes(&v.as_bytes())
+        self.serialize_bytes(v.as_bytes())
     }
 
     fn serialize_bytes(self, v: &[u8]) -> Result<()> {
-        let len_str = v.len().to_string();
-        let len_bytes = len_str.as_bytes();
-        let mut f = vec![0 as u8; len_bytes.len() + 1 + v.len() + 1];
-        let mut index = 0;
-        while index < len_bytes.len() {
-            f[index] = len_bytes[index].clone();
-            index += 1;
-        }
-        f[index] = b':';
-        index += 1;
-        while index - (len_bytes.len() + 1) < v.len() {
-            let rindex = index - (len_bytes.len() + 1);
-            f[index] = v[rindex].clone();
-            index += 1
-        }
-        f[index] = b',';
-        self.append_bytes(&f);
-        Ok(())
+        self.append_scalar(DataType::Bytes, v)
     }
 
     fn serialize_none(self) -> Result<()> {
@@ -192,13 +217,11 @@ impl<'a> ser::Serializer for &'a mut Serializer {
     }
 
     fn serialize_unit(self) -> Result<()> {
-        self.append_bytes(&NULL);
-        Ok(())
+        self.append_scalar(DataType::Null, b"")
     }
 
     fn serialize_unit_struct(self, _name: &'static str) -> Result<()> {
-        self.append_bytes(&NULL);
-        Ok(())
+        self.serialize_unit()
     }
 
     fn serialize_unit_variant(
@@ -238,7 +261,7 @@ impl<'a> ser::Serializer for &'a mut Serializer {
     }
 
     fn serialize_seq(self, _len: Option<usize>) -> Result<Self::SerializeSeq> {
-        self.stack.push(vec![]);
+        self.start_list()?;
         Ok(self)
     }
 
@@ -271,7 +294,7 @@ impl<'a> ser::Serializer for &'a mut Serializer {
     }
 
     fn serialize_map(self, _len: Option<usize>) -> Result<Self::SerializeMap> {
-        self.stack.push(vec![]);
+        self.start_map()?;
         Ok(self)
     }
 
@@ -296,14 +319,14 @@ impl<'a> ser::Serializer for &'a mut Serializer {
         Ok(self)
     }
 
-    fn collect_str<T: ?Sized>(self, _value: &T) -> Result<()> where
-        T: Display {
+    fn collect_str<T>(self, _value: &T) -> Result<()> where
+        T: ?Sized + Display {
         unimplemented!()
     }
 }
 
 
-impl<'a> ser::SerializeSeq for &'a mut Serializer {
+impl<W: Write> ser::SerializeSeq for &mut Serializer<W> {
 
     type Ok = ();
     type Error = SerdeTNetError;
@@ -322,7 +345,7 @@ impl<'a> ser::SerializeSeq for &'a mut Serializer {
     }
 }
 
-impl<'a> ser::SerializeTuple for &'a mut Serializer {
+impl<W: Write> ser::SerializeTuple for &mut Serializer<W> {
     type Ok = ();
     type Error = SerdeTNetError;
 
@@ -338,7 +361,7 @@ impl<'a> ser::SerializeTuple for &'a mut Serializer {
     }
 }
 
-impl<'a> ser::SerializeTupleStruct for &'a mut Serializer {
+impl<W: Write> ser::SerializeTupleStruct for &mut Serializer<W> {
     type Ok = ();
     type Error = SerdeTNetError;
 
@@ -354,7 +377,7 @@ impl<'a> ser::SerializeTupleStruct for &'a mut Serializer {
     }
 }
 
-impl<'a> ser::SerializeTupleVariant for &'a mut Serializer {
+impl<W: Write> ser::SerializeTupleVariant for &mut Serializer<W> {
     type Ok = ();
     type Error = SerdeTNetError;
 
@@ -371,7 +394,7 @@ impl<'a> ser::SerializeTupleVariant for &'a mut Serializer {
     }
 }
 
-impl<'a> ser::SerializeMap for &'a mut Serializer {
+impl<W: Write> ser::SerializeMap for &mut Serializer<W> {
     type Ok = ();
     type Error = SerdeTNetError;
 
@@ -394,7 +417,7 @@ impl<'a> ser::SerializeMap for &'a mut Serializer {
     }
 }
 
-impl<'a> ser::SerializeStruct for &'a mut Serializer {
+impl<W: Write> ser::SerializeStruct for &mut Serializer<W> {
     type Ok = ();
     type Error = SerdeTNetError;
 
@@ -411,7 +434,7 @@ impl<'a> ser::SerializeStruct for &'a mut Serializer {
     }
 }
 
-impl<'a> ser::SerializeStructVariant for &'a mut Serializer {
+impl<W: Write> ser::SerializeStructVariant for &mut Serializer<W> {
     type Ok = ();
     type Error = SerdeTNetError;
 
@@ -439,11 +462,11 @@ fn test_seq() {
 
 #[test]
 fn test_map() {
-    use std::collections::HashMap;
-    let mut map = HashMap::<String, i32>::new();
+    use std::collections::BTreeMap;
+    let mut map = BTreeMap::<String, i32>::new();
     map.insert("a".to_owned(), 1);
     map.insert("b".to_owned(), 2);
-    let expected = "16:1:b,1:2#1:a,1:1#}";
+    let expected = "16:1:a,1:1#1:b,1:2#}";
     assert_eq!(to_string(&map).unwrap(), expected);
 }
 
@@ -488,4 +511,28 @@ fn test_enum() {
     let s = E::Struct { a: 1 };
     let expected = r#"20:6:Struct,8:1:a,1:1#}}"#;
     assert_eq!(to_string(&s).unwrap(), expected);
-}
\ No newline at end of file
+}
+
+#[test]
+fn test_to_writer_nested() {
+    #[derive(Serialize)]
+    struct Inner {
+        values: Vec<Option<bool>>,
+    }
+
+    #[derive(Serialize)]
+    struct Outer {
+        name: &'static str,
+        inner: Vec<Inner>,
+    }
+
+    let outer = Outer {
+        name: "x",
+        inner: vec![Inner { values: vec![Some(true), None] }, Inner { values: vec![] }],
+    };
+    let mut output = vec![];
+    to_writer(&mut output, &outer).unwrap();
+    let expected = r#"66:4:name,1:x,5:inner,43:23:6:values,10:4:true!0:~]}12:6:values,0:]}]}"#;
+    assert_eq!(byte_string(&output), expected);
+    assert!(tnetstr::parse(&output).is_ok());
+}
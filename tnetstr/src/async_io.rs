@@ -0,0 +1,140 @@
+use super::{
+    TNetEntry,
+    TNetStrError
+};
+use super::encode::encode;
+use super::parse::{
+    frame_data_buffer,
+    parse,
+    push_length_byte,
+    LengthDigits
+};
+use futures_io::{
+    AsyncRead,
+    AsyncWrite
+};
+use futures_util::{
+    io::{
+        AsyncReadExt,
+        AsyncWriteExt
+    },
+    stream::{
+        self,
+        Stream
+    }
+};
+use std::io::ErrorKind;
+
+/// Reads the next whole tnetstring from an async byte stream into `buffer`, replacing its contents.
+/// Returns `Ok(false)` if the stream ended cleanly before the first byte of the length.
+/// This is the async counterpart of `read_frame`.
+async fn read_frame_async<R: AsyncRead + Unpin + ?Sized>(reader: &mut R, buffer: &mut Vec<u8>) -> Result<bool, TNetStrError> {
+    let mut digits = LengthDigits::default();
+    let mut len_buff:[u8; 1] = [0];
+
+    // the first byte is read separately, so that the end of the stream can be told apart from a truncated length
+    loop {
+        match reader.read(&mut len_buff).await {
+            Ok(0) => return Ok(false),
+            Ok(_) => break,
+            Err(ref e) if e.kind() == ErrorKind::Interrupted => continue,
+            Err(e) => return Err(TNetStrError::from(e))
+        }
+    }
+    buffer.clear();
+    let data_length = loop {
+        if let Some(data_length) = push_length_byte(len_buff[0], &mut digits, buffer)? {
+            break data_length
+        }
+        reader.read_exact(&mut len_buff).await?;
+    };
+
+    reader.read_exact(frame_data_buffer(buffer, data_length)).await?;
+    Ok(true)
+}
+
+/// Attempts to read the next tnetstring from an async byte stream.
+/// This is the async counterpart of `parse_stream`.
+pub async fn parse_async<R: AsyncRead + Unpin + ?Sized>(reader: &mut R) -> Result<TNetEntry, TNetStrError> {
+    let mut buffer = vec![];
+    if !read_frame_async(reader, &mut buffer).await? {
+        return Err(TNetStrError::StreamReadFailed("Reached end of stream without reading a full length value".to_string()))
+    }
+    parse(&buffer)
+}
+
+/// Encodes an entry as a tnetstring and writes it to an async byte stream
+pub async fn write_async<W: AsyncWrite + Unpin + ?Sized>(writer: &mut W, entry: &TNetEntry) -> Result<(), TNetStrError> {
+    writer.write_all(&encode(entry)).await?;
+    Ok(())
+}
+
+/// Returns a stream of the successive tnetstrings read from an async byte stream.
+/// The stream ends when the reader ends between tnetstrings, or after the first error.
+pub fn entries_async<R: AsyncRead + Unpin>(reader: R) -> impl Stream<Item = Result<TNetEntry, TNetStrError>> {
+    stream::unfold(Some((reader, vec![])), |state| async move {
+        let (mut reader, mut buffer) = state?;
+        match read_frame_async(&mut reader, &mut buffer).await {
+            Ok(false) => None,
+            Err(e) => Some((Err(e), None)),
+            Ok(true) => match parse(&buffer) {
+                Ok(entry) => Some((Ok(entry), Some((reader, buffer)))),
+                Err(e) => Some((Err(e), None))
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+    use super::super::TNetData;
+    use futures::{
+        executor::block_on,
+        io::Cursor,
+        StreamExt
+    };
+
+    #[test]
+    fn parses_from_async_reader() {
+        let mut input = Cursor::new(b"10:aaaaaaaaaa,3:123#".to_vec());
+        let entry = block_on(parse_async(&mut input)).unwrap();
+        assert_eq!(entry.data, TNetData::Bytes(b"aaaaaaaaaa".to_vec()));
+        let entry = block_on(parse_async(&mut input)).unwrap();
+        assert_eq!(entry.data, TNetData::Integer(123));
+        assert!(block_on(parse_async(&mut input)).is_err());
+    }
+
+    #[test]
+    fn writes_to_async_writer() {
+        let entry = parse(b"19:1:a,1:1#3:bbb,2:hi,}").unwrap();
+        let mut output = Cursor::new(vec![]);
+        block_on(write_async(&mut output, &entry)).unwrap();
+        assert_eq!(output.into_inner(), b"19:1:a,1:1#3:bbb,2:hi,}");
+    }
+
+    #[test]
+    fn streams_entries_until_end() {
+        let input = Cursor::new(b"1:a,8:1:b,1:c,]0:~".to_vec());
+        let entries: Vec<_> = block_on(entries_async(input).collect());
+        assert_eq!(entries.len(), 3);
+        assert_eq!(entries[2], Ok(parse(b"0:~").unwrap()));
+    }
+
+    #[test]
+    fn stream_stops_after_error() {
+        let input = Cursor::new(b"1:a,3:abc#1:b,".to_vec());
+        let entries: Vec<_> = block_on(entries_async(input).collect());
+        assert_eq!(entries, vec![Ok(parse(b"1:a,").unwrap()), Err(TNetStrError::CouldNotParseData)]);
+    }
+
+    #[test]
+    fn reports_truncated_stream() {
+        let mut input = Cursor::new(b"10:abc".to_vec());
+        match block_on(parse_async(&mut input)) {
+            Err(TNetStrError::StreamReadFailed(_)) => {},
+            other => panic!("Expected a read failure, got {:?}", other)
+        }
+    }
+}
@@ -0,0 +1,267 @@
+use super::{
+    DataType,
+    TNetEntry,
+    TNetStrError
+};
+use super::writer::{
+    IoSink,
+    TNetWriter,
+    MAX_DATA_LENGTH
+};
+use std::io::{
+    Read,
+    Seek,
+    SeekFrom,
+    Write
+};
+
+/// The number of digits reserved for the length of a backpatched container, the most the spec allows
+const RESERVED_DIGITS: usize = 9;
+
+/// The state shared by `ListWriter` and `DictWriter`
+struct Backpatch<W: Write + Seek> {
+    sink: W,
+    data_type: DataType,
+    /// The position of the container's reserved length field
+    header_start: u64
+}
+
+impl<W: Write + Seek> Backpatch<W> {
+
+    fn new(mut sink: W, data_type: DataType) -> Result<Backpatch<W>, TNetStrError> {
+        let header_start = sink.stream_position()?;
+        sink.write_all(&[b'0'; RESERVED_DIGITS])?;
+        sink.write_all(b":")?;
+        Ok(Backpatch {
+            sink,
+            data_type,
+            header_start
+        })
+    }
+
+    fn data_start(&self) -> u64 {
+        self.header_start + RESERVED_DIGITS as u64 + 1
+    }
+
+    fn data_length(&mut self) -> Result<u64, TNetStrError> {
+        let len = self.sink.stream_position()? - self.data_start();
+        if len > MAX_DATA_LENGTH as u64 {
+            return Err(TNetStrError::LengthTooLong)
+        }
+        Ok(len)
+    }
+
+    fn push(&mut self, entry: &TNetEntry) -> Result<(), TNetStrError> {
+        TNetWriter::new(IoSink(&mut self.sink)).write_entry(entry)
+    }
+
+    fn finish(mut self) -> Result<u64, TNetStrError> {
+        let len = self.data_length()?;
+        self.sink.write_all(&[self.data_type.to_byte()])?;
+        let end = self.sink.stream_position()?;
+        self.sink.seek(SeekFrom::Start(self.header_start))?;
+        self.sink.write_all(format!("{:0width$}", len, width = RESERVED_DIGITS).as_bytes())?;
+        self.sink.seek(SeekFrom::Start(end))?;
+        Ok(end - self.header_start)
+    }
+}
+
+impl<W: Read + Write + Seek> Backpatch<W> {
+
+    fn finish_compact(mut self) -> Result<u64, TNetStrError> {
+        let len = self.data_length()?;
+        let header = format!("{}:", len);
+        let shift = (RESERVED_DIGITS + 1 - header.len()) as u64;
+
+        // move the data down to sit immediately after the compact header
+        let mut buffer = [0; 8192];
+        let mut moved = 0;
+        while moved < len {
+            let chunk = (len - moved).min(buffer.len() as u64) as usize;
+            self.sink.seek(SeekFrom::Start(self.data_start() + moved))?;
+            self.sink.read_exact(&mut buffer[..chunk])?;
+            self.sink.seek(SeekFrom::Start(self.data_start() + moved - shift))?;
+            self.sink.write_all(&buffer[..chunk])?;
+            moved += chunk as u64;
+        }
+        self.sink.seek(SeekFrom::Start(self.data_start() + len - shift))?;
+        self.sink.write_all(&[self.data_type.to_byte()])?;
+        let end = self.sink.stream_position()?;
+        self.sink.seek(SeekFrom::Start(self.header_start))?;
+        self.sink.write_all(header.as_bytes())?;
+        self.sink.seek(SeekFrom::Start(end))?;
+        Ok(end - self.header_start)
+    }
+}
+
+/// Streams the items of a list to a seekable sink, without knowing their total length in advance.
+///
+/// Nine digits are reserved for the list's length, and patched when the list is finished:
+///
+/// * `finish` writes the length zero padded to nine digits (e.g. `000000012:`). The result is
+///   still a valid tnetstring and is read by `parse`, but other implementations may not accept
+///   leading zeros in lengths.
+/// * `finish_compact` (which also needs to read the sink) moves the data back to follow a minimal
+///   length (e.g. `12:`). This leaves stale bytes after the end of the list, which the caller
+///   should truncate (e.g. with `File::set_len`) if nothing else will be written over them.
+///
+/// Both return the number of bytes in the finished list, and leave the sink positioned after it.
+/// Nested containers can be started with `start_list` and `start_dictionary`, and must be finished
+/// before anything else is pushed to this list.
+pub struct ListWriter<W: Write + Seek> {
+    inner: Backpatch<W>
+}
+
+impl<W: Write + Seek> ListWriter<W> {
+
+    /// Starts a list at the sink's current position
+    pub fn new(sink: W) -> Result<ListWriter<W>, TNetStrError> {
+        Ok(ListWriter {
+            inner: Backpatch::new(sink, DataType::List)?
+        })
+    }
+
+    pub fn push(&mut self, entry: &TNetEntry) -> Result<(), TNetStrError> {
+        self.inner.push(entry)
+    }
+
+    /// Starts a list nested in this one
+    pub fn start_list(&mut self) -> Result<ListWriter<&mut W>, TNetStrError> {
+        ListWriter::new(&mut self.inner.sink)
+    }
+
+    /// Starts a dictionary nested in this list
+    pub fn start_dictionary(&mut self) -> Result<DictWriter<&mut W>, TNetStrError> {
+        DictWriter::new(&mut self.inner.sink)
+    }
+
+    /// Patches the list's length zero padded to nine digits
+    pub fn finish(self) -> Result<u64, TNetStrError> {
+        self.inner.finish()
+    }
+}
+
+impl<W: Read + Write + Seek> ListWriter<W> {
+
+    /// Rewrites the list with a minimal length
+    pub fn finish_compact(self) -> Result<u64, TNetStrError> {
+        self.inner.finish_compact()
+    }
+}
+
+/// Streams the entries of a dictionary to a seekable sink, without knowing their total length in
+/// advance. Lengths are patched in the same way as for `ListWriter`.
+pub struct DictWriter<W: Write + Seek> {
+    inner: Backpatch<W>
+}
+
+impl<W: Write + Seek> DictWriter<W> {
+
+    /// Starts a dictionary at the sink's current position
+    pub fn new(sink: W) -> Result<DictWriter<W>, TNetStrError> {
+        Ok(DictWriter {
+            inner: Backpatch::new(sink, DataType::Dictionary)?
+        })
+    }
+
+    fn push_key(&mut self, key: &[u8]) -> Result<(), TNetStrError> {
+        TNetWriter::new(IoSink(&mut self.inner.sink)).write_bytes(key)
+    }
+
+    pub fn push(&mut self, key: &[u8], value: &TNetEntry) -> Result<(), TNetStrError> {
+        self.push_key(key)?;
+        self.inner.push(value)
+    }
+
+    /// Starts a list as the value of `key`
+    pub fn start_list(&mut self, key: &[u8]) -> Result<ListWriter<&mut W>, TNetStrError> {
+        self.push_key(key)?;
+        ListWriter::new(&mut self.inner.sink)
+    }
+
+    /// Starts a dictionary as the value of `key`
+    pub fn start_dictionary(&mut self, key: &[u8]) -> Result<DictWriter<&mut W>, TNetStrError> {
+        self.push_key(key)?;
+        DictWriter::new(&mut self.inner.sink)
+    }
+
+    /// Patches the dictionary's length zero padded to nine digits
+    pub fn finish(self) -> Result<u64, TNetStrError> {
+        self.inner.finish()
+    }
+}
+
+impl<W: Read + Write + Seek> DictWriter<W> {
+
+    /// Rewrites the dictionary with a minimal length
+    pub fn finish_compact(self) -> Result<u64, TNetStrError> {
+        self.inner.finish_compact()
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+    use super::super::{
+        parse,
+        TNetData
+    };
+    use std::io::Cursor;
+
+    fn entry(input: &[u8]) -> TNetEntry {
+        parse(input).unwrap()
+    }
+
+    #[test]
+    fn patches_zero_padded_length() {
+        let mut sink = Cursor::new(vec![]);
+        let mut list = ListWriter::new(&mut sink).unwrap();
+        list.push(&entry(b"1:a,")).unwrap();
+        list.push(&entry(b"3:123#")).unwrap();
+        assert_eq!(list.finish().unwrap(), 21);
+        let output = sink.into_inner();
+        assert_eq!(output, b"000000010:1:a,3:123#]");
+        assert_eq!(parse(&output).unwrap(), entry(b"10:1:a,3:123#]"));
+    }
+
+    #[test]
+    fn compacts_nested_containers() {
+        let mut sink = Cursor::new(vec![]);
+        let mut dict = DictWriter::new(&mut sink).unwrap();
+        dict.push(b"id", &entry(b"1:7#")).unwrap();
+        let mut items = dict.start_list(b"items").unwrap();
+        items.push(&entry(b"4:true!")).unwrap();
+        items.push(&entry(b"0:~")).unwrap();
+        assert_eq!(items.finish_compact().unwrap(), 14);
+        assert_eq!(dict.finish_compact().unwrap(), 35);
+        let mut output = sink.into_inner();
+        output.truncate(35);
+        assert_eq!(output, b"31:2:id,1:7#5:items,10:4:true!0:~]}");
+    }
+
+    #[test]
+    fn writes_after_finished_container() {
+        let mut sink = Cursor::new(vec![]);
+        ListWriter::new(&mut sink).unwrap().finish_compact().unwrap();
+        TNetWriter::new(IoSink(&mut sink)).write_null().unwrap();
+        let position = sink.position() as usize;
+        assert_eq!(&sink.into_inner()[..position], b"0:]0:~");
+    }
+
+    #[test]
+    fn compacts_long_data() {
+        let payload = vec![b'x'; 20_000];
+        let mut sink = Cursor::new(vec![]);
+        let mut list = ListWriter::new(&mut sink).unwrap();
+        list.push(&TNetEntry {
+            size: 0,
+            data_type: DataType::Bytes,
+            data: TNetData::Bytes(payload.clone())
+        }).unwrap();
+        let len = list.finish_compact().unwrap() as usize;
+        let output = sink.into_inner();
+        let parsed = parse(&output[..len]).unwrap();
+        assert_eq!(parsed.data.as_list().unwrap()[0].data, TNetData::Bytes(payload));
+    }
+}
@@ -0,0 +1,250 @@
+use super::{
+    DataType,
+    TNetData,
+    TNetDictionary,
+    TNetEntry,
+    TNetStrError
+};
+use super::encode::encode;
+use super::parse::parse_frame;
+use super::writer::{
+    float_len,
+    frame_len,
+    integer_len,
+    ByteSink,
+    TNetWriter
+};
+use alloc::vec::Vec;
+#[cfg(feature = "digest")]
+use digest::{
+    Digest,
+    Output
+};
+
+/// Orders dictionary pairs by the bytes of their keys, then pairs with equal keys by `encode` of
+/// their values. Only the values of repeated keys are encoded, each once.
+fn sort_pairs<P, F>(pairs: &mut [P], pair: fn(&P) -> &(TNetEntry, TNetEntry), encode: F)
+where
+    F: Fn(&TNetEntry) -> Vec<u8>
+{
+    pairs.sort_by(|a, b| pair(a).0.data.as_bytes().cmp(&pair(b).0.data.as_bytes()));
+    for run in pairs.chunk_by_mut(|a, b| pair(a).0.data == pair(b).0.data).filter(|run| run.len() > 1) {
+        run.sort_by_cached_key(|p| encode(&pair(p).1));
+    }
+}
+
+/// The pairs of a dictionary in canonical order
+fn sorted_pairs(dict: &TNetDictionary) -> Vec<&(TNetEntry, TNetEntry)> {
+    let mut pairs: Vec<_> = dict.entries.iter().collect();
+    sort_pairs(&mut pairs, |p| p, encode_canonical);
+    pairs
+}
+
+/// The data length of the canonical encoding of `data`
+fn canonical_data_len(data: &TNetData) -> usize {
+    match data {
+        TNetData::Null => 0,
+        TNetData::Bytes(b) | TNetData::Extension { payload: b, .. } => b.len(),
+        TNetData::Integer(n) => integer_len(*n),
+        TNetData::Float(n) => float_len(&n.canonical()),
+        TNetData::Boolean(b) => if *b { 4 } else { 5 },
+        TNetData::Dictionary(dict) => dict.entries.iter()
+            .map(|(key, value)| frame_len(canonical_data_len(&key.data)) + frame_len(canonical_data_len(&value.data)))
+            .sum(),
+        TNetData::List(l) => l.iter()
+            .map(|entry| frame_len(canonical_data_len(&entry.data)))
+            .sum()
+    }
+}
+
+/// Writes the canonical encoding of `data`. Containers are measured as they are reached, so nothing
+/// but the order of each dictionary's pairs is held in memory.
+pub(crate) fn write_canonical<W: ByteSink>(writer: &mut TNetWriter<W>, data: &TNetData) -> Result<(), TNetStrError> {
+    match data {
+        TNetData::Float(n) => writer.write_float(&n.canonical()),
+        TNetData::Dictionary(dict) => {
+            writer.start_dictionary(canonical_data_len(data))?;
+            for (key, value) in sorted_pairs(dict) {
+                write_canonical(writer, &key.data)?;
+                write_canonical(writer, &value.data)?;
+            }
+            writer.end()
+        },
+        TNetData::List(l) => {
+            writer.start_list(canonical_data_len(data))?;
+            for entry in l {
+                write_canonical(writer, &entry.data)?;
+            }
+            writer.end()
+        },
+        TNetData::Null => writer.write_null(),
+        TNetData::Bytes(b) => writer.write_bytes(b),
+        TNetData::Integer(n) => writer.write_integer(*n),
+        TNetData::Boolean(b) => writer.write_bool(*b),
+        TNetData::Extension { tag, payload } => writer.write_scalar(DataType::Extension(*tag), payload)
+    }
+}
+
+/// Encodes an entry in the canonical form described in the crate documentation
+///
+/// # Panics
+///
+/// If the data of a value is longer than `MAX_DATA_LENGTH`.
+pub fn encode_canonical(entry: &TNetEntry) -> Vec<u8> {
+    let mut output = Vec::with_capacity(frame_len(canonical_data_len(&entry.data)));
+    write_canonical(&mut TNetWriter::new(&mut output), &entry.data).expect("Data was longer than the spec allows");
+    output
+}
+
+/// Returns whether `input` is exactly one tnetstring in canonical form
+pub fn is_canonical(input: &[u8]) -> bool {
+    let mut position = 0;
+    if parse_frame(input, &mut position).is_err() || position != input.len() {
+        return false
+    }
+    match super::parse(input) {
+        Ok(entry) => encode_canonical(&entry) == input,
+        Err(_) => false
+    }
+}
+
+/// Feeds the bytes written by a `TNetWriter` to a hasher
+#[cfg(feature = "digest")]
+struct DigestSink<'a, D: Digest>(&'a mut D);
+
+#[cfg(feature = "digest")]
+impl<D: Digest> ByteSink for DigestSink<'_, D> {
+    fn put_bytes(&mut self, bytes: &[u8]) -> Result<(), TNetStrError> {
+        self.0.update(bytes);
+        Ok(())
+    }
+}
+
+impl TNetEntry {
+
+    /// Hashes the canonical encoding of the entry, so that equal values hash identically however
+    /// their dictionaries are ordered. The encoding is streamed into the hasher rather than built
+    /// in memory; only the values of duplicate dictionary keys are encoded separately, to order them.
+    ///
+    /// # Panics
+    ///
+    /// If the data of a value is longer than `MAX_DATA_LENGTH`.
+    #[cfg(feature = "digest")]
+    pub fn digest<D: Digest>(&self) -> Output<D> {
+        let mut hasher = D::new();
+        write_canonical(&mut TNetWriter::new(DigestSink(&mut hasher)), &self.data).expect("Data was longer than the spec allows");
+        hasher.finalize()
+    }
+
+    /// Rewrites the entry into canonical form: floats are put in their canonical form, dictionary
+    /// pairs are put in canonical order, and every `size` and `data_type` is set from the data.
+    /// `encode` then produces the same bytes as `encode_canonical`.
+    pub fn canonicalize(&mut self) {
+        match &mut self.data {
+            TNetData::Float(n) => *n = n.canonical(),
+            TNetData::List(l) => l.iter_mut().for_each(TNetEntry::canonicalize),
+            TNetData::Dictionary(dict) => {
+                for (key, value) in &mut dict.entries {
+                    key.canonicalize();
+                    value.canonicalize();
+                }
+                // values are already canonical, so their plain encoding is their canonical one
+                sort_pairs(&mut dict.entries, |p| p, encode);
+            },
+            _ => {}
+        }
+        self.size = canonical_data_len(&self.data) as u32;
+        self.data_type = self.data.data_type();
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+    use super::super::parse;
+
+    #[test]
+    fn sorts_keys_and_normalizes_floats() {
+        let input = parse(b"29:1:b,6:1.2500^1:a,1:1#2:aa,0:~}").unwrap();
+        let expected = b"27:1:a,1:1#2:aa,0:~1:b,4:1.25^}";
+        assert_eq!(encode_canonical(&input), &expected[..]);
+        assert!(is_canonical(expected));
+        assert!(!is_canonical(&encode(&input)));
+    }
+
+    #[test]
+    fn keeps_leading_fractional_zeros() {
+        let canonical = |input: &[u8]| encode_canonical(&parse(input).unwrap());
+        assert_eq!(canonical(b"4:0.05^"), &b"4:0.05^"[..]);
+        assert_eq!(canonical(b"6:0.0500^"), &b"4:0.05^"[..]);
+        assert_ne!(canonical(b"4:0.05^"), canonical(b"3:0.5^"));
+        assert_eq!(canonical(b"4:1.00^"), &b"3:1.0^"[..]);
+        assert!(is_canonical(b"5:3.001^"));
+    }
+
+    #[test]
+    fn writes_one_form_for_each_float_value() {
+        let canonical = |input: &[u8]| encode_canonical(&parse(input).unwrap());
+        for (input, expected) in [
+            (&b"7:15.0e-1^"[..], &b"3:1.5^"[..]),
+            (b"9:0.0015e+3^", b"3:1.5^"),
+            (b"4:-0.0^", b"3:0.0^"),
+            (b"20:100000000000000000.0^", b"7:1.0e+17^"),
+            (b"7:0.00001^", b"7:0.00001^"),
+            (b"8:0.000001^", b"6:1.0e-6^"),
+            (b"8:-2.50e+2^", b"6:-250.0^"),
+            (b"3:nan^", b"3:nan^"),
+            (b"4:-inf^", b"4:-inf^")
+        ] {
+            assert_eq!(canonical(input), expected);
+        }
+    }
+
+    #[test]
+    fn orders_duplicate_keys_by_value() {
+        let first = parse(b"19:1:k,1:2#1:k,4:1:x,]}").unwrap();
+        let second = parse(b"19:1:k,4:1:x,]1:k,1:2#}").unwrap();
+        assert_eq!(encode_canonical(&first), encode_canonical(&second));
+        assert_eq!(encode_canonical(&first), &b"19:1:k,1:2#1:k,4:1:x,]}"[..]);
+    }
+
+    #[test]
+    fn canonicalizes_in_place() {
+        let mut entry = parse(b"40:4:list,16:1:z,6:0.1000^0:~]3:key,4:true!}").unwrap();
+        let canonical = encode_canonical(&entry);
+        entry.canonicalize();
+        assert_eq!(encode(&entry), canonical);
+        assert_eq!(entry, parse(&canonical).unwrap());
+    }
+
+    #[test]
+    fn rejects_non_canonical_input() {
+        assert!(is_canonical(b"2:12#"));
+        assert!(!is_canonical(b"3:+12#"));
+        assert!(!is_canonical(b"02:12#"));
+        assert!(!is_canonical(b"2:12#0:~"));
+        assert!(!is_canonical(b"2:12"));
+    }
+
+    #[cfg(feature = "digest")]
+    #[test]
+    fn digests_canonical_encoding() {
+        use sha2::Sha256;
+        let first = parse(b"27:1:b,4:1.25^1:a,1:1#2:aa,0:~}").unwrap();
+        let second = parse(b"29:2:aa,0:~1:a,1:1#1:b,6:1.2500^}").unwrap();
+        assert_eq!(first.digest::<Sha256>(), second.digest::<Sha256>());
+        assert_eq!(first.digest::<Sha256>(), Sha256::digest(encode_canonical(&first)));
+        assert_ne!(first.digest::<Sha256>(), parse(b"4:1:a,]").unwrap().digest::<Sha256>());
+    }
+
+    #[cfg(feature = "digest")]
+    #[test]
+    fn digests_floats_with_leading_fractional_zeros() {
+        use sha2::Sha256;
+        let digest = |input: &[u8]| parse(input).unwrap().digest::<Sha256>();
+        assert_ne!(digest(b"4:0.05^"), digest(b"3:0.5^"));
+        assert_ne!(digest(b"5:1.005^"), digest(b"4:1.05^"));
+        assert_eq!(digest(b"5:0.050^"), digest(b"4:0.05^"));
+    }
+}
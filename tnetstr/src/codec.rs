@@ -0,0 +1,202 @@
+use super::{
+    TNetEntry,
+    TNetStrError
+};
+use super::encode::encode;
+use super::parse::{
+    parse,
+    parse_length
+};
+use bytes::BytesMut;
+use tokio_util::codec::{
+    Decoder,
+    Encoder
+};
+
+/// The default maximum length of a whole tnetstring frame, in bytes
+pub const DEFAULT_MAX_FRAME_LENGTH: usize = 8 * 1024 * 1024;
+
+/// A `tokio_util` codec that reads and writes successive tnetstrings as `TNetEntry` values.
+///
+/// Frames longer than the maximum frame length (including the length prefix and type tag) are
+/// rejected with `TNetStrError::FrameTooLong` as soon as their length has been read, before their
+/// data is buffered. Framing errors end the stream, as the position of the next frame is unknown.
+#[derive(Debug, Clone)]
+pub struct TNetCodec {
+    max_frame_length: usize
+}
+
+impl TNetCodec {
+
+    pub fn new() -> TNetCodec {
+        TNetCodec {
+            max_frame_length: DEFAULT_MAX_FRAME_LENGTH
+        }
+    }
+
+    pub fn with_max_frame_length(max_frame_length: usize) -> TNetCodec {
+        TNetCodec {
+            max_frame_length
+        }
+    }
+
+    pub fn max_frame_length(&self) -> usize {
+        self.max_frame_length
+    }
+
+    /// Returns the length of the whole frame at the start of `src`, or `None` if more bytes are
+    /// needed to read its length prefix
+    pub(crate) fn frame_length(&self, src: &[u8]) -> Result<Option<usize>, TNetStrError> {
+        let mut position = 0;
+        let data_length = match parse_length(src, &mut position) {
+            Ok(len) => len as usize,
+            Err(TNetStrError::LengthTerminatorNotFound) => return Ok(None),
+            Err(e) => return Err(e)
+        };
+        let frame_length = position + data_length + 1;
+        if frame_length > self.max_frame_length {
+            return Err(TNetStrError::FrameTooLong(frame_length, self.max_frame_length))
+        }
+        Ok(Some(frame_length))
+    }
+
+    /// Splits the next whole frame off the front of `src`, reserving space for the rest of it if
+    /// it has not all arrived yet
+    pub(crate) fn next_frame(&self, src: &mut BytesMut) -> Result<Option<BytesMut>, TNetStrError> {
+        let frame_length = match self.frame_length(src)? {
+            Some(len) => len,
+            None => return Ok(None)
+        };
+        if src.len() < frame_length {
+            src.reserve(frame_length - src.len());
+            return Ok(None)
+        }
+        Ok(Some(src.split_to(frame_length)))
+    }
+
+    /// Checks an encoded frame against the maximum frame length and appends it to `dst`
+    pub(crate) fn write_frame(&self, frame: &[u8], dst: &mut BytesMut) -> Result<(), TNetStrError> {
+        if frame.len() > self.max_frame_length {
+            return Err(TNetStrError::FrameTooLong(frame.len(), self.max_frame_length))
+        }
+        dst.extend_from_slice(frame);
+        Ok(())
+    }
+}
+
+impl Default for TNetCodec {
+    fn default() -> TNetCodec {
+        TNetCodec::new()
+    }
+}
+
+impl Decoder for TNetCodec {
+    type Item = TNetEntry;
+    type Error = TNetStrError;
+
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<TNetEntry>, TNetStrError> {
+        match self.next_frame(src)? {
+            None => Ok(None),
+            Some(frame) => parse(&frame).map(Some)
+        }
+    }
+}
+
+impl Encoder<&TNetEntry> for TNetCodec {
+    type Error = TNetStrError;
+
+    fn encode(&mut self, item: &TNetEntry, dst: &mut BytesMut) -> Result<(), TNetStrError> {
+        self.write_frame(&encode(item), dst)
+    }
+}
+
+impl Encoder<TNetEntry> for TNetCodec {
+    type Error = TNetStrError;
+
+    fn encode(&mut self, item: TNetEntry, dst: &mut BytesMut) -> Result<(), TNetStrError> {
+        self.encode(&item, dst)
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+    use super::super::TNetData;
+    use futures::{
+        SinkExt,
+        StreamExt
+    };
+    use tokio::io::AsyncWriteExt;
+    use tokio_util::codec::{
+        FramedRead,
+        FramedWrite
+    };
+
+    #[test]
+    fn waits_for_partial_frames() {
+        let mut codec = TNetCodec::new();
+        let mut src = BytesMut::from(&b"1"[..]);
+        assert_eq!(codec.decode(&mut src), Ok(None));
+        src.extend_from_slice(b"0:aaaa");
+        assert_eq!(codec.decode(&mut src), Ok(None));
+        assert!(src.capacity() >= 14);
+        src.extend_from_slice(b"aaaaaa,3:1");
+        let entry = codec.decode(&mut src).unwrap().unwrap();
+        assert_eq!(entry.data, TNetData::Bytes(b"aaaaaaaaaa".to_vec()));
+        assert_eq!(&src[..], b"3:1");
+    }
+
+    #[test]
+    fn rejects_long_frames_before_buffering() {
+        let mut codec = TNetCodec::with_max_frame_length(10);
+        let mut src = BytesMut::from(&b"8:"[..]);
+        assert_eq!(codec.decode(&mut src), Err(TNetStrError::FrameTooLong(11, 10)));
+        let entry = parse(b"8:abcdefgh,").unwrap();
+        assert_eq!(codec.encode(&entry, &mut BytesMut::new()), Err(TNetStrError::FrameTooLong(11, 10)));
+    }
+
+    #[test]
+    fn reports_framing_errors() {
+        let mut codec = TNetCodec::new();
+        assert_eq!(codec.decode(&mut BytesMut::from(&b"x:"[..])), Err(TNetStrError::NonASCIINumericValueInLengthField(b'x')));
+        assert_eq!(codec.decode(&mut BytesMut::from(&b"3:abc?"[..])), Err(TNetStrError::UnrecognizedDataType(b'?')));
+    }
+
+    #[tokio::test]
+    async fn round_trips_over_duplex_stream() {
+        let (client, server) = tokio::io::duplex(16);
+        let mut writer = FramedWrite::new(client, TNetCodec::new());
+        let mut reader = FramedRead::new(server, TNetCodec::new());
+        let first = parse(b"24:4:true!6:0.4529^5:abcde,]").unwrap();
+        let second = parse(b"19:1:a,1:1#3:bbb,2:hi,}").unwrap();
+
+        let write = async move {
+            writer.send(&first).await.unwrap();
+            writer.send(&second).await.unwrap();
+            writer.into_inner().shutdown().await.unwrap();
+            (first, second)
+        };
+        let read = async move {
+            let mut entries = vec![];
+            while let Some(entry) = reader.next().await {
+                entries.push(entry.unwrap());
+            }
+            entries
+        };
+        let ((first, second), entries) = futures::join!(write, read);
+        assert_eq!(entries, vec![first, second]);
+    }
+
+    #[tokio::test]
+    async fn reports_truncated_stream() {
+        let (mut client, server) = tokio::io::duplex(16);
+        let mut reader = FramedRead::new(server, TNetCodec::new());
+        client.write_all(b"10:abc").await.unwrap();
+        drop(client);
+        match reader.next().await {
+            Some(Err(TNetStrError::StreamReadFailed(_))) => {},
+            other => panic!("Expected a read failure, got {:?}", other)
+        }
+    }
+}
@@ -0,0 +1,463 @@
+use super::{
+    DataType,
+    TNetStrError
+};
+use super::diff::{
+    DiffPath,
+    PathSegment
+};
+use super::schema::Schema;
+use alloc::{
+    format,
+    string::{
+        String,
+        ToString
+    },
+    vec,
+    vec::Vec
+};
+use core::fmt::Write;
+
+/// Words that cannot be used as plain identifiers
+const KEYWORDS: &[&str] = &[
+    "as", "async", "await", "break", "const", "continue", "crate", "dyn", "else", "enum", "extern",
+    "false", "fn", "for", "if", "impl", "in", "let", "loop", "match", "mod", "move", "mut", "pub",
+    "ref", "return", "self", "Self", "static", "struct", "super", "trait", "true", "type", "unsafe",
+    "use", "where", "while", "abstract", "become", "box", "do", "final", "gen", "macro", "override",
+    "priv", "try", "typeof", "unsized", "virtual", "yield"
+];
+
+/// Keywords that cannot be raw identifiers either, which are given a trailing `_` instead
+const RESERVED: &[&str] = &["crate", "self", "Self", "super"];
+
+/// The traits whose derives read the serde and tnet attributes
+const SERDE_TRAITS: &[&str] = &["Serialize", "Deserialize"];
+const TNET_TRAITS: &[&str] = &["ToTNet", "FromTNet"];
+
+/// Options for `generate_rust`
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct CodegenOptions {
+    /// The traits derived for every generated struct and enum
+    pub derives: Vec<String>,
+    /// The type of bytes values that are not required to be UTF-8. Values that are use `String`.
+    pub bytes_type: String,
+    /// The type of values whose schema does not give them a single type. Without one, such a
+    /// schema is an error.
+    pub any_type: Option<String>
+}
+
+/// Derives `Debug`, `Clone`, `PartialEq` and serde's `Serialize` and `Deserialize`, and uses
+/// `serde_bytes::ByteBuf` for bytes, so the generated code needs `serde` (with its `derive`
+/// feature) and `serde_bytes`. As `serde_tnetstr` only serializes, these types can be written as
+/// tnetstrings but not read from them; `CodegenOptions::tnet` gives types that can be both.
+impl Default for CodegenOptions {
+    fn default() -> CodegenOptions {
+        CodegenOptions {
+            derives: ["Debug", "Clone", "PartialEq", "serde::Serialize", "serde::Deserialize"].iter()
+                .map(|derive| derive.to_string())
+                .collect(),
+            bytes_type: "serde_bytes::ByteBuf".to_string(),
+            any_type: None
+        }
+    }
+}
+
+impl CodegenOptions {
+
+    /// Derives `Debug`, `Clone`, `PartialEq` and this crate's `ToTNet` and `FromTNet`, and uses
+    /// `tnetstr::ByteBuf` for bytes, so the generated code needs the `derive` feature
+    pub fn tnet() -> CodegenOptions {
+        CodegenOptions {
+            derives: ["Debug", "Clone", "PartialEq", "tnetstr::ToTNet", "tnetstr::FromTNet"].iter()
+                .map(|derive| derive.to_string())
+                .collect(),
+            bytes_type: "tnetstr::ByteBuf".to_string(),
+            any_type: None
+        }
+    }
+
+    /// Whether any of `traits` is derived, by name or by path
+    fn derives_any(&self, traits: &[&str]) -> bool {
+        self.derives.iter().any(|derive| traits.contains(&derive.rsplit("::").next().unwrap_or(derive)))
+    }
+}
+
+/// Splits a key or value into lower case words, at anything that is not a letter or digit and
+/// before upper case letters that follow lower case ones
+fn words(text: &str) -> Vec<String> {
+    let mut words = vec![];
+    let mut word = String::new();
+    let mut previous_lower = false;
+    for c in text.chars() {
+        if !c.is_alphanumeric() {
+            if !word.is_empty() {
+                words.push(core::mem::take(&mut word));
+            }
+            previous_lower = false;
+            continue
+        }
+        if c.is_uppercase() && previous_lower {
+            words.push(core::mem::take(&mut word));
+        }
+        previous_lower = c.is_lowercase() || c.is_numeric();
+        word.extend(c.to_lowercase());
+    }
+    if !word.is_empty() {
+        words.push(word);
+    }
+    words
+}
+
+fn snake_case(text: &str) -> String {
+    let name = words(text).join("_");
+    match name.chars().next() {
+        Some(c) if c.is_numeric() => format!("_{}", name),
+        _ if RESERVED.contains(&name.as_str()) => format!("{}_", name),
+        _ if KEYWORDS.contains(&name.as_str()) => format!("r#{}", name),
+        _ => name
+    }
+}
+
+fn upper_camel_case(text: &str) -> String {
+    let name: String = words(text).iter()
+        .map(|word| {
+            let mut chars = word.chars();
+            chars.next().map(|first| first.to_uppercase().chain(chars).collect()).unwrap_or_default()
+        })
+        .collect::<Vec<String>>()
+        .concat();
+    match name.chars().next() {
+        Some(c) if c.is_numeric() => format!("V{}", name),
+        _ if RESERVED.contains(&name.as_str()) => format!("{}_", name),
+        _ => name
+    }
+}
+
+struct Generator<'o> {
+    options: &'o CodegenOptions,
+    path: Vec<PathSegment>,
+    /// Completed struct and enum definitions, nested types before the types that use them
+    items: Vec<String>,
+    /// The names of the structs and enums defined
+    names: Vec<String>
+}
+
+impl Generator<'_> {
+
+    fn error(&self, message: &str) -> TNetStrError {
+        TNetStrError::InvalidSchema(format!("{} {}", DiffPath(self.path.clone()), message))
+    }
+
+    fn derives(&self) -> String {
+        format!("#[derive({})]\n", self.options.derives.join(", "))
+    }
+
+    /// The `#[serde(...)]` and `#[tnet(...)]` attributes for a field or variant, each only if
+    /// there is something in it and a trait that reads it is derived
+    fn attributes(&self, serde: &[&str], tnet: &[&str]) -> String {
+        let mut attributes = String::new();
+        for (name, items, traits) in [("serde", serde, SERDE_TRAITS), ("tnet", tnet, TNET_TRAITS)] {
+            if !items.is_empty() && self.options.derives_any(traits) {
+                writeln!(attributes, "    #[{}({})]", name, items.join(", ")).expect("Bug - writing to a String failed");
+            }
+        }
+        attributes
+    }
+
+    /// Adds a struct or enum definition, unless another type already has its name
+    fn define(&mut self, name: &str, definition: String) -> Result<String, TNetStrError> {
+        if self.names.iter().any(|defined| defined == name) {
+            return Err(self.error(&format!("would define {}, which is already defined", name)))
+        }
+        self.names.push(name.to_string());
+        self.items.push(definition);
+        Ok(name.to_string())
+    }
+
+    /// The Rust type of values matching `schema`, defining any structs and enums it needs with
+    /// names starting with `name`
+    fn rust_type(&mut self, schema: &Schema, name: &str) -> Result<String, TNetStrError> {
+        let types = schema.types.as_deref().unwrap_or(&[]);
+        if let [t] = types {
+            return self.single_type(*t, schema, name)
+        }
+        let non_null: Vec<DataType> = types.iter().copied().filter(|t| *t != DataType::Null).collect();
+        match non_null.as_slice() {
+            [t] => Ok(format!("Option<{}>", self.single_type(*t, schema, name)?)),
+            _ => self.options.any_type.clone().ok_or_else(|| self.error("does not have a single type"))
+        }
+    }
+
+    fn single_type(&mut self, data_type: DataType, schema: &Schema, name: &str) -> Result<String, TNetStrError> {
+        Ok(match data_type {
+            DataType::Null => "()".to_string(),
+            DataType::Boolean => "bool".to_string(),
+            DataType::Integer => "i64".to_string(),
+            DataType::Float => "f64".to_string(),
+            DataType::Bytes => match &schema.allowed {
+                Some(allowed) if schema.utf8 => self.enumeration(allowed, name)?,
+                _ if schema.utf8 => "String".to_string(),
+                _ => self.options.bytes_type.clone()
+            },
+            DataType::List => match &schema.items {
+                Some(items) => {
+                    self.path.push(PathSegment::Index(0));
+                    let item_type = self.rust_type(items, &format!("{}Item", name))?;
+                    self.path.pop();
+                    format!("Vec<{}>", item_type)
+                },
+                None => format!("Vec<{}>", self.options.any_type.clone().ok_or_else(|| self.error("has no items schema"))?)
+            },
+            DataType::Dictionary if schema.keys.is_empty() => return Err(self.error("has no keys")),
+            DataType::Dictionary => self.structure(schema, name)?,
+            DataType::Extension(_) => self.options.any_type.clone().ok_or_else(|| self.error("has an extension type"))?
+        })
+    }
+
+    fn enumeration(&mut self, allowed: &[Vec<u8>], name: &str) -> Result<String, TNetStrError> {
+        let mut definition = self.derives();
+        writeln!(definition, "pub enum {} {{", name).expect("Bug - writing to a String failed");
+        let mut variants = vec![];
+        for value in allowed {
+            let value = core::str::from_utf8(value).map_err(|_| self.error("has an enum value that is not UTF-8"))?;
+            let variant = upper_camel_case(value);
+            if variant.is_empty() {
+                return Err(self.error("has an enum value with no letters or digits"))
+            }
+            if variants.contains(&variant) {
+                return Err(self.error(&format!("has more than one enum value named {}", variant)))
+            }
+            let rename = format!("rename = \"{}\"", value.escape_default());
+            writeln!(definition, "{}    {},", self.attributes(&[&rename], &[&rename]), variant)
+                .expect("Bug - writing to a String failed");
+            variants.push(variant);
+        }
+        definition.push('}');
+        self.define(name, definition)
+    }
+
+    fn structure(&mut self, schema: &Schema, name: &str) -> Result<String, TNetStrError> {
+        let mut fields = String::new();
+        let mut field_names = vec![];
+        for (key, key_schema) in &schema.keys {
+            self.path.push(PathSegment::Key { key: key.clone(), occurrence: 0 });
+            let key = core::str::from_utf8(key).map_err(|_| self.error("is not UTF-8"))?;
+            let field = snake_case(key);
+            if field.is_empty() {
+                return Err(self.error("has no letters or digits"))
+            }
+            if field_names.contains(&field) {
+                return Err(self.error(&format!("has the field name {}, as another key does", field)))
+            }
+            let mut rust_type = self.rust_type(key_schema, &format!("{}{}", name, upper_camel_case(key)))?;
+            self.path.pop();
+
+            let (mut serde, mut tnet) = (vec![], vec![]);
+            let rename = format!("rename = \"{}\"", key.escape_default());
+            if field.trim_start_matches("r#") != key {
+                serde.push(rename.as_str());
+                tnet.push(rename.as_str());
+            }
+            if key_schema.optional {
+                serde.push("default, skip_serializing_if = \"Option::is_none\"");
+                tnet.push("skip_if = \"Option::is_none\"");
+                rust_type = format!("Option<{}>", rust_type);
+            }
+            writeln!(fields, "{}    pub {}: {},", self.attributes(&serde, &tnet), field, rust_type)
+                .expect("Bug - writing to a String failed");
+            field_names.push(field);
+        }
+        let definition = format!("{}pub struct {} {{\n{}}}", self.derives(), name, fields);
+        self.define(name, definition)
+    }
+}
+
+/// Generates Rust types for the documents described by a schema, for use in a build script.
+///
+/// A dictionary schema becomes a struct named `name`, with a field for each key. Nested
+/// dictionaries become structs named after their parent and key (`name` followed by the key in
+/// upper camel case), bytes with `utf8` and an `enum` become enums, other bytes with `utf8` become
+/// `String`, and bytes without it become `options.bytes_type`. Optional keys and types that allow
+/// null become `Option`. Keys that are not snake case identifiers are renamed with serde or tnet
+/// attributes, for whichever of the two is derived; `self`, `Self`, `super` and `crate` get a
+/// trailing `_`. Keys, enum values or types that would give two things the same name are an error.
+/// Any other schema becomes a type alias named `name`. An error is returned for a
+/// schema that has no single Rust type, such as a dictionary without keys, unless
+/// `options.any_type` is given.
+pub fn generate_rust(schema: &Schema, name: &str, options: &CodegenOptions) -> Result<String, TNetStrError> {
+    let mut generator = Generator {
+        options,
+        path: vec![],
+        items: vec![],
+        names: vec![]
+    };
+    let root = generator.rust_type(schema, name)?;
+    if root != name {
+        generator.items.push(format!("pub type {} = {};", name, root));
+    }
+    let mut output = String::from("// Generated from a tnetstring schema. Do not edit.\n");
+    for item in generator.items {
+        write!(output, "\n{}\n", item).expect("Bug - writing to a String failed");
+    }
+    Ok(output)
+}
+
+/// Reads a schema from `schema_path` and writes the code `generate_rust` produces for it to
+/// `output_path`, telling Cargo to rerun the build script when the schema changes. For use in a
+/// build script, with the output written to `OUT_DIR` and included with `include!`.
+#[cfg(feature = "std")]
+pub fn generate_rust_file<P, Q>(schema_path: P, output_path: Q, name: &str, options: &CodegenOptions) -> Result<(), TNetStrError>
+where
+    P: AsRef<std::path::Path>,
+    Q: AsRef<std::path::Path>
+{
+    let schema = Schema::from_entry(&super::parse(&std::fs::read(&schema_path)?)?)?;
+    std::fs::write(output_path, generate_rust(&schema, name, options)?)?;
+    println!("cargo:rerun-if-changed={}", schema_path.as_ref().display());
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+    use super::super::parse;
+
+    fn schema(input: &[u8]) -> Schema {
+        Schema::from_entry(&parse(input).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn generates_structs_and_enums() {
+        let schema = schema(b"367:4:type,10:dictionary,4:keys,334:\
+            4:type,61:4:type,5:bytes,4:utf8,4:true!4:enum,21:4:ping,10:pong-reply,]}\
+            6:userId,17:4:type,7:integer,}\
+            5:score,33:4:type,5:float,8:optional,4:true!}\
+            4:note,40:4:type,15:5:bytes,4:null,]4:utf8,4:true!}\
+            4:tags,124:4:type,4:list,5:items,98:4:type,10:dictionary,4:keys,66:\
+            4:name,29:4:type,5:bytes,4:utf8,4:true!}4:data,15:4:type,5:bytes,}}}}}}");
+        let expected = "\
+// Generated from a tnetstring schema. Do not edit.
+
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub enum MessageType {
+    #[serde(rename = \"ping\")]
+    Ping,
+    #[serde(rename = \"pong-reply\")]
+    PongReply,
+}
+
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct MessageTagsItem {
+    pub name: String,
+    pub data: serde_bytes::ByteBuf,
+}
+
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct Message {
+    pub r#type: MessageType,
+    #[serde(rename = \"userId\")]
+    pub user_id: i64,
+    #[serde(default, skip_serializing_if = \"Option::is_none\")]
+    pub score: Option<f64>,
+    pub note: Option<String>,
+    pub tags: Vec<MessageTagsItem>,
+}
+";
+        assert_eq!(generate_rust(&schema, "Message", &CodegenOptions::default()), Ok(expected.to_string()));
+    }
+
+    #[test]
+    fn uses_any_type_for_untyped_values() {
+        let schema = schema(b"25:4:type,4:list,5:items,0:}}");
+        assert_eq!(
+            generate_rust(&schema, "Values", &CodegenOptions::default()),
+            Err(TNetStrError::InvalidSchema("$[0] does not have a single type".to_string()))
+        );
+        let options = CodegenOptions {
+            any_type: Some("tnetstr::TNetEntry".to_string()),
+            ..CodegenOptions::default()
+        };
+        assert_eq!(
+            generate_rust(&schema, "Values", &options),
+            Ok("// Generated from a tnetstring schema. Do not edit.\n\npub type Values = Vec<tnetstr::TNetEntry>;\n".to_string())
+        );
+    }
+
+    fn typed(data_type: DataType) -> Schema {
+        Schema { types: Some(vec![data_type]), ..Schema::default() }
+    }
+
+    fn dictionary(keys: Vec<(&str, Schema)>) -> Schema {
+        Schema {
+            keys: keys.into_iter().map(|(key, schema)| (key.as_bytes().to_vec(), schema)).collect(),
+            ..typed(DataType::Dictionary)
+        }
+    }
+
+    fn strings(values: &[&str]) -> Schema {
+        Schema {
+            utf8: true,
+            allowed: Some(values.iter().map(|value| value.as_bytes().to_vec()).collect()),
+            ..typed(DataType::Bytes)
+        }
+    }
+
+    #[test]
+    fn generates_tnet_attributes_and_renames_reserved_words() {
+        let schema = dictionary(vec![
+            ("self", typed(DataType::Integer)),
+            ("type", strings(&["a-b", "Self"])),
+            ("note", Schema { optional: true, ..typed(DataType::Bytes) })
+        ]);
+        let expected = "\
+// Generated from a tnetstring schema. Do not edit.
+
+#[derive(Debug, Clone, PartialEq, tnetstr::ToTNet, tnetstr::FromTNet)]
+pub enum RecordType {
+    #[tnet(rename = \"a-b\")]
+    AB,
+    #[tnet(rename = \"Self\")]
+    Self_,
+}
+
+#[derive(Debug, Clone, PartialEq, tnetstr::ToTNet, tnetstr::FromTNet)]
+pub struct Record {
+    #[tnet(rename = \"self\")]
+    pub self_: i64,
+    pub r#type: RecordType,
+    #[tnet(skip_if = \"Option::is_none\")]
+    pub note: Option<tnetstr::ByteBuf>,
+}
+";
+        assert_eq!(generate_rust(&schema, "Record", &CodegenOptions::tnet()), Ok(expected.to_string()));
+
+        let options = CodegenOptions {
+            derives: vec!["Debug".to_string()],
+            ..CodegenOptions::default()
+        };
+        let generated = generate_rust(&schema, "Record", &options).unwrap();
+        assert!(!generated.contains("#[serde") && !generated.contains("#[tnet"));
+    }
+
+    #[test]
+    fn rejects_names_used_twice() {
+        let error = |schema: Schema| generate_rust(&schema, "Message", &CodegenOptions::default()).unwrap_err();
+        assert_eq!(
+            error(dictionary(vec![("a-b", typed(DataType::Integer)), ("a_b", typed(DataType::Integer))])),
+            TNetStrError::InvalidSchema("$[\"a_b\"] has the field name a_b, as another key does".to_string())
+        );
+        let item = || dictionary(vec![("x", typed(DataType::Integer))]);
+        assert_eq!(
+            error(dictionary(vec![
+                ("tagsItem", item()),
+                ("tags", Schema { items: Some(Box::new(item())), ..typed(DataType::List) })
+            ])),
+            TNetStrError::InvalidSchema("$[\"tags\"][0] would define MessageTagsItem, which is already defined".to_string())
+        );
+        assert_eq!(
+            error(dictionary(vec![("kind", strings(&["a-b", "a_b"]))])),
+            TNetStrError::InvalidSchema("$[\"kind\"] has more than one enum value named AB".to_string())
+        );
+    }
+}
@@ -0,0 +1,537 @@
+use super::{
+    DataType,
+    TNetData,
+    TNetDictionary,
+    TNetEntry,
+    TNetFloat,
+    TNetStrError
+};
+use super::encode::encode;
+use super::lazy::{
+    parse_lazy,
+    LazyEntry
+};
+use alloc::{
+    boxed::Box,
+    collections::BTreeMap,
+    format,
+    string::{
+        String,
+        ToString
+    },
+    vec::Vec
+};
+use core::convert::TryFrom;
+use core::iter::FromIterator;
+#[cfg(feature = "std")]
+use std::{
+    collections::HashMap,
+    hash::BuildHasher
+};
+
+/// A parsed tnetstring that `FromTNet` can read values from: a `TNetEntry`, or a `LazyEntry`,
+/// which borrows from the buffer it was parsed from and only parses what is read
+pub trait TNetValue: Sized {
+    fn data_type(&self) -> DataType;
+    fn as_bytes(&self) -> Option<&[u8]>;
+    fn as_integer(&self) -> Result<Option<i64>, TNetStrError>;
+    fn as_float(&self) -> Result<Option<TNetFloat>, TNetStrError>;
+    fn as_bool(&self) -> Result<Option<bool>, TNetStrError>;
+    fn as_list(&self) -> Result<Option<&[Self]>, TNetStrError>;
+    fn as_dictionary(&self) -> Result<Option<&[(Self, Self)]>, TNetStrError>;
+    fn to_entry(&self) -> Result<TNetEntry, TNetStrError>;
+}
+
+impl TNetValue for TNetEntry {
+
+    fn data_type(&self) -> DataType {
+        self.data.data_type()
+    }
+
+    fn as_bytes(&self) -> Option<&[u8]> {
+        self.data.as_bytes()
+    }
+
+    fn as_integer(&self) -> Result<Option<i64>, TNetStrError> {
+        Ok(self.data.as_integer())
+    }
+
+    fn as_float(&self) -> Result<Option<TNetFloat>, TNetStrError> {
+        Ok(self.data.as_float().cloned())
+    }
+
+    fn as_bool(&self) -> Result<Option<bool>, TNetStrError> {
+        Ok(self.data.as_bool())
+    }
+
+    fn as_list(&self) -> Result<Option<&[TNetEntry]>, TNetStrError> {
+        Ok(self.data.as_list().map(Vec::as_slice))
+    }
+
+    fn as_dictionary(&self) -> Result<Option<&[(TNetEntry, TNetEntry)]>, TNetStrError> {
+        Ok(self.data.as_dictionary().map(|dict| dict.entries.as_slice()))
+    }
+
+    fn to_entry(&self) -> Result<TNetEntry, TNetStrError> {
+        Ok(self.clone())
+    }
+}
+
+impl<B: AsRef<[u8]> + Clone> TNetValue for LazyEntry<B> {
+
+    fn data_type(&self) -> DataType {
+        *LazyEntry::data_type(self)
+    }
+
+    fn as_bytes(&self) -> Option<&[u8]> {
+        LazyEntry::as_bytes(self)
+    }
+
+    fn as_integer(&self) -> Result<Option<i64>, TNetStrError> {
+        LazyEntry::as_integer(self)
+    }
+
+    fn as_float(&self) -> Result<Option<TNetFloat>, TNetStrError> {
+        LazyEntry::as_float(self)
+    }
+
+    fn as_bool(&self) -> Result<Option<bool>, TNetStrError> {
+        LazyEntry::as_bool(self)
+    }
+
+    fn as_list(&self) -> Result<Option<&[LazyEntry<B>]>, TNetStrError> {
+        LazyEntry::as_list(self)
+    }
+
+    fn as_dictionary(&self) -> Result<Option<&[(LazyEntry<B>, LazyEntry<B>)]>, TNetStrError> {
+        LazyEntry::as_dictionary(self)
+    }
+
+    fn to_entry(&self) -> Result<TNetEntry, TNetStrError> {
+        LazyEntry::to_entry(self)
+    }
+}
+
+/// A type that can be converted to a tnetstring. It can be derived with the `derive` feature.
+pub trait ToTNet {
+    fn to_tnet(&self) -> Result<TNetEntry, TNetStrError>;
+}
+
+/// A type that can be read from a tnetstring. It can be derived with the `derive` feature.
+pub trait FromTNet: Sized {
+
+    fn from_tnet<V: TNetValue>(value: &V) -> Result<Self, TNetStrError>;
+
+    /// The value to use when the dictionary key for a field is missing, if there is one. `Option`
+    /// uses `None`, and other types have no value so the key is required.
+    fn from_missing() -> Option<Self> {
+        None
+    }
+}
+
+/// Converts a value and encodes it
+pub fn to_tnetstring<T: ToTNet + ?Sized>(value: &T) -> Result<Vec<u8>, TNetStrError> {
+    Ok(encode(&value.to_tnet()?))
+}
+
+/// Reads a value from the first tnetstring in `input`, parsing only the parts of it that are read
+pub fn from_tnetstring<T: FromTNet>(input: &[u8]) -> Result<T, TNetStrError> {
+    T::from_tnet(&parse_lazy(input)?)
+}
+
+fn unexpected<V: TNetValue>(value: &V, expected: DataType) -> TNetStrError {
+    TNetStrError::UnexpectedDataType(expected.to_byte(), value.data_type().to_byte())
+}
+
+fn integer<V: TNetValue>(value: &V) -> Result<i64, TNetStrError> {
+    value.as_integer()?.ok_or_else(|| unexpected(value, DataType::Integer))
+}
+
+macro_rules! integer_conversions {
+    ($($t:ty),*) => {
+        $(
+            impl ToTNet for $t {
+                fn to_tnet(&self) -> Result<TNetEntry, TNetStrError> {
+                    let n = i64::try_from(*self).map_err(|_| TNetStrError::IntegerOutOfRange)?;
+                    Ok(TNetEntry::from(TNetData::Integer(n)))
+                }
+            }
+
+            impl FromTNet for $t {
+                fn from_tnet<V: TNetValue>(value: &V) -> Result<$t, TNetStrError> {
+                    <$t>::try_from(integer(value)?).map_err(|_| TNetStrError::IntegerOutOfRange)
+                }
+            }
+        )*
+    };
+}
+
+integer_conversions!(i8, i16, i32, i64, isize, u8, u16, u32, u64, usize);
+
+impl ToTNet for f64 {
+    fn to_tnet(&self) -> Result<TNetEntry, TNetStrError> {
+        Ok(TNetEntry::from(TNetData::Float(TNetFloat::from_f64(*self))))
+    }
+}
+
+/// Reads floats, and integers as their value
+impl FromTNet for f64 {
+    fn from_tnet<V: TNetValue>(value: &V) -> Result<f64, TNetStrError> {
+        match value.as_float()? {
+            Some(n) => Ok(n.to_f64()),
+            None => Ok(integer(value).map_err(|_| unexpected(value, DataType::Float))? as f64)
+        }
+    }
+}
+
+impl ToTNet for f32 {
+    fn to_tnet(&self) -> Result<TNetEntry, TNetStrError> {
+        Ok(TNetEntry::from(TNetData::Float(TNetFloat::from_f32(*self))))
+    }
+}
+
+impl FromTNet for f32 {
+    fn from_tnet<V: TNetValue>(value: &V) -> Result<f32, TNetStrError> {
+        match value.as_float()? {
+            Some(n) => Ok(n.to_f32()),
+            None => Ok(integer(value).map_err(|_| unexpected(value, DataType::Float))? as f32)
+        }
+    }
+}
+
+impl ToTNet for bool {
+    fn to_tnet(&self) -> Result<TNetEntry, TNetStrError> {
+        Ok(TNetEntry::from(TNetData::Boolean(*self)))
+    }
+}
+
+impl FromTNet for bool {
+    fn from_tnet<V: TNetValue>(value: &V) -> Result<bool, TNetStrError> {
+        value.as_bool()?.ok_or_else(|| unexpected(value, DataType::Boolean))
+    }
+}
+
+impl ToTNet for () {
+    fn to_tnet(&self) -> Result<TNetEntry, TNetStrError> {
+        Ok(TNetEntry::from(TNetData::Null))
+    }
+}
+
+impl FromTNet for () {
+    fn from_tnet<V: TNetValue>(value: &V) -> Result<(), TNetStrError> {
+        match value.data_type() {
+            DataType::Null => Ok(()),
+            _ => Err(unexpected(value, DataType::Null))
+        }
+    }
+}
+
+/// Strings are written as bytes
+impl ToTNet for str {
+    fn to_tnet(&self) -> Result<TNetEntry, TNetStrError> {
+        Ok(TNetEntry::from(TNetData::Bytes(self.as_bytes().to_vec())))
+    }
+}
+
+impl ToTNet for String {
+    fn to_tnet(&self) -> Result<TNetEntry, TNetStrError> {
+        self.as_str().to_tnet()
+    }
+}
+
+/// Reads bytes that are valid UTF-8
+impl FromTNet for String {
+    fn from_tnet<V: TNetValue>(value: &V) -> Result<String, TNetStrError> {
+        let bytes = value.as_bytes().ok_or_else(|| unexpected(value, DataType::Bytes))?;
+        core::str::from_utf8(bytes)
+            .map(str::to_string)
+            .map_err(|_| TNetStrError::DataNotUTF8Compatible)
+    }
+}
+
+/// Bytes that are written as bytes rather than as a list, wherever they are, such as in an
+/// `Option` or a `Vec`
+#[derive(Debug, Clone, Default, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub struct ByteBuf(pub Vec<u8>);
+
+impl From<Vec<u8>> for ByteBuf {
+    fn from(bytes: Vec<u8>) -> ByteBuf {
+        ByteBuf(bytes)
+    }
+}
+
+impl ToTNet for ByteBuf {
+    fn to_tnet(&self) -> Result<TNetEntry, TNetStrError> {
+        Ok(TNetEntry::from(TNetData::Bytes(self.0.clone())))
+    }
+}
+
+impl FromTNet for ByteBuf {
+    fn from_tnet<V: TNetValue>(value: &V) -> Result<ByteBuf, TNetStrError> {
+        __private::read_bytes(value).map(ByteBuf)
+    }
+}
+
+/// `None` is written as null
+impl<T: ToTNet> ToTNet for Option<T> {
+    fn to_tnet(&self) -> Result<TNetEntry, TNetStrError> {
+        match self {
+            Some(value) => value.to_tnet(),
+            None => Ok(TNetEntry::from(TNetData::Null))
+        }
+    }
+}
+
+/// Null and missing keys are read as `None`
+impl<T: FromTNet> FromTNet for Option<T> {
+
+    fn from_tnet<V: TNetValue>(value: &V) -> Result<Option<T>, TNetStrError> {
+        match value.data_type() {
+            DataType::Null => Ok(None),
+            _ => T::from_tnet(value).map(Some)
+        }
+    }
+
+    fn from_missing() -> Option<Option<T>> {
+        Some(None)
+    }
+}
+
+/// Slices and `Vec`s are written as lists. Use `#[tnet(bytes)]` when deriving to write a
+/// `Vec<u8>` field as bytes.
+impl<T: ToTNet> ToTNet for [T] {
+    fn to_tnet(&self) -> Result<TNetEntry, TNetStrError> {
+        let items = self.iter().map(T::to_tnet).collect::<Result<_, _>>()?;
+        Ok(TNetEntry::from(TNetData::List(items)))
+    }
+}
+
+impl<T: ToTNet> ToTNet for Vec<T> {
+    fn to_tnet(&self) -> Result<TNetEntry, TNetStrError> {
+        self.as_slice().to_tnet()
+    }
+}
+
+impl<T: FromTNet> FromTNet for Vec<T> {
+    fn from_tnet<V: TNetValue>(value: &V) -> Result<Vec<T>, TNetStrError> {
+        value.as_list()?
+            .ok_or_else(|| unexpected(value, DataType::List))?
+            .iter()
+            .map(T::from_tnet)
+            .collect()
+    }
+}
+
+impl<T: ToTNet + ?Sized> ToTNet for &T {
+    fn to_tnet(&self) -> Result<TNetEntry, TNetStrError> {
+        (**self).to_tnet()
+    }
+}
+
+impl<T: ToTNet + ?Sized> ToTNet for Box<T> {
+    fn to_tnet(&self) -> Result<TNetEntry, TNetStrError> {
+        (**self).to_tnet()
+    }
+}
+
+impl<T: FromTNet> FromTNet for Box<T> {
+    fn from_tnet<V: TNetValue>(value: &V) -> Result<Box<T>, TNetStrError> {
+        T::from_tnet(value).map(Box::new)
+    }
+}
+
+/// The pairs of a map written as a dictionary
+fn map_to_tnet<'m, K, T, I>(pairs: I) -> Result<TNetEntry, TNetStrError>
+where
+    K: AsRef<str> + 'm,
+    T: ToTNet + 'm,
+    I: Iterator<Item = (&'m K, &'m T)>
+{
+    let entries = pairs
+        .map(|(key, value)| Ok((key.as_ref().to_tnet()?, value.to_tnet()?)))
+        .collect::<Result<_, TNetStrError>>()?;
+    Ok(TNetEntry::from(TNetData::Dictionary(TNetDictionary { entries })))
+}
+
+/// The pairs of a dictionary read as a map. Later values of a repeated key replace earlier ones.
+fn map_from_tnet<V: TNetValue, T: FromTNet, M: FromIterator<(String, T)>>(value: &V) -> Result<M, TNetStrError> {
+    value.as_dictionary()?
+        .ok_or_else(|| unexpected(value, DataType::Dictionary))?
+        .iter()
+        .map(|(key, value)| Ok((String::from_tnet(key)?, T::from_tnet(value)?)))
+        .collect()
+}
+
+/// Maps are written as dictionaries in key order
+impl<T: ToTNet> ToTNet for BTreeMap<String, T> {
+    fn to_tnet(&self) -> Result<TNetEntry, TNetStrError> {
+        map_to_tnet(self.iter())
+    }
+}
+
+impl<T: FromTNet> FromTNet for BTreeMap<String, T> {
+    fn from_tnet<V: TNetValue>(value: &V) -> Result<BTreeMap<String, T>, TNetStrError> {
+        map_from_tnet(value)
+    }
+}
+
+/// Maps are written as dictionaries in iteration order
+#[cfg(feature = "std")]
+impl<T: ToTNet, S> ToTNet for HashMap<String, T, S> {
+    fn to_tnet(&self) -> Result<TNetEntry, TNetStrError> {
+        map_to_tnet(self.iter())
+    }
+}
+
+#[cfg(feature = "std")]
+impl<T: FromTNet, S: BuildHasher + Default> FromTNet for HashMap<String, T, S> {
+    fn from_tnet<V: TNetValue>(value: &V) -> Result<HashMap<String, T, S>, TNetStrError> {
+        map_from_tnet(value)
+    }
+}
+
+impl ToTNet for TNetEntry {
+    fn to_tnet(&self) -> Result<TNetEntry, TNetStrError> {
+        Ok(self.clone())
+    }
+}
+
+impl FromTNet for TNetEntry {
+    fn from_tnet<V: TNetValue>(value: &V) -> Result<TNetEntry, TNetStrError> {
+        value.to_entry()
+    }
+}
+
+/// Helpers for the code generated by `#[derive(ToTNet, FromTNet)]`. Not part of the public API.
+#[doc(hidden)]
+pub mod __private {
+
+    use super::*;
+    pub use alloc::vec::Vec;
+
+    pub fn key(name: &str) -> TNetEntry {
+        bytes(name.as_bytes())
+    }
+
+    pub fn bytes(value: &[u8]) -> TNetEntry {
+        TNetEntry::from(TNetData::Bytes(value.to_vec()))
+    }
+
+    pub fn null() -> TNetEntry {
+        TNetEntry::from(TNetData::Null)
+    }
+
+    pub fn list(items: Vec<TNetEntry>) -> TNetEntry {
+        TNetEntry::from(TNetData::List(items))
+    }
+
+    pub fn dictionary(entries: Vec<(TNetEntry, TNetEntry)>) -> TNetEntry {
+        TNetEntry::from(TNetData::Dictionary(TNetDictionary { entries }))
+    }
+
+    pub fn pairs<V: TNetValue>(value: &V) -> Result<&[(V, V)], TNetStrError> {
+        value.as_dictionary()?.ok_or_else(|| unexpected(value, DataType::Dictionary))
+    }
+
+    /// The items of a list that must have `length` items
+    pub fn items<V: TNetValue>(value: &V, length: usize) -> Result<&[V], TNetStrError> {
+        let items = value.as_list()?.ok_or_else(|| unexpected(value, DataType::List))?;
+        match items.len() == length {
+            true => Ok(items),
+            false => Err(TNetStrError::SerializationError(format!("Expected a list of {} items but found {}", length, items.len())))
+        }
+    }
+
+    /// The only pair of a dictionary, as an externally tagged enum is written
+    pub fn single_pair<V: TNetValue>(value: &V) -> Result<&(V, V), TNetStrError> {
+        match pairs(value)? {
+            [pair] => Ok(pair),
+            _ => Err(TNetStrError::SerializationError("Expected an enum variant name or a dictionary with one pair".to_string()))
+        }
+    }
+
+    pub fn read_bytes<V: TNetValue>(value: &V) -> Result<Vec<u8>, TNetStrError> {
+        value.as_bytes().map(<[u8]>::to_vec).ok_or_else(|| unexpected(value, DataType::Bytes))
+    }
+
+    /// The value of the first pair with `key`
+    pub fn get<'v, V: TNetValue>(pairs: &'v [(V, V)], key: &str) -> Option<&'v V> {
+        pairs.iter()
+            .find(|(k, _)| k.as_bytes() == Some(key.as_bytes()))
+            .map(|(_, value)| value)
+    }
+
+    pub fn required<'v, V: TNetValue>(pairs: &'v [(V, V)], key: &str) -> Result<&'v V, TNetStrError> {
+        get(pairs, key).ok_or_else(|| missing_key(key))
+    }
+
+    pub fn field<V: TNetValue, T: FromTNet>(pairs: &[(V, V)], key: &str) -> Result<Option<T>, TNetStrError> {
+        get(pairs, key).map(T::from_tnet).transpose()
+    }
+
+    pub fn bytes_field<V: TNetValue>(pairs: &[(V, V)], key: &str) -> Result<Option<Vec<u8>>, TNetStrError> {
+        get(pairs, key).map(read_bytes).transpose()
+    }
+
+    pub fn missing<T: FromTNet>(key: &str) -> Result<T, TNetStrError> {
+        T::from_missing().ok_or_else(|| missing_key(key))
+    }
+
+    pub fn missing_key(key: &str) -> TNetStrError {
+        TNetStrError::MissingKey(key.to_string())
+    }
+
+    pub fn unknown_variant(name: &[u8]) -> TNetStrError {
+        TNetStrError::UnknownVariant(name.escape_ascii().to_string())
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+    use super::super::parse;
+    use alloc::vec;
+
+    #[test]
+    fn converts_std_types() {
+        let mut map = BTreeMap::new();
+        map.insert("b".to_string(), vec![Some(1u8), None]);
+        map.insert("a".to_string(), vec![]);
+        let encoded = to_tnetstring(&map).unwrap();
+        assert_eq!(encoded, b"21:1:a,0:]1:b,7:1:1#0:~]}");
+        assert_eq!(from_tnetstring::<BTreeMap<String, Vec<Option<u8>>>>(&encoded), Ok(map.clone()));
+        assert_eq!(BTreeMap::from_tnet(&parse(&encoded).unwrap()), Ok(map));
+
+        assert_eq!(to_tnetstring(&1.25f64), Ok(b"4:1.25^".to_vec()));
+        assert_eq!(to_tnetstring(&2.0f64), Ok(b"3:2.0^".to_vec()));
+        assert_eq!(from_tnetstring::<f64>(b"1:3#"), Ok(3.0));
+        assert_eq!(to_tnetstring("hi"), Ok(b"2:hi,".to_vec()));
+
+        let bytes = vec![Some(ByteBuf(b"\xff".to_vec())), None];
+        assert_eq!(to_tnetstring(&bytes), Ok(b"7:1:\xff,0:~]".to_vec()));
+        assert_eq!(from_tnetstring::<Vec<Option<ByteBuf>>>(b"7:1:\xff,0:~]"), Ok(bytes));
+    }
+
+    #[test]
+    fn reports_conversion_errors() {
+        assert_eq!(from_tnetstring::<u8>(b"3:300#"), Err(TNetStrError::IntegerOutOfRange));
+        assert_eq!(from_tnetstring::<bool>(b"1:1#"), Err(TNetStrError::UnexpectedDataType(b'!', b'#')));
+        assert_eq!(from_tnetstring::<String>(b"1:\xff,"), Err(TNetStrError::DataNotUTF8Compatible));
+        assert_eq!(u64::MAX.to_tnet(), Err(TNetStrError::IntegerOutOfRange));
+    }
+
+    #[test]
+    fn round_trips_floats() {
+        for n in [-1.5, 0.05, 3.01, 1e300, -0.0, 1e-7, f64::INFINITY, f64::NEG_INFINITY] {
+            let encoded = to_tnetstring(&n).unwrap();
+            assert_eq!(from_tnetstring::<f64>(&encoded), Ok(n));
+            assert_eq!(from_tnetstring::<f64>(&encoded).unwrap().is_sign_negative(), n.is_sign_negative());
+        }
+        assert!(from_tnetstring::<f64>(&to_tnetstring(&f64::NAN).unwrap()).unwrap().is_nan());
+        assert_eq!(to_tnetstring(&0.05f64), Ok(b"4:0.05^".to_vec()));
+        assert_eq!(to_tnetstring(&1e300f64), Ok(b"8:1.0e+300^".to_vec()));
+        assert_eq!(from_tnetstring::<f64>(b"4:0.05^"), Ok(0.05));
+        assert_eq!(from_tnetstring::<f32>(&to_tnetstring(&0.1f32).unwrap()), Ok(0.1));
+    }
+}
@@ -1,11 +1,12 @@
-use std::fmt::{
+use core::cmp::Ordering;
+use core::fmt::{
     Display,
     Formatter,
     Error
 };
 pub use super::errors::TNetStrError;
 
-#[derive(Debug, Eq, PartialEq, Hash)]
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
 pub enum DataType {
     Bytes,
     Integer,
@@ -13,12 +14,36 @@ pub enum DataType {
     Boolean,
     Null,
     Dictionary,
-    List
+    List,
+    /// A tag outside the spec, registered with `Extensions`
+    Extension(u8)
 }
 
+const EXTENSION_PREFIX: &[u8] = b"extension:";
+
+/// The name of a type, given by `DataType::name`. It is bytes rather than a `str` because an
+/// extension's tag may be any byte; `Display` escapes the bytes that are not printable ASCII.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub struct TypeName {
+    bytes: [u8; 11],
+    len: usize
+}
+
+impl TypeName {
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.bytes[..self.len]
+    }
+}
+
+impl Display for TypeName {
+    fn fmt(&self, f: &mut Formatter) -> Result<(), Error> {
+        write!(f, "{}", self.as_bytes().escape_ascii())
+    }
+}
 
 impl DataType {
 
+    /// The type with the tag `c` in the spec. Other tags are rejected; `Extensions` reads them.
     pub fn from_byte(c: &u8) -> Result<DataType, TNetStrError> {
         match c {
             b',' => Ok(DataType::Bytes),
@@ -28,7 +53,7 @@ impl DataType {
             b'~' => Ok(DataType::Null),
             b'}' => Ok(DataType::Dictionary),
             b']' => Ok(DataType::List),
-            _ => Err(TNetStrError::UnrecognizedDataType(c.clone()))
+            _ => Err(TNetStrError::UnrecognizedDataType(*c))
         }
     }
 
@@ -41,16 +66,112 @@ impl DataType {
             DataType::Null => b'~',
             DataType::Dictionary => b'}',
             DataType::List => b']',
+            DataType::Extension(tag) => *tag
         }
     }
 
     pub fn to_char(&self) -> char {
         self.to_byte() as char
     }
+
+    /// The position of the type in the ordering of values: null, boolean, integer, float, bytes,
+    /// list, dictionary, extensions
+    fn rank(&self) -> u8 {
+        match self {
+            DataType::Null => 0,
+            DataType::Boolean => 1,
+            DataType::Integer => 2,
+            DataType::Float => 3,
+            DataType::Bytes => 4,
+            DataType::List => 5,
+            DataType::Dictionary => 6,
+            DataType::Extension(_) => 7
+        }
+    }
+
+    /// Whether `tag` can be used for an extension: tags in the spec, digits and `:` cannot
+    pub(crate) fn is_extension_tag(tag: u8) -> bool {
+        DataType::from_byte(&tag).is_err() && !tag.is_ascii_digit() && tag != b':'
+    }
+
+    /// A lower case name for the type, for messages and schemas. An extension is named
+    /// `extension:` followed by its tag, such as `extension:;`.
+    pub fn name(&self) -> TypeName {
+        let name: &[u8] = match self {
+            DataType::Bytes => b"bytes",
+            DataType::Integer => b"integer",
+            DataType::Float => b"float",
+            DataType::Boolean => b"boolean",
+            DataType::Null => b"null",
+            DataType::Dictionary => b"dictionary",
+            DataType::List => b"list",
+            DataType::Extension(_) => EXTENSION_PREFIX
+        };
+        let mut type_name = TypeName {
+            bytes: [0; 11],
+            len: name.len()
+        };
+        type_name.bytes[..name.len()].copy_from_slice(name);
+        if let DataType::Extension(tag) = self {
+            type_name.bytes[name.len()] = *tag;
+            type_name.len += 1;
+        }
+        type_name
+    }
+
+    /// The type with the given name, as written by `name`
+    pub fn from_name(name: &[u8]) -> Option<DataType> {
+        match name {
+            b"bytes" => Some(DataType::Bytes),
+            b"integer" => Some(DataType::Integer),
+            b"float" => Some(DataType::Float),
+            b"boolean" => Some(DataType::Boolean),
+            b"null" => Some(DataType::Null),
+            b"dictionary" => Some(DataType::Dictionary),
+            b"list" => Some(DataType::List),
+            _ => match name.strip_prefix(EXTENSION_PREFIX) {
+                Some([tag]) if DataType::is_extension_tag(*tag) => Some(DataType::Extension(*tag)),
+                _ => None
+            }
+        }
+    }
+}
+
+/// Types are ordered null, boolean, integer, float, bytes, list, dictionary, then extensions by
+/// tag, the order in which values of different types sort
+impl Ord for DataType {
+    fn cmp(&self, other: &DataType) -> Ordering {
+        self.rank().cmp(&other.rank()).then_with(|| self.to_byte().cmp(&other.to_byte()))
+    }
+}
+
+impl PartialOrd for DataType {
+    fn partial_cmp(&self, other: &DataType) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
 }
 
 impl Display for DataType {
     fn fmt(&self, f: &mut Formatter) -> Result<(), Error> {
         write!(f, "{}", self.to_char())
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+
+    #[test]
+    fn reads_the_names_it_gives() {
+        let types = [DataType::Bytes, DataType::Integer, DataType::Float, DataType::Boolean, DataType::Null,
+            DataType::Dictionary, DataType::List, DataType::Extension(b';'), DataType::Extension(0xff)];
+        for t in types {
+            assert_eq!(DataType::from_name(t.name().as_bytes()), Some(t));
+        }
+        assert_eq!(DataType::Extension(b';').name().as_bytes(), b"extension:;");
+        assert_eq!(DataType::from_name(b"extension"), None);
+        assert_eq!(DataType::from_name(b"extension:,"), None);
+        assert_eq!(DataType::from_name(b"extension:;;"), None);
+    }
+}
@@ -0,0 +1,429 @@
+use super::{
+    TNetData,
+    TNetDictionary,
+    TNetEntry
+};
+use super::encode::encode;
+use alloc::{
+    string::String,
+    vec,
+    vec::Vec
+};
+use core::fmt::{
+    Display,
+    Error,
+    Formatter,
+    Write
+};
+use core::iter;
+use core::ops::Range;
+
+/// A step from a container to one of its values
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub enum PathSegment {
+    /// The value of a dictionary key. As keys may be repeated, `occurrence` counts the earlier
+    /// pairs with the same key (0 for the first).
+    Key { key: Vec<u8>, occurrence: usize },
+    /// An item of a list
+    Index(usize)
+}
+
+/// The location of a value within a tnetstring, from the outermost value inwards.
+/// It is displayed as `$` for the outermost value, followed by `["key"]` or `[index]` for each
+/// segment, and `#n` after a key for its nth repeat.
+#[derive(Debug, Clone, Default, Eq, PartialEq, Hash)]
+pub struct DiffPath(pub Vec<PathSegment>);
+
+impl Display for DiffPath {
+    fn fmt(&self, f: &mut Formatter) -> Result<(), Error> {
+        f.write_char('$')?;
+        for segment in &self.0 {
+            match segment {
+                PathSegment::Key { key, occurrence: 0 } => write!(f, "[\"{}\"]", key.escape_ascii())?,
+                PathSegment::Key { key, occurrence } => write!(f, "[\"{}\"]#{}", key.escape_ascii(), occurrence)?,
+                PathSegment::Index(i) => write!(f, "[{}]", i)?
+            }
+        }
+        Ok(())
+    }
+}
+
+/// How a value differs between the two entries given to `diff`
+#[derive(Debug, PartialEq)]
+pub enum ChangeKind<'a> {
+    /// A dictionary pair or list item only present in the new entry
+    Added(&'a TNetEntry),
+    /// A dictionary pair or list item only present in the old entry
+    Removed(&'a TNetEntry),
+    /// A scalar whose value differs, with the same type in both entries
+    Changed { old: &'a TNetEntry, new: &'a TNetEntry },
+    /// A value whose type differs
+    TypeChanged { old: &'a TNetEntry, new: &'a TNetEntry }
+}
+
+/// A single difference found by `diff`
+#[derive(Debug, PartialEq)]
+pub struct Change<'a> {
+    pub path: DiffPath,
+    pub kind: ChangeKind<'a>
+}
+
+/// Writes an entry's tnetstring with non-printable bytes escaped
+struct Encoded<'a>(&'a TNetEntry);
+
+impl Display for Encoded<'_> {
+    fn fmt(&self, f: &mut Formatter) -> Result<(), Error> {
+        write!(f, "{}", encode(self.0).escape_ascii())
+    }
+}
+
+impl Display for Change<'_> {
+    fn fmt(&self, f: &mut Formatter) -> Result<(), Error> {
+        match &self.kind {
+            ChangeKind::Added(value) => write!(f, "+ {}: {}", self.path, Encoded(value)),
+            ChangeKind::Removed(value) => write!(f, "- {}: {}", self.path, Encoded(value)),
+            ChangeKind::Changed { old, new } => write!(f, "~ {}: {} -> {}", self.path, Encoded(old), Encoded(new)),
+            ChangeKind::TypeChanged { old, new } => write!(f, "~ {}: {} -> {} ({} -> {})",
+                self.path, Encoded(old), Encoded(new), old.data.data_type().name(), new.data.data_type().name())
+        }
+    }
+}
+
+/// Renders changes one per line, for test failure messages
+pub fn render_diff(changes: &[Change]) -> String {
+    let mut output = String::new();
+    for change in changes {
+        writeln!(output, "{}", change).expect("Bug - writing to a String failed");
+    }
+    output
+}
+
+/// The values of a dictionary grouped by key, in the order each key first appears
+fn key_groups(dict: &TNetDictionary) -> Vec<(&[u8], Vec<&TNetEntry>)> {
+    let mut groups: Vec<(&[u8], Vec<&TNetEntry>)> = vec![];
+    for (key, value) in &dict.entries {
+        let key = key.data.as_bytes().unwrap_or(&[]);
+        match groups.iter_mut().find(|(k, _)| *k == key) {
+            Some((_, values)) => values.push(value),
+            None => groups.push((key, vec![value]))
+        }
+    }
+    groups
+}
+
+/// Whether two dictionaries have the same pairs regardless of their order, with the values of
+/// repeated keys compared in order of appearance
+pub(crate) fn equal_dictionaries(a: &TNetDictionary, b: &TNetDictionary) -> bool {
+    let (a, b) = (key_groups(a), key_groups(b));
+    a.len() == b.len() && a.iter().all(|(key, a_values)| match b.iter().find(|(k, _)| k == key) {
+        Some((_, b_values)) => a_values.len() == b_values.len()
+            && a_values.iter().zip(b_values).all(|(a, b)| equal(a, b)),
+        None => false
+    })
+}
+
+/// Whether two values have no differences, ignoring the `size` fields of their entries and the
+/// order of dictionary keys
+pub(crate) fn equal_data(a: &TNetData, b: &TNetData) -> bool {
+    match (a, b) {
+        (TNetData::List(a), TNetData::List(b)) => a.len() == b.len() && a.iter().zip(b).all(|(a, b)| equal(a, b)),
+        (TNetData::Dictionary(a), TNetData::Dictionary(b)) => equal_dictionaries(a, b),
+        (a, b) => a == b
+    }
+}
+
+/// Whether two entries have no differences, ignoring their `size` fields and the order of
+/// dictionary keys
+pub(crate) fn equal(a: &TNetEntry, b: &TNetEntry) -> bool {
+    equal_data(&a.data, &b.data)
+}
+
+/// Appends the index pairs of a longest common subsequence of `a` and `b`, in order, to
+/// `matches`, with `start` added to the indexes. Equal items at the start and end are matched
+/// directly, and the rest is split where the forward and reverse searches of Myers' algorithm
+/// meet, so only two arrays of the length of the lists are held and items are compared along the
+/// differences found rather than for every pair.
+fn common_subsequence(a: &[TNetEntry], b: &[TNetEntry], start: (usize, usize), matches: &mut Vec<(usize, usize)>) {
+    let prefix = a.iter().zip(b).take_while(|(a, b)| equal(a, b)).count();
+    matches.extend((0..prefix).map(|k| (start.0 + k, start.1 + k)));
+    let (a, b, start) = (&a[prefix..], &b[prefix..], (start.0 + prefix, start.1 + prefix));
+    let suffix = a.iter().rev().zip(b.iter().rev()).take_while(|(a, b)| equal(a, b)).count();
+    let (a, b) = (&a[..a.len() - suffix], &b[..b.len() - suffix]);
+    if !a.is_empty() && !b.is_empty() {
+        if let Some((x, y)) = middle(a, b) {
+            common_subsequence(&a[..x], &b[..y], start, matches);
+            common_subsequence(&a[x..], &b[y..], (start.0 + x, start.1 + y), matches);
+        }
+    }
+    matches.extend((0..suffix).map(|k| (start.0 + a.len() + k, start.1 + b.len() + k)));
+}
+
+/// Where a shortest edit script from `a` to `b` crosses its middle, found by searching forwards
+/// from the start and backwards from the end until the searches overlap, or `None` if the lists
+/// have no equal items
+fn middle(a: &[TNetEntry], b: &[TNetEntry]) -> Option<(usize, usize)> {
+    let (n, m) = (a.len() as isize, b.len() as isize);
+    let max = (n + m + 1) / 2;
+    let delta = n - m;
+    // forward[max + k] is the furthest x reached from the start on the diagonal k = x - y, and
+    // backward[max + k] the same from the end, with x and y counted back from the ends
+    let mut forward = vec![-1; 2 * max as usize + 2];
+    let mut backward = forward.clone();
+    forward[max as usize + 1] = 0;
+    backward[max as usize + 1] = 0;
+    let index = |k: isize| (max + k) as usize;
+    let diagonal = |k: isize| (-max..=max).contains(&k);
+    // diagonals at either edge that have run off the end of `a` or `b` are no longer searched
+    let (mut forward_start, mut forward_end, mut backward_start, mut backward_end) = (0, 0, 0, 0);
+    for d in 0..max {
+        for k in (-d + forward_start..=d - forward_end).step_by(2) {
+            let mut x = match k == -d || (k != d && forward[index(k - 1)] < forward[index(k + 1)]) {
+                true => forward[index(k + 1)],
+                false => forward[index(k - 1)] + 1
+            };
+            let mut y = x - k;
+            while x < n && y < m && equal(&a[x as usize], &b[y as usize]) {
+                x += 1;
+                y += 1;
+            }
+            forward[index(k)] = x;
+            if x > n {
+                forward_end += 2;
+            } else if y > m {
+                forward_start += 2;
+            } else if delta % 2 != 0 && diagonal(delta - k) && backward[index(delta - k)] != -1 && x >= n - backward[index(delta - k)] {
+                return Some((x as usize, y as usize))
+            }
+        }
+        for k in (-d + backward_start..=d - backward_end).step_by(2) {
+            let mut x = match k == -d || (k != d && backward[index(k - 1)] < backward[index(k + 1)]) {
+                true => backward[index(k + 1)],
+                false => backward[index(k - 1)] + 1
+            };
+            let mut y = x - k;
+            while x < n && y < m && equal(&a[(n - x - 1) as usize], &b[(m - y - 1) as usize]) {
+                x += 1;
+                y += 1;
+            }
+            backward[index(k)] = x;
+            if x > n {
+                backward_end += 2;
+            } else if y > m {
+                backward_start += 2;
+            } else if delta % 2 == 0 && diagonal(delta - k) && forward[index(delta - k)] != -1 {
+                let forward_x = forward[index(delta - k)];
+                if forward_x >= n - x {
+                    return Some((forward_x as usize, (forward_x - (delta - k)) as usize))
+                }
+            }
+        }
+    }
+    None
+}
+
+struct Differ<'a> {
+    path: Vec<PathSegment>,
+    changes: Vec<Change<'a>>
+}
+
+impl<'a> Differ<'a> {
+
+    fn push(&mut self, kind: ChangeKind<'a>) {
+        self.changes.push(Change {
+            path: DiffPath(self.path.clone()),
+            kind
+        });
+    }
+
+    fn at(&mut self, segment: PathSegment, kind: ChangeKind<'a>) {
+        self.path.push(segment);
+        self.push(kind);
+        self.path.pop();
+    }
+
+    fn within(&mut self, segment: PathSegment, a: &'a TNetEntry, b: &'a TNetEntry) {
+        self.path.push(segment);
+        self.entries(a, b);
+        self.path.pop();
+    }
+
+    fn entries(&mut self, a: &'a TNetEntry, b: &'a TNetEntry) {
+        match (&a.data, &b.data) {
+            (TNetData::Dictionary(da), TNetData::Dictionary(db)) => self.dictionaries(da, db),
+            (TNetData::List(la), TNetData::List(lb)) => self.lists(la, lb),
+            (da, db) if da.data_type() != db.data_type() => self.push(ChangeKind::TypeChanged { old: a, new: b }),
+            (da, db) if da != db => self.push(ChangeKind::Changed { old: a, new: b }),
+            _ => {}
+        }
+    }
+
+    fn dictionaries(&mut self, a: &'a TNetDictionary, b: &'a TNetDictionary) {
+        let (a_groups, b_groups) = (key_groups(a), key_groups(b));
+        let no_values = vec![];
+        for (key, a_values) in &a_groups {
+            let b_values = b_groups.iter().find(|(k, _)| k == key).map(|(_, v)| v).unwrap_or(&no_values);
+            for occurrence in 0..a_values.len().max(b_values.len()) {
+                let segment = PathSegment::Key { key: key.to_vec(), occurrence };
+                match (a_values.get(occurrence), b_values.get(occurrence)) {
+                    (Some(a), Some(b)) => self.within(segment, a, b),
+                    (Some(a), None) => self.at(segment, ChangeKind::Removed(a)),
+                    (None, Some(b)) => self.at(segment, ChangeKind::Added(b)),
+                    (None, None) => unreachable!()
+                }
+            }
+        }
+        for (key, b_values) in &b_groups {
+            if a_groups.iter().any(|(k, _)| k == key) {
+                continue
+            }
+            for (occurrence, b) in b_values.iter().enumerate() {
+                self.at(PathSegment::Key { key: key.to_vec(), occurrence }, ChangeKind::Added(b));
+            }
+        }
+    }
+
+    /// Aligns the lists on their longest common subsequence of equal items. Between aligned items,
+    /// removed and added items are paired up and compared, and any left over are reported as
+    /// removed or added.
+    ///
+    /// As in a JSON Patch, each index is that of the list once the earlier changes have been
+    /// applied to it, so applying the changes in order turns `a` into `b`. Paired and added items
+    /// are at their index in `b`, and a run of removed items are all at the index after the items
+    /// paired before them.
+    fn lists(&mut self, a: &'a [TNetEntry], b: &'a [TNetEntry]) {
+        let mut matches = vec![];
+        common_subsequence(a, b, (0, 0), &mut matches);
+        let (mut i, mut j) = (0, 0);
+        for (next_i, next_j) in matches.into_iter().chain(iter::once((a.len(), b.len()))) {
+            self.unaligned(a, b, i..next_i, j..next_j);
+            i = next_i + 1;
+            j = next_j + 1;
+        }
+    }
+
+    /// Reports a run of list items between aligned items, given by their indexes. The items of `b`
+    /// before the run are in place by the time it is reached.
+    fn unaligned(&mut self, a: &'a [TNetEntry], b: &'a [TNetEntry], removed: Range<usize>, added: Range<usize>) {
+        let paired = removed.len().min(added.len());
+        for k in 0..paired {
+            self.within(PathSegment::Index(added.start + k), &a[removed.start + k], &b[added.start + k]);
+        }
+        for i in removed.skip(paired) {
+            self.at(PathSegment::Index(added.start + paired), ChangeKind::Removed(&a[i]));
+        }
+        for j in added.skip(paired) {
+            self.at(PathSegment::Index(j), ChangeKind::Added(&b[j]));
+        }
+    }
+}
+
+/// Lists the differences between two entries, from `a` to `b`.
+///
+/// Dictionaries are compared by key regardless of the order of their pairs, with repeated keys
+/// matched up in order of appearance. Lists are aligned so that inserted and deleted items are
+/// reported as added and removed rather than as changes to every following item, at the index
+/// each has once the changes before it are applied, as in a JSON Patch. The `size` of
+/// entries is ignored, so hand built entries compare by their data.
+pub fn diff<'a>(a: &'a TNetEntry, b: &'a TNetEntry) -> Vec<Change<'a>> {
+    let mut differ = Differ {
+        path: vec![],
+        changes: vec![]
+    };
+    differ.entries(a, b);
+    differ.changes
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+    use super::super::{
+        apply_patch,
+        parse,
+        Patch,
+        PatchOperation
+    };
+
+    fn rendered(a: &[u8], b: &[u8]) -> String {
+        render_diff(&diff(&parse(a).unwrap(), &parse(b).unwrap()))
+    }
+
+    #[test]
+    fn equal_entries_have_no_changes() {
+        let a = parse(b"19:1:a,1:1#3:bbb,2:hi,}").unwrap();
+        let b = parse(b"19:3:bbb,2:hi,1:a,1:1#}").unwrap();
+        assert_eq!(diff(&a, &b), vec![]);
+    }
+
+    #[test]
+    fn reports_dictionary_changes_by_path() {
+        let output = rendered(
+            b"41:4:name,3:bob,3:age,2:30#4:meta,7:1:x,0:~}}",
+            b"43:4:name,3:bob,3:age,4:true!4:meta,7:1:y,0:~}}"
+        );
+        assert_eq!(output, "~ $[\"age\"]: 2:30# -> 4:true! (integer -> boolean)\n- $[\"meta\"][\"x\"]: 0:~\n+ $[\"meta\"][\"y\"]: 0:~\n");
+    }
+
+    #[test]
+    fn matches_duplicate_keys_in_order() {
+        let a = parse(b"16:1:k,1:1#1:k,1:2#}").unwrap();
+        let b = parse(b"24:1:k,1:1#1:k,1:3#1:k,1:4#}").unwrap();
+        let changes = diff(&a, &b);
+        assert_eq!(changes.len(), 2);
+        assert_eq!(changes[0].to_string(), "~ $[\"k\"]#1: 1:2# -> 1:3#");
+        assert_eq!(changes[1].to_string(), "+ $[\"k\"]#2: 1:4#");
+    }
+
+    #[test]
+    fn reports_list_insertions_and_deletions() {
+        let output = rendered(
+            b"20:1:a,1:b,1:c,1:d,1:e,]",
+            b"20:1:a,1:c,1:d,1:x,1:e,]"
+        );
+        assert_eq!(output, "- $[1]: 1:b,\n+ $[3]: 1:x,\n");
+    }
+
+    #[test]
+    fn aligns_long_lists_that_mostly_match() {
+        let list = |items: Vec<i64>| TNetEntry::from(TNetData::List(items.into_iter().map(|n| TNetEntry::from(TNetData::Integer(n))).collect()));
+        let a: Vec<i64> = (0..100_000).collect();
+        let mut b = a.clone();
+        b.remove(10);
+        b.insert(50_000, -1);
+        b[99_000] = -2;
+        let (a, b) = (list(a), list(b));
+        let changes: Vec<String> = diff(&a, &b).iter().map(Change::to_string).collect();
+        assert_eq!(changes, vec!["- $[10]: 2:10#", "+ $[50000]: 2:-1#", "~ $[99000]: 5:99000# -> 2:-2#"]);
+    }
+
+    #[test]
+    fn aligns_reordered_lists() {
+        assert_eq!(rendered(b"12:1:a,1:b,1:c,]", b"12:1:c,1:a,1:b,]"), "+ $[0]: 1:c,\n- $[3]: 1:c,\n");
+        assert_eq!(rendered(b"8:1:a,1:b,]", b"0:]"), "- $[0]: 1:a,\n- $[0]: 1:b,\n");
+        assert_eq!(rendered(b"0:]", b"4:1:a,]"), "+ $[0]: 1:a,\n");
+        assert_eq!(rendered(b"12:1:a,1:b,1:c,]", b"12:1:x,1:y,1:z,]"), "~ $[0]: 1:a, -> 1:x,\n~ $[1]: 1:b, -> 1:y,\n~ $[2]: 1:c, -> 1:z,\n");
+    }
+
+    #[test]
+    fn applies_as_a_patch() {
+        let list = |items: &[i64]| TNetEntry::from(TNetData::List(items.iter().map(|n| TNetEntry::from(TNetData::Integer(*n))).collect()));
+        let a = list(&[1, 2, 3, 4, 5, 6, 9, 10]);
+        let b = list(&[0, 1, 3, 7, 8, 4, 6, 11, 12, 13]);
+        let operations = diff(&a, &b).into_iter()
+            .map(|change| match change.kind {
+                ChangeKind::Added(value) => PatchOperation::Add { path: change.path, value: value.clone() },
+                ChangeKind::Removed(_) => PatchOperation::Remove { path: change.path },
+                ChangeKind::Changed { new, .. } | ChangeKind::TypeChanged { new, .. } => PatchOperation::Replace { path: change.path, value: new.clone() }
+            })
+            .collect();
+        let mut patched = a.clone();
+        apply_patch(&mut patched, &Patch { operations }).unwrap();
+        assert_eq!(diff(&patched, &b), vec![]);
+    }
+
+    #[test]
+    fn compares_replaced_list_items() {
+        let output = rendered(b"8:1:a,1:b,]", b"8:1:a,1:z,]");
+        assert_eq!(output, "~ $[1]: 1:b, -> 1:z,\n");
+    }
+}
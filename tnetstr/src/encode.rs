@@ -0,0 +1,210 @@
+use super::{
+    TNetEntry,
+    TNetStrError
+};
+use super::writer::{
+    encoded_len,
+    frame_len,
+    TNetWriter,
+    MAX_DATA_LENGTH
+};
+#[cfg(feature = "bytes")]
+use super::writer::ByteSink;
+use alloc::vec::Vec;
+#[cfg(feature = "bytes")]
+use bytes::BufMut;
+
+/// Adapts a `BufMut` to a `ByteSink`, as `BufMut::writer` needs `std`
+#[cfg(feature = "bytes")]
+struct BufMutSink<'a, B: BufMut + ?Sized>(&'a mut B);
+
+#[cfg(feature = "bytes")]
+impl<B: BufMut + ?Sized> ByteSink for BufMutSink<'_, B> {
+    fn put_bytes(&mut self, bytes: &[u8]) -> Result<(), TNetStrError> {
+        self.0.put_slice(bytes);
+        Ok(())
+    }
+}
+
+/// Appends the tnetstring encoding of an entry to `output`.
+/// Lengths are calculated from the entry's data, and the type tag is taken from the data rather
+/// than the entry's `data_type`, so entries built by hand do not need an accurate `size`.
+///
+/// # Panics
+///
+/// If the data of a value is longer than `MAX_DATA_LENGTH`; `try_encode_to` returns an error instead.
+pub fn encode_to(entry: &TNetEntry, output: &mut Vec<u8>) {
+    try_encode_to(entry, output).expect("Data was longer than the spec allows")
+}
+
+/// Encodes an entry as a tnetstring
+///
+/// # Panics
+///
+/// If the data of a value is longer than `MAX_DATA_LENGTH`; `try_encode` returns an error instead.
+pub fn encode(entry: &TNetEntry) -> Vec<u8> {
+    try_encode(entry).expect("Data was longer than the spec allows")
+}
+
+/// Appends the tnetstring encoding of an entry to `output`, as `encode_to` does. If the data of a
+/// value is longer than `MAX_DATA_LENGTH` nothing is written, and `LengthTooLong` is returned.
+pub fn try_encode_to(entry: &TNetEntry, output: &mut Vec<u8>) -> Result<(), TNetStrError> {
+    if encoded_len(entry) > frame_len(MAX_DATA_LENGTH) {
+        return Err(TNetStrError::LengthTooLong)
+    }
+    TNetWriter::new(output).write_entry(entry)
+}
+
+/// Encodes an entry as a tnetstring, returning `LengthTooLong` if the data of a value is longer
+/// than `MAX_DATA_LENGTH`
+pub fn try_encode(entry: &TNetEntry) -> Result<Vec<u8>, TNetStrError> {
+    let len = encoded_len(entry);
+    if len > frame_len(MAX_DATA_LENGTH) {
+        return Err(TNetStrError::LengthTooLong)
+    }
+    let mut output = Vec::with_capacity(len);
+    TNetWriter::new(&mut output).write_entry(entry)?;
+    Ok(output)
+}
+
+/// Encodes an entry into the start of `buffer` without allocating, returning the number of bytes
+/// written. If the buffer is too small nothing is written, and the error gives the length needed.
+pub fn encode_into(entry: &TNetEntry, buffer: &mut [u8]) -> Result<usize, TNetStrError> {
+    let needed = encoded_len(entry);
+    if buffer.len() < needed {
+        return Err(TNetStrError::BufferTooSmall { needed })
+    }
+    TNetWriter::new(&mut buffer[..needed]).write_data_measured(&entry.data)?;
+    Ok(needed)
+}
+
+/// Encodes an entry into a `BufMut`, returning the number of bytes written. If the buffer has too
+/// little remaining capacity nothing is written, and the error gives the length needed.
+/// Nothing is allocated by the encoder, but a growable buffer such as `BytesMut` reallocates as it
+/// is written unless `encoded_len` bytes are reserved first.
+#[cfg(feature = "bytes")]
+pub fn encode_into_buf<B: BufMut + ?Sized>(entry: &TNetEntry, buffer: &mut B) -> Result<usize, TNetStrError> {
+    let needed = encoded_len(entry);
+    if buffer.remaining_mut() < needed {
+        return Err(TNetStrError::BufferTooSmall { needed })
+    }
+    TNetWriter::new(BufMutSink(buffer)).write_data_measured(&entry.data)?;
+    Ok(needed)
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+    use super::super::{
+        parse,
+        DataType,
+        TNetData
+    };
+    use std::alloc::{
+        GlobalAlloc,
+        Layout,
+        System
+    };
+    use std::cell::Cell;
+
+    thread_local! {
+        static ALLOCATIONS: Cell<usize> = const { Cell::new(0) };
+    }
+
+    /// Counts the allocations made by each thread, so that tests can check nothing is allocated
+    struct CountingAllocator;
+
+    unsafe impl GlobalAlloc for CountingAllocator {
+        unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+            ALLOCATIONS.with(|count| count.set(count.get() + 1));
+            System.alloc(layout)
+        }
+
+        unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+            ALLOCATIONS.with(|count| count.set(count.get() + 1));
+            System.alloc_zeroed(layout)
+        }
+
+        unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+            System.dealloc(ptr, layout)
+        }
+    }
+
+    #[global_allocator]
+    static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+    fn allocations() -> usize {
+        ALLOCATIONS.with(Cell::get)
+    }
+
+    #[test]
+    fn round_trips_parsed_entries() {
+        let inputs: Vec<&[u8]> = vec![
+            b"0:~", b"3:123#", b"2:-5#", b"6:12.543^", b"4:true!", b"5:false!", b"3:a\xffb,",
+            b"24:4:true!6:0.4529^5:abcde,]", b"19:1:a,1:1#3:bbb,2:hi,}", b"0:]", b"0:}"
+        ];
+        for input in inputs {
+            assert_eq!(encode(&parse(input).unwrap()), input);
+        }
+    }
+
+    #[test]
+    fn calculates_lengths_from_data() {
+        let entry = TNetEntry {
+            size: 0,
+            data_type: DataType::Null,
+            data: TNetData::List(vec![TNetEntry {
+                size: 0,
+                data_type: DataType::Null,
+                data: TNetData::Bytes(b"hello".to_vec())
+            }])
+        };
+        assert_eq!(encode(&entry), b"8:5:hello,]");
+    }
+
+    #[test]
+    fn rejects_data_above_the_spec_maximum() {
+        // zeroed pages are not touched, so this does not use a gigabyte of memory
+        let entry = TNetEntry::from(TNetData::List(vec![TNetEntry::from(TNetData::Bytes(vec![0; MAX_DATA_LENGTH]))]));
+        let mut output = b"0:~".to_vec();
+        assert_eq!(try_encode_to(&entry, &mut output), Err(TNetStrError::LengthTooLong));
+        assert_eq!(output, b"0:~");
+        assert_eq!(try_encode(&entry), Err(TNetStrError::LengthTooLong));
+        let mut buffer = [0; 16];
+        assert_eq!(encode_into(&entry, &mut buffer), Err(TNetStrError::BufferTooSmall { needed: encoded_len(&entry) }));
+    }
+
+    #[test]
+    fn encodes_into_slice() {
+        let input = b"24:4:true!6:0.4529^5:abcde,]";
+        let entry = parse(input).unwrap();
+        let mut buffer = [0; 32];
+        assert_eq!(encode_into(&entry, &mut buffer), Ok(input.len()));
+        assert_eq!(&buffer[..input.len()], &input[..]);
+        assert_eq!(encode_into(&entry, &mut buffer[..27]), Err(TNetStrError::BufferTooSmall { needed: 28 }));
+    }
+
+    #[test]
+    fn encodes_into_slice_without_allocating() {
+        let input = b"42:4:meta,18:4:tags,8:1:a,1:b,]]4:list,3:0:]]}";
+        let entry = parse(input).unwrap();
+        let mut buffer = [0; 64];
+        let before = allocations();
+        assert_eq!(encode_into(&entry, &mut buffer), Ok(input.len()));
+        assert_eq!(allocations(), before);
+        assert_eq!(&buffer[..input.len()], &input[..]);
+    }
+
+    #[cfg(feature = "bytes")]
+    #[test]
+    fn encodes_into_buf_mut() {
+        let input = b"19:1:a,1:1#3:bbb,2:hi,}";
+        let entry = parse(input).unwrap();
+        let mut buffer = bytes::BytesMut::with_capacity(64);
+        assert_eq!(encode_into_buf(&entry, &mut buffer), Ok(input.len()));
+        assert_eq!(&buffer[..], &input[..]);
+        let mut small = [0; 4];
+        assert_eq!(encode_into_buf(&entry, &mut &mut small[..]), Err(TNetStrError::BufferTooSmall { needed: 23 }));
+    }
+}
@@ -1,9 +1,14 @@
-use std::{
-    error::Error,
-    fmt::{Formatter, Display}
-};
+#[cfg(feature = "alloc")]
+use alloc::string::String;
+#[cfg(feature = "std")]
+use alloc::format;
+use core::fmt::{Formatter, Display};
 
+/// The errors for reading and writing tnetstrings. Every variant that carries a `String` needs the
+/// `alloc` feature, so the enum is non-exhaustive: a match that handles it with a wildcard arm
+/// compiles whichever features are enabled.
 #[derive(Debug, Eq, PartialEq)]
+#[non_exhaustive]
 pub enum TNetStrError {
     UnrecognizedDataType(u8),
     LengthTooLong,
@@ -18,14 +23,48 @@ pub enum TNetStrError {
     DataNotUTF8Compatible,
     UnbalancedDictionary,
     DictionaryKeyWasNotBytes,
+    #[cfg(feature = "alloc")]
     FloatParseError(String),
+    #[cfg(feature = "alloc")]
     StreamReadFailed(String),
-    SerializationError(String)
+    /// Streamed data could not be written to its sink
+    #[cfg(feature = "alloc")]
+    StreamWriteFailed(String),
+    #[cfg(feature = "alloc")]
+    SerializationError(String),
+    /// A path did not lead to a value
+    #[cfg(feature = "alloc")]
+    PathNotFound(String),
+    /// A patch's test operation found a different value at a path
+    #[cfg(feature = "alloc")]
+    PatchTestFailed(String),
+    #[cfg(feature = "alloc")]
+    InvalidPatch(String),
+    /// Values of different types were found at a path while merging
+    #[cfg(feature = "alloc")]
+    MergeConflict(String),
+    #[cfg(feature = "alloc")]
+    InvalidSchema(String),
+    /// A dictionary converted with `FromTNet` did not have a required key
+    #[cfg(feature = "alloc")]
+    MissingKey(String),
+    /// An enum converted with `FromTNet` had no variant with the name found
+    #[cfg(feature = "alloc")]
+    UnknownVariant(String),
+    /// An integer did not fit in the type it was converted to or from
+    IntegerOutOfRange,
+    FrameTooLong(usize, usize),
+    UnexpectedDataType(u8, u8),
+    BufferTooSmall { needed: usize },
+    /// The input nested containers deeper than a fixed capacity parser supports
+    NestingTooDeep(usize),
+    /// An extension was registered for a byte that is a standard type tag or part of a length
+    InvalidExtensionTag(u8)
 }
 
 impl Display for TNetStrError {
 
-    fn fmt(&self, f: &mut Formatter) -> Result<(), std::fmt::Error> {
+    fn fmt(&self, f: &mut Formatter) -> Result<(), core::fmt::Error> {
         match self {
             TNetStrError::UnrecognizedDataType(c) => write!(f, "No data type match char '{:?}'", *c as char),
             TNetStrError::LengthTooLong => write!(f, "The length specified was too long"),
@@ -39,14 +78,47 @@ impl Display for TNetStrError {
             TNetStrError::DataNotUTF8Compatible => write!(f, "Input of non-raw byte data was not utf-8 compatible."),
             TNetStrError::UnbalancedDictionary => write!(f, "A dictionary value contained an uneven number of entries."),
             TNetStrError::DictionaryKeyWasNotBytes => write!(f, "A dictionary key was found that was not of the 'Bytes' type."),
+            #[cfg(feature = "alloc")]
             TNetStrError::FloatParseError(s) => write!(f, "Failed to parse float '{}.", s),
+            #[cfg(feature = "alloc")]
             TNetStrError::StreamReadFailed(s) => write!(f, "Failed to read stream '{}.", s),
+            #[cfg(feature = "alloc")]
+            TNetStrError::StreamWriteFailed(s) => write!(f, "Failed to write streamed data '{}.", s),
             TNetStrError::LengthWasNotAccurate => write!(f, "The length specified was beyond the end of the data"),
-            TNetStrError::SerializationError(s) => write!(f, "{}", s)
+            #[cfg(feature = "alloc")]
+            TNetStrError::SerializationError(s) => write!(f, "{}", s),
+            #[cfg(feature = "alloc")]
+            TNetStrError::PathNotFound(path) => write!(f, "No value was found at {}", path),
+            #[cfg(feature = "alloc")]
+            TNetStrError::PatchTestFailed(path) => write!(f, "The value at {} did not match the patch's test", path),
+            #[cfg(feature = "alloc")]
+            TNetStrError::InvalidPatch(s) => write!(f, "Invalid patch: {}", s),
+            #[cfg(feature = "alloc")]
+            TNetStrError::MergeConflict(path) => write!(f, "The values at {} had different types and could not be merged", path),
+            #[cfg(feature = "alloc")]
+            TNetStrError::InvalidSchema(s) => write!(f, "Invalid schema: {}", s),
+            #[cfg(feature = "alloc")]
+            TNetStrError::MissingKey(key) => write!(f, "The dictionary had no '{}' key", key),
+            #[cfg(feature = "alloc")]
+            TNetStrError::UnknownVariant(name) => write!(f, "No variant is named '{}'", name),
+            TNetStrError::IntegerOutOfRange => write!(f, "The integer was out of range for its type"),
+            TNetStrError::FrameTooLong(length, max) => write!(f, "The tnetstring was {} bytes long, longer than the maximum of {}", length, max),
+            TNetStrError::UnexpectedDataType(expected, actual) => write!(f, "Expected data type '{}' but found '{}'", *expected as char, *actual as char),
+            TNetStrError::BufferTooSmall { needed } => write!(f, "The buffer was too small, {} bytes are needed", needed),
+            TNetStrError::NestingTooDeep(max) => write!(f, "Containers were nested more than {} deep", max),
+            TNetStrError::InvalidExtensionTag(c) => write!(f, "'{}' cannot be used as an extension type tag", *c as char)
         }
     }
 }
 
+#[cfg(feature = "std")]
 impl std::error::Error for TNetStrError {
 
+}
+
+#[cfg(feature = "std")]
+impl From<std::io::Error> for TNetStrError {
+    fn from(e: std::io::Error) -> TNetStrError {
+        TNetStrError::StreamReadFailed(format!("{:?}", e))
+    }
 }
\ No newline at end of file
@@ -0,0 +1,483 @@
+use super::{
+    DataType,
+    TNetFloat,
+    TNetStrError
+};
+use super::parse::{
+    parse_frame,
+    parse_float,
+    parse_val,
+    validate
+};
+#[cfg(feature = "std")]
+use super::parse::read_frame;
+#[cfg(feature = "alloc")]
+use alloc::vec::Vec;
+#[cfg(feature = "std")]
+use alloc::vec;
+#[cfg(feature = "std")]
+use std::io::Read;
+
+/// A single step through a tnetstring, as produced by the pull parsers in this module.
+/// Events are produced in document order, so a dictionary appears as `StartDict`, then a `Key`
+/// followed by the events of its value for each entry, then `End`.
+#[derive(Debug, PartialEq)]
+pub enum TNetEvent<'a> {
+    /// The start of a dictionary whose data is `len` bytes long
+    StartDict { len: u32 },
+    /// The start of a list whose data is `len` bytes long
+    StartList { len: u32 },
+    /// A dictionary key
+    Key(&'a [u8]),
+    Bytes(&'a [u8]),
+    Integer(i64),
+    Float(TNetFloat),
+    Boolean(bool),
+    Null,
+    /// The end of the most recently started dictionary or list
+    End
+}
+
+#[derive(Clone, Copy)]
+struct Container {
+    /// The position of the container's type tag, which immediately follows its data
+    end: usize,
+    is_dict: bool,
+    expect_key: bool
+}
+
+/// Storage for the containers a pull parser is inside, innermost last
+trait ContainerStack {
+    fn push(&mut self, container: Container) -> Result<(), TNetStrError>;
+    fn pop(&mut self) -> Option<Container>;
+    fn last_mut(&mut self) -> Option<&mut Container>;
+    fn is_empty(&self) -> bool;
+}
+
+#[cfg(feature = "alloc")]
+impl ContainerStack for Vec<Container> {
+
+    fn push(&mut self, container: Container) -> Result<(), TNetStrError> {
+        Vec::push(self, container);
+        Ok(())
+    }
+
+    fn pop(&mut self) -> Option<Container> {
+        Vec::pop(self)
+    }
+
+    fn last_mut(&mut self) -> Option<&mut Container> {
+        <[Container]>::last_mut(self)
+    }
+
+    fn is_empty(&self) -> bool {
+        <[Container]>::is_empty(self)
+    }
+}
+
+/// A stack of at most `N` containers, stored inline
+struct FixedStack<const N: usize> {
+    containers: [Container; N],
+    len: usize
+}
+
+impl<const N: usize> Default for FixedStack<N> {
+    fn default() -> FixedStack<N> {
+        FixedStack {
+            containers: [Container { end: 0, is_dict: false, expect_key: false }; N],
+            len: 0
+        }
+    }
+}
+
+impl<const N: usize> ContainerStack for FixedStack<N> {
+
+    fn push(&mut self, container: Container) -> Result<(), TNetStrError> {
+        if self.len == N {
+            return Err(TNetStrError::NestingTooDeep(N))
+        }
+        self.containers[self.len] = container;
+        self.len += 1;
+        Ok(())
+    }
+
+    fn pop(&mut self) -> Option<Container> {
+        if self.len == 0 {
+            return None
+        }
+        self.len -= 1;
+        Some(self.containers[self.len])
+    }
+
+    fn last_mut(&mut self) -> Option<&mut Container> {
+        self.containers[..self.len].last_mut()
+    }
+
+    fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+}
+
+/// The position of a pull parser within a single tnetstring.
+#[derive(Default)]
+struct EventState<S: ContainerStack> {
+    position: usize,
+    containers: S,
+    started: bool,
+    done: bool
+}
+
+impl<S: ContainerStack> EventState<S> {
+
+    fn next<'a>(&mut self, input: &'a [u8]) -> Option<Result<TNetEvent<'a>, TNetStrError>> {
+        if self.done {
+            return None
+        }
+        match self.step(input) {
+            Ok(event) => event.map(Ok),
+            Err(e) => {
+                self.done = true;
+                Some(Err(e))
+            }
+        }
+    }
+
+    fn step<'a>(&mut self, input: &'a [u8]) -> Result<Option<TNetEvent<'a>>, TNetStrError> {
+        let (limit, is_key) = match self.containers.last_mut() {
+            Some(container) => {
+                if self.position >= container.end {
+                    self.position = container.end + 1;
+                    self.containers.pop();
+                    self.done = self.containers.is_empty();
+                    return Ok(Some(TNetEvent::End))
+                }
+                let is_key = container.is_dict && container.expect_key;
+                if container.is_dict {
+                    container.expect_key = !container.expect_key;
+                }
+                (container.end, is_key)
+            },
+            None if self.started => {
+                self.done = true;
+                return Ok(None)
+            },
+            None => {
+                self.started = true;
+                (input.len(), false)
+            }
+        };
+
+        let start = self.position;
+        let (t, data) = parse_frame(&input[..limit], &mut self.position)?;
+
+        if is_key {
+            // as in `parse`, a key that is not bytes is read whole, then its value, before its
+            // type is rejected, so that both report the same error for the same input
+            if t != DataType::Bytes {
+                validate(&input[start..self.position])?;
+            }
+            if self.position >= limit {
+                return Err(TNetStrError::UnbalancedDictionary)
+            }
+            if t != DataType::Bytes {
+                validate(&input[self.position..limit])?;
+                return Err(TNetStrError::DictionaryKeyWasNotBytes)
+            }
+            return Ok(Some(TNetEvent::Key(data)))
+        }
+
+        let event = match t {
+            DataType::Bytes => TNetEvent::Bytes(data),
+            DataType::Null => TNetEvent::Null,
+            DataType::Integer => TNetEvent::Integer(parse_val(data)?),
+            DataType::Float => TNetEvent::Float(parse_float(data)?),
+            DataType::Boolean => TNetEvent::Boolean(parse_val(data)?),
+            DataType::Extension(tag) => return Err(TNetStrError::UnrecognizedDataType(tag)),
+            DataType::Dictionary | DataType::List => {
+                let is_dict = t == DataType::Dictionary;
+                let end = self.position - 1;
+                self.containers.push(Container {
+                    end,
+                    is_dict,
+                    expect_key: true
+                })?;
+                // step back inside the container so its children are read next
+                self.position = end - data.len();
+                let len = data.len() as u32;
+                return Ok(Some(if is_dict { TNetEvent::StartDict { len } } else { TNetEvent::StartList { len } }))
+            }
+        };
+
+        self.done = self.containers.is_empty();
+        Ok(Some(event))
+    }
+}
+
+/// A pull parser that yields the events of the first tnetstring in a slice of bytes,
+/// without building any `TNetEntry` values.
+/// Once an error has been returned the parser yields nothing further.
+#[cfg(feature = "alloc")]
+pub struct EventParser<'a> {
+    input: &'a [u8],
+    state: EventState<Vec<Container>>
+}
+
+#[cfg(feature = "alloc")]
+impl<'a> EventParser<'a> {
+
+    pub fn new(input: &'a [u8]) -> EventParser<'a> {
+        EventParser {
+            input,
+            state: EventState::default()
+        }
+    }
+
+    /// The number of bytes of the input consumed so far
+    pub fn position(&self) -> usize {
+        self.state.position
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<'a> Iterator for EventParser<'a> {
+    type Item = Result<TNetEvent<'a>, TNetStrError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.state.next(self.input)
+    }
+}
+
+/// Returns a pull parser over the events of the first tnetstring in a slice of bytes
+#[cfg(feature = "alloc")]
+pub fn parse_events(data: &[u8]) -> EventParser<'_> {
+    EventParser::new(data)
+}
+
+/// A pull parser like `EventParser` that keeps its state inline instead of on the heap, so it can
+/// be used without `alloc`. Containers can be nested at most `DEPTH` deep; deeper input fails with
+/// `TNetStrError::NestingTooDeep`. Without `alloc` invalid float text is reported as
+/// `CouldNotParseData`, as the text cannot be kept in the error.
+pub struct FixedEventParser<'a, const DEPTH: usize> {
+    input: &'a [u8],
+    state: EventState<FixedStack<DEPTH>>
+}
+
+impl<'a, const DEPTH: usize> FixedEventParser<'a, DEPTH> {
+
+    pub fn new(input: &'a [u8]) -> FixedEventParser<'a, DEPTH> {
+        FixedEventParser {
+            input,
+            state: EventState::default()
+        }
+    }
+
+    /// The number of bytes of the input consumed so far
+    pub fn position(&self) -> usize {
+        self.state.position
+    }
+}
+
+impl<'a, const DEPTH: usize> Iterator for FixedEventParser<'a, DEPTH> {
+    type Item = Result<TNetEvent<'a>, TNetStrError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.state.next(self.input)
+    }
+}
+
+/// A pull parser that yields the events of each successive tnetstring in a byte stream.
+///
+/// The type of a tnetstring is only known once all of its data has been read, so each top
+/// level tnetstring is read into an internal buffer (which is reused between tnetstrings)
+/// before its events are produced. No `TNetEntry` values are built.
+/// Once an error has been returned the parser yields nothing further.
+#[cfg(feature = "std")]
+pub struct StreamEventParser<R: Read> {
+    reader: R,
+    buffer: Vec<u8>,
+    state: EventState<Vec<Container>>,
+    failed: bool
+}
+
+#[cfg(feature = "std")]
+impl<R: Read> StreamEventParser<R> {
+
+    pub fn new(reader: R) -> StreamEventParser<R> {
+        StreamEventParser {
+            reader,
+            buffer: vec![],
+            state: EventState {
+                done: true,
+                ..EventState::default()
+            },
+            failed: false
+        }
+    }
+
+    /// Returns the next event, or `None` once the stream has ended between tnetstrings.
+    /// A top level tnetstring is complete when its scalar event, or the `End` of its outermost
+    /// container, has been returned.
+    pub fn next_event(&mut self) -> Option<Result<TNetEvent<'_>, TNetStrError>> {
+        if self.failed {
+            return None
+        }
+        if self.state.done {
+            match read_frame(&mut self.reader, &mut self.buffer) {
+                Ok(true) => self.state = EventState::default(),
+                Ok(false) => return None,
+                Err(e) => {
+                    self.failed = true;
+                    return Some(Err(e))
+                }
+            }
+        }
+        let event = self.state.next(&self.buffer);
+        if let Some(Err(_)) = event {
+            self.failed = true;
+        }
+        event
+    }
+
+    /// Returns the underlying reader
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+}
+
+#[cfg(all(test, feature = "alloc"))]
+mod test {
+
+    use super::*;
+
+    fn events(input: &[u8]) -> Vec<TNetEvent<'_>> {
+        parse_events(input).collect::<Result<Vec<_>, _>>().unwrap()
+    }
+
+    #[test]
+    fn yields_single_scalar() {
+        assert_eq!(events(b"3:123#"), vec![TNetEvent::Integer(123)]);
+        assert_eq!(events(b"0:~"), vec![TNetEvent::Null]);
+        assert_eq!(events(b"5:abcde,"), vec![TNetEvent::Bytes(b"abcde")]);
+    }
+
+    #[test]
+    fn yields_nested_events_in_order() {
+        let input = b"40:4:meta,15:2:id,1:7#0:,0:]}4:flag,4:true!}";
+        assert_eq!(events(input), vec![
+            TNetEvent::StartDict { len: 40 },
+            TNetEvent::Key(b"meta"),
+            TNetEvent::StartDict { len: 15 },
+            TNetEvent::Key(b"id"),
+            TNetEvent::Integer(7),
+            TNetEvent::Key(b""),
+            TNetEvent::StartList { len: 0 },
+            TNetEvent::End,
+            TNetEvent::End,
+            TNetEvent::Key(b"flag"),
+            TNetEvent::Boolean(true),
+            TNetEvent::End
+        ]);
+    }
+
+    #[test]
+    fn yields_list_events() {
+        let input = b"24:4:true!6:0.4529^5:abcde,]";
+        assert_eq!(events(input), vec![
+            TNetEvent::StartList { len: 24 },
+            TNetEvent::Boolean(true),
+            TNetEvent::Float(TNetFloat { integral: 0, fractional: 4529, leading_zeros: 0, ..TNetFloat::default() }),
+            TNetEvent::Bytes(b"abcde"),
+            TNetEvent::End
+        ]);
+    }
+
+    #[test]
+    fn ignores_trailing_input() {
+        let mut parser = parse_events(b"1:a,1:b,");
+        assert_eq!(parser.next(), Some(Ok(TNetEvent::Bytes(b"a"))));
+        assert_eq!(parser.next(), None);
+        assert_eq!(parser.position(), 4);
+    }
+
+    fn expect_error(input: &[u8], expected: TNetStrError) {
+        let result = parse_events(input).collect::<Result<Vec<_>, _>>();
+        assert_eq!(result, Err(expected));
+    }
+
+    #[test]
+    fn reports_same_errors_as_parse() {
+        expect_error(b":abc,", TNetStrError::NoLengthSpecified);
+        expect_error(b"2:a,", TNetStrError::LengthWasNotAccurate);
+        expect_error(b"4:1:a,}", TNetStrError::UnbalancedDictionary);
+        expect_error(b"8:1:1#1:a,}", TNetStrError::DictionaryKeyWasNotBytes);
+        expect_error(b"3:abc#", TNetStrError::CouldNotParseData);
+        expect_error(b"6:9:abc,]", TNetStrError::LengthWasNotAccurate);
+    }
+
+    #[test]
+    fn checks_keys_in_the_same_order_as_parse() {
+        for input in [
+            &b"9:1:1#5:ab,}"[..],
+            &b"10:3:abc#1:a,}"[..],
+            &b"6:3:abc#}"[..],
+            &b"10:4:1:a,]0:~}"[..],
+            &b"11:4:1:a,]1:x#}"[..],
+            &b"4:1:1#}"[..]
+        ] {
+            let expected = super::super::parse(input).unwrap_err();
+            assert_eq!(parse_events(input).collect::<Result<Vec<_>, _>>(), Err(expected), "{:?}", input.escape_ascii().to_string());
+        }
+    }
+
+    #[test]
+    fn stops_after_error() {
+        let mut parser = parse_events(b"8:1:x,5:a,]");
+        assert_eq!(parser.next(), Some(Ok(TNetEvent::StartList { len: 8 })));
+        assert_eq!(parser.next(), Some(Ok(TNetEvent::Bytes(b"x"))));
+        assert!(parser.next().unwrap().is_err());
+        assert_eq!(parser.next(), None);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn reads_successive_tnetstrings_from_stream() {
+        let input: &[u8] = b"8:1:a,1:b,]3:123#";
+        let mut parser = StreamEventParser::new(input);
+        assert_eq!(parser.next_event(), Some(Ok(TNetEvent::StartList { len: 8 })));
+        assert_eq!(parser.next_event(), Some(Ok(TNetEvent::Bytes(b"a"))));
+        assert_eq!(parser.next_event(), Some(Ok(TNetEvent::Bytes(b"b"))));
+        assert_eq!(parser.next_event(), Some(Ok(TNetEvent::End)));
+        assert_eq!(parser.next_event(), Some(Ok(TNetEvent::Integer(123))));
+        assert_eq!(parser.next_event(), None);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn stream_reports_truncated_input() {
+        let input: &[u8] = b"8:1:a,";
+        let mut parser = StreamEventParser::new(input);
+        match parser.next_event() {
+            Some(Err(TNetStrError::StreamReadFailed(_))) => {},
+            other => panic!("Expected read failure, got {:?}", other)
+        }
+        assert_eq!(parser.next_event(), None);
+    }
+
+    #[test]
+    fn fixed_parser_matches_event_parser() {
+        let input = b"37:4:name,5:hello,4:tags,11:1:a,4:1:b,]]}";
+        let fixed = FixedEventParser::<3>::new(input).collect::<Result<Vec<_>, _>>().unwrap();
+        assert_eq!(fixed, events(input));
+        let mut parser = FixedEventParser::<0>::new(b"3:123#");
+        assert_eq!(parser.next(), Some(Ok(TNetEvent::Integer(123))));
+        assert_eq!(parser.position(), 6);
+    }
+
+    #[test]
+    fn fixed_parser_rejects_deep_nesting() {
+        let mut parser = FixedEventParser::<1>::new(b"3:0:]]");
+        assert_eq!(parser.next(), Some(Ok(TNetEvent::StartList { len: 3 })));
+        assert_eq!(parser.next(), Some(Err(TNetStrError::NestingTooDeep(1))));
+        assert_eq!(parser.next(), None);
+    }
+}
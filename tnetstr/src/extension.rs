@@ -0,0 +1,180 @@
+use super::{
+    DataType,
+    TNetData,
+    TNetDictionary,
+    TNetEntry,
+    TNetStrError
+};
+use super::encode::encode;
+use super::parse::parse_with;
+use alloc::{
+    sync::Arc,
+    vec::Vec
+};
+
+/// Reads the payload of an extension as data
+pub type Decoder = Arc<dyn Fn(&[u8]) -> Result<TNetData, TNetStrError> + Send + Sync>;
+
+/// Gives the payload to write data with an extension's tag, or `None` if the data is written as
+/// usual
+pub type Encoder = Arc<dyn Fn(&TNetData) -> Option<Vec<u8>> + Send + Sync>;
+
+#[derive(Clone)]
+struct Extension {
+    tag: u8,
+    decoder: Option<Decoder>,
+    encoder: Option<Encoder>
+}
+
+/// A set of type tags outside the spec, for reading and writing dialects such as mitmproxy's,
+/// which writes unicode strings with a `;` tag.
+///
+/// Data with a registered tag is read as `TNetData::Extension`, or by the tag's decoder if it has
+/// one, and entries read by a decoder are sized from the data it gives. Dictionary keys must
+/// still be bytes, so a tag that is used for keys needs a decoder that gives bytes.
+/// `TNetData::Extension` is always written with its own tag, and `encode` also offers every other
+/// value to the encoders in the order they were registered.
+#[derive(Clone, Default)]
+pub struct Extensions {
+    extensions: Vec<Extension>
+}
+
+impl Extensions {
+
+    pub const fn new() -> Extensions {
+        Extensions {
+            extensions: Vec::new()
+        }
+    }
+
+    /// Registers `tag`, replacing any extension already registered for it. Tags in the spec,
+    /// digits and `:` cannot be registered.
+    pub fn register(&mut self, tag: u8, decoder: Option<Decoder>, encoder: Option<Encoder>) -> Result<(), TNetStrError> {
+        if !DataType::is_extension_tag(tag) {
+            return Err(TNetStrError::InvalidExtensionTag(tag))
+        }
+        self.extensions.retain(|extension| extension.tag != tag);
+        self.extensions.push(Extension { tag, decoder, encoder });
+        Ok(())
+    }
+
+    fn get(&self, tag: u8) -> Option<&Extension> {
+        self.extensions.iter().find(|extension| extension.tag == tag)
+    }
+
+    /// The type of data with the type tag `c`
+    pub fn data_type(&self, c: &u8) -> Result<DataType, TNetStrError> {
+        DataType::from_byte(c).or_else(|e| match self.get(*c) {
+            Some(_) => Ok(DataType::Extension(*c)),
+            None => Err(e)
+        })
+    }
+
+    /// Reads the payload of data with a registered tag
+    pub fn decode(&self, tag: u8, payload: &[u8]) -> Result<TNetData, TNetStrError> {
+        match self.get(tag) {
+            None => Err(TNetStrError::UnrecognizedDataType(tag)),
+            Some(Extension { decoder: Some(decoder), .. }) => decoder(payload),
+            Some(_) => Ok(TNetData::Extension { tag, payload: payload.to_vec() })
+        }
+    }
+
+    /// Attempts to read the first tnetstring from a slice of bytes, accepting the registered tags
+    pub fn parse(&self, data: &[u8]) -> Result<TNetEntry, TNetStrError> {
+        parse_with(data, self)
+    }
+
+    /// The data, and everything in it, with the values that an encoder applies to replaced by
+    /// extension data
+    fn apply_encoders(&self, data: &TNetData) -> TNetData {
+        let encoded = self.extensions.iter()
+            .find_map(|extension| extension.encoder.as_ref().and_then(|encoder| encoder(data)).map(|payload| (extension.tag, payload)));
+        if let Some((tag, payload)) = encoded {
+            return TNetData::Extension { tag, payload }
+        }
+        let entry = |entry: &TNetEntry| TNetEntry::from(self.apply_encoders(&entry.data));
+        match data {
+            TNetData::List(l) => TNetData::List(l.iter().map(entry).collect()),
+            TNetData::Dictionary(dict) => TNetData::Dictionary(TNetDictionary {
+                entries: dict.entries.iter().map(|(key, value)| (entry(key), entry(value))).collect()
+            }),
+            data => data.clone()
+        }
+    }
+
+    /// Encodes an entry as a tnetstring, writing the values that an encoder applies to with its tag
+    pub fn encode(&self, entry: &TNetEntry) -> Vec<u8> {
+        match self.extensions.iter().any(|extension| extension.encoder.is_some()) {
+            true => encode(&TNetEntry::from(self.apply_encoders(&entry.data))),
+            false => encode(entry)
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+    use super::super::parse;
+
+    /// mitmproxy's unicode strings
+    fn string_dialect() -> Extensions {
+        let mut extensions = Extensions::new();
+        extensions.register(b';',
+            Some(Arc::new(|payload: &[u8]| match core::str::from_utf8(payload) {
+                Ok(_) => Ok(TNetData::Bytes(payload.to_vec())),
+                Err(_) => Err(TNetStrError::DataNotUTF8Compatible)
+            })),
+            Some(Arc::new(|data: &TNetData| match data {
+                TNetData::Bytes(b) if core::str::from_utf8(b).is_ok() => Some(b.clone()),
+                _ => None
+            }))
+        ).unwrap();
+        extensions
+    }
+
+    #[test]
+    fn reads_registered_tags() {
+        assert_eq!(parse(b"2:hi;"), Err(TNetStrError::UnrecognizedDataType(b';')));
+
+        let mut extensions = Extensions::new();
+        extensions.register(b';', None, None).unwrap();
+        let entry = extensions.parse(b"12:2:hi;4:1:a;]]").unwrap();
+        let items = entry.data.as_list().unwrap();
+        assert_eq!(items[0].data, TNetData::Extension { tag: b';', payload: b"hi".to_vec() });
+        assert_eq!(items[0].data_type, DataType::Extension(b';'));
+        assert_eq!(items[1].data.as_list().unwrap()[0].data.as_extension(), Some((b';', &b"a"[..])));
+        assert_eq!(encode(&entry), b"12:2:hi;4:1:a;]]");
+        assert_eq!(extensions.parse(b"2:hi?"), Err(TNetStrError::UnrecognizedDataType(b'?')));
+        assert_eq!(extensions.parse(b"7:1:k;0:~}"), Err(TNetStrError::DictionaryKeyWasNotBytes));
+    }
+
+    #[test]
+    fn decodes_and_encodes_with_a_dialect() {
+        let extensions = string_dialect();
+        let entry = extensions.parse(b"16:2:hi;8:1:a;1:\xff,]}").unwrap();
+        assert_eq!(entry, parse(b"16:2:hi,8:1:a,1:\xff,]}").unwrap());
+        let (key, value) = &entry.data.as_dictionary().unwrap().entries[0];
+        assert_eq!(key.data, TNetData::Bytes(b"hi".to_vec()));
+        assert_eq!(value.data.as_list().unwrap()[1].data, TNetData::Bytes(b"\xff".to_vec()));
+        assert_eq!(extensions.encode(&entry), b"16:2:hi;8:1:a;1:\xff,]}");
+        assert_eq!(encode(&entry), b"16:2:hi,8:1:a,1:\xff,]}");
+        assert_eq!(extensions.parse(b"1:\xff;"), Err(TNetStrError::DataNotUTF8Compatible));
+    }
+
+    #[test]
+    fn decodes_with_captured_state() {
+        let prefix = b"decoded:".to_vec();
+        let mut extensions = Extensions::new();
+        extensions.register(b'@', Some(Arc::new(move |payload: &[u8]| Ok(TNetData::Bytes([&prefix[..], payload].concat())))), None).unwrap();
+        assert_eq!(extensions.parse(b"2:hi@").unwrap().data, TNetData::Bytes(b"decoded:hi".to_vec()));
+    }
+
+    #[test]
+    fn rejects_standard_tags() {
+        let mut extensions = Extensions::new();
+        assert_eq!(extensions.register(b',', None, None), Err(TNetStrError::InvalidExtensionTag(b',')));
+        assert_eq!(extensions.register(b'7', None, None), Err(TNetStrError::InvalidExtensionTag(b'7')));
+        assert_eq!(extensions.register(b':', None, None), Err(TNetStrError::InvalidExtensionTag(b':')));
+    }
+}
@@ -0,0 +1,175 @@
+use super::{
+    DataType,
+    TNetData,
+    TNetEntry
+};
+use super::schema::Schema;
+use alloc::{
+    boxed::Box,
+    vec,
+    vec::Vec
+};
+use core::borrow::Borrow;
+
+/// What has been seen of the values at one place in the sample documents
+#[derive(Default)]
+struct Observed {
+    types: Vec<DataType>,
+    min: Option<TNetData>,
+    max: Option<TNetData>,
+    min_length: Option<usize>,
+    max_length: Option<usize>,
+    /// Whether any bytes were seen, and whether all of them were UTF-8
+    bytes: bool,
+    utf8: bool,
+    items: Option<Box<Observed>>,
+    /// Each key seen, with the number of dictionaries it was in
+    keys: Vec<(Vec<u8>, Observed, usize)>,
+    dictionaries: usize
+}
+
+impl Observed {
+
+    fn length(&mut self, length: usize) {
+        self.min_length = Some(self.min_length.map_or(length, |min| min.min(length)));
+        self.max_length = Some(self.max_length.map_or(length, |max| max.max(length)));
+    }
+
+    fn add(&mut self, entry: &TNetEntry) {
+        let data_type = entry.data.data_type();
+        if !self.types.contains(&data_type) {
+            self.types.push(data_type);
+        }
+        match &entry.data {
+            TNetData::Integer(_) | TNetData::Float(_) => {
+                if self.min.as_ref().is_none_or(|min| entry.data.cmp_numeric(min).is_lt()) {
+                    self.min = Some(entry.data.clone());
+                }
+                if self.max.as_ref().is_none_or(|max| entry.data.cmp_numeric(max).is_gt()) {
+                    self.max = Some(entry.data.clone());
+                }
+            },
+            TNetData::Bytes(b) => {
+                self.length(b.len());
+                self.utf8 = (self.utf8 || !self.bytes) && core::str::from_utf8(b).is_ok();
+                self.bytes = true;
+            },
+            TNetData::List(l) => {
+                self.length(l.len());
+                let items = self.items.get_or_insert_with(Box::default);
+                l.iter().for_each(|item| items.add(item));
+            },
+            TNetData::Dictionary(dict) => {
+                self.dictionaries += 1;
+                let mut seen: Vec<&[u8]> = vec![];
+                for (key, value) in &dict.entries {
+                    let key = key.data.as_bytes().unwrap_or(&[]);
+                    let index = match self.keys.iter().position(|(k, _, _)| k == key) {
+                        Some(index) => index,
+                        None => {
+                            self.keys.push((key.to_vec(), Observed::default(), 0));
+                            self.keys.len() - 1
+                        }
+                    };
+                    let (_, observed, count) = &mut self.keys[index];
+                    if !seen.contains(&key) {
+                        seen.push(key);
+                        *count += 1;
+                    }
+                    observed.add(value);
+                }
+            },
+            _ => {}
+        }
+    }
+
+    fn schema(self) -> Schema {
+        let mut types = self.types;
+        types.sort();
+        let dictionaries = self.dictionaries;
+        Schema {
+            types: Some(types),
+            min: self.min,
+            max: self.max,
+            min_length: self.min_length,
+            max_length: self.max_length,
+            utf8: self.bytes && self.utf8,
+            items: self.items.map(|items| Box::new(items.schema())),
+            keys: self.keys.into_iter()
+                .map(|(key, observed, count)| {
+                    let mut schema = observed.schema();
+                    schema.optional = count < dictionaries;
+                    (key, schema)
+                })
+                .collect(),
+            ..Schema::default()
+        }
+    }
+}
+
+/// Infers the schema of a set of sample documents, such as the entries of a captured stream.
+///
+/// The schema has the types seen at each place, the smallest and largest numbers and lengths
+/// seen, whether every bytes value was UTF-8, and the keys of every dictionary seen, with keys
+/// that were missing from any dictionary marked optional. Every sample validates against it.
+/// Sets of allowed values are not inferred, and dictionaries may have keys that were not seen.
+/// With no samples the schema accepts anything.
+pub fn infer_schema<I>(samples: I) -> Schema
+where
+    I: IntoIterator,
+    I::Item: Borrow<TNetEntry>
+{
+    let mut observed = Observed::default();
+    let mut any = false;
+    for sample in samples {
+        observed.add(sample.borrow());
+        any = true;
+    }
+    if any {
+        observed.schema()
+    } else {
+        Schema::default()
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+    use super::super::parse;
+
+    fn samples() -> Vec<TNetEntry> {
+        vec![
+            parse(b"47:2:id,1:1#4:name,3:bob,4:tags,4:1:a,]4:blob,1:\xff,}").unwrap(),
+            parse(b"34:2:id,1:7#4:name,5:alice,4:tags,0:]}").unwrap(),
+            parse(b"55:2:id,1:3#4:name,3:eve,4:tags,9:1:x,2:yy,]5:score,3:1.5^}").unwrap()
+        ]
+    }
+
+    #[test]
+    fn infers_keys_types_and_ranges() {
+        let schema = infer_schema(samples());
+        let expected = parse(b"443:4:type,10:dictionary,4:keys,410:\
+            2:id,37:4:type,7:integer,3:min,1:1#3:max,1:7#}\
+            4:name,65:4:type,5:bytes,10:min_length,1:3#10:max_length,1:5#4:utf8,4:true!}\
+            4:tags,127:4:type,4:list,10:min_length,1:0#10:max_length,1:2#5:items,65:4:type,5:bytes,10:min_length,1:1#10:max_length,1:2#4:utf8,4:true!}}\
+            4:blob,69:4:type,5:bytes,10:min_length,1:1#10:max_length,1:1#8:optional,4:true!}\
+            5:score,57:4:type,5:float,3:min,3:1.5^3:max,3:1.5^8:optional,4:true!}}}").unwrap();
+        assert_eq!(schema.to_entry(), expected);
+    }
+
+    #[test]
+    fn samples_validate_against_their_schema() {
+        let samples = samples();
+        let schema = infer_schema(&samples);
+        for sample in &samples {
+            assert_eq!(schema.validate(sample), vec![]);
+        }
+        assert_eq!(schema.validate(&parse(b"22:2:id,1:9#4:name,3:bob,}").unwrap()).len(), 2);
+    }
+
+    #[test]
+    fn accepts_anything_without_samples() {
+        assert_eq!(infer_schema(Vec::<TNetEntry>::new()), Schema::default());
+    }
+}
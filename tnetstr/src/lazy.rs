@@ -0,0 +1,232 @@
+use super::{
+    DataType,
+    TNetEntry,
+    TNetFloat,
+    TNetStrError
+};
+use super::parse::{
+    items,
+    pairs,
+    parse,
+    parse_frame,
+    parse_float,
+    parse_val
+};
+use alloc::vec::Vec;
+use core::cell::OnceCell;
+
+/// A (key, value) pair of a lazily parsed dictionary
+pub type LazyPair<B> = (LazyEntry<B>, LazyEntry<B>);
+
+enum LazyChildren<B: AsRef<[u8]> + Clone> {
+    List(Vec<LazyEntry<B>>),
+    Dictionary(Vec<LazyPair<B>>)
+}
+
+/// A tnetstring whose lists and dictionaries are only parsed when they are first accessed.
+///
+/// The buffer can be anything that can be cheaply cloned and viewed as bytes, such as a borrowed
+/// `&[u8]` or a shared `Rc<[u8]>`/`Arc<[u8]>`; every child entry holds its own clone of it.
+/// Only the framing of an entry is checked when it is created, so scalar values and the children of
+/// containers may fail to parse when accessed. Successfully parsed children are cached.
+pub struct LazyEntry<B: AsRef<[u8]> + Clone> {
+    buffer: B,
+    /// The position of the first byte of the tnetstring's length in the buffer
+    start: usize,
+    /// The position of the first byte of the tnetstring's data in the buffer
+    data_start: usize,
+    /// The number of bytes in the tnetstring data
+    size: u32,
+    data_type: DataType,
+    children: OnceCell<LazyChildren<B>>
+}
+
+impl<B: AsRef<[u8]> + Clone> LazyEntry<B> {
+
+    /// Reads the framing of the first tnetstring in the buffer
+    pub fn new(buffer: B) -> Result<LazyEntry<B>, TNetStrError> {
+        let mut position = 0;
+        LazyEntry::at(buffer, &mut position, usize::MAX)
+    }
+
+    fn at(buffer: B, position: &mut usize, limit: usize) -> Result<LazyEntry<B>, TNetStrError> {
+        let start = *position;
+        let bytes = buffer.as_ref();
+        let (data_type, data) = parse_frame(&bytes[..limit.min(bytes.len())], position)?;
+        let size = data.len() as u32;
+        let data_start = *position - 1 - data.len();
+        Ok(LazyEntry {
+            buffer,
+            start,
+            data_start,
+            size,
+            data_type,
+            children: OnceCell::new()
+        })
+    }
+
+    /// The number of bytes in the tnetstring data
+    pub fn size(&self) -> u32 {
+        self.size
+    }
+
+    /// The type of the tnetstring data
+    pub fn data_type(&self) -> &DataType {
+        &self.data_type
+    }
+
+    /// The raw data of the tnetstring, without its length or type tag
+    pub fn data_bytes(&self) -> &[u8] {
+        &self.buffer.as_ref()[self.data_start..self.data_end()]
+    }
+
+    /// The whole tnetstring, including its length and type tag
+    pub fn as_tnetstring(&self) -> &[u8] {
+        &self.buffer.as_ref()[self.start..self.data_end() + 1]
+    }
+
+    fn data_end(&self) -> usize {
+        self.data_start + self.size as usize
+    }
+
+    fn children(&self) -> Result<Option<&LazyChildren<B>>, TNetStrError> {
+        if let Some(children) = self.children.get() {
+            return Ok(Some(children))
+        }
+        let end = self.data_end();
+        let read = |position: &mut usize| LazyEntry::at(self.buffer.clone(), position, end);
+        let children = match self.data_type {
+            DataType::List => LazyChildren::List(items(self.data_start, end, read).collect::<Result<_, _>>()?),
+            DataType::Dictionary => LazyChildren::Dictionary(
+                pairs(self.data_start, end, read, |key: &LazyEntry<B>| key.data_type).collect::<Result<_, _>>()?
+            ),
+            _ => return Ok(None)
+        };
+        Ok(Some(self.children.get_or_init(|| children)))
+    }
+
+    pub fn as_bytes(&self) -> Option<&[u8]> {
+        match self.data_type {
+            DataType::Bytes => Some(self.data_bytes()),
+            _ => None
+        }
+    }
+
+    pub fn as_integer(&self) -> Result<Option<i64>, TNetStrError> {
+        match self.data_type {
+            DataType::Integer => parse_val(self.data_bytes()).map(Some),
+            _ => Ok(None)
+        }
+    }
+
+    pub fn as_float(&self) -> Result<Option<TNetFloat>, TNetStrError> {
+        match self.data_type {
+            DataType::Float => parse_float(self.data_bytes()).map(Some),
+            _ => Ok(None)
+        }
+    }
+
+    pub fn as_bool(&self) -> Result<Option<bool>, TNetStrError> {
+        match self.data_type {
+            DataType::Boolean => parse_val(self.data_bytes()).map(Some),
+            _ => Ok(None)
+        }
+    }
+
+    pub fn is_null(&self) -> bool {
+        self.data_type == DataType::Null
+    }
+
+    /// The (key, value) pairs of a dictionary, parsing their framing on first access
+    pub fn as_dictionary(&self) -> Result<Option<&[LazyPair<B>]>, TNetStrError> {
+        match self.children()? {
+            Some(LazyChildren::Dictionary(d)) => Ok(Some(d)),
+            _ => Ok(None)
+        }
+    }
+
+    /// The items of a list, parsing their framing on first access
+    pub fn as_list(&self) -> Result<Option<&[LazyEntry<B>]>, TNetStrError> {
+        match self.children()? {
+            Some(LazyChildren::List(l)) => Ok(Some(l)),
+            _ => Ok(None)
+        }
+    }
+
+    /// All values with the given key, if this is a dictionary
+    pub fn get(&self, key: &[u8]) -> Result<Vec<&LazyEntry<B>>, TNetStrError> {
+        Ok(self.as_dictionary()?
+            .unwrap_or(&[])
+            .iter()
+            .filter(|(k, _)| k.data_bytes() == key)
+            .map(|(_, v)| v)
+            .collect())
+    }
+
+    /// Fully parses the tnetstring into a `TNetEntry`
+    pub fn to_entry(&self) -> Result<TNetEntry, TNetStrError> {
+        parse(self.as_tnetstring())
+    }
+}
+
+/// Reads the framing of the first tnetstring in a slice of bytes, leaving its content to be parsed on demand
+pub fn parse_lazy(data: &[u8]) -> Result<LazyEntry<&[u8]>, TNetStrError> {
+    LazyEntry::new(data)
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+    use super::super::TNetData;
+    use std::rc::Rc;
+
+    #[test]
+    fn reads_scalars() {
+        assert_eq!(parse_lazy(b"3:123#").unwrap().as_integer(), Ok(Some(123)));
+        assert_eq!(parse_lazy(b"4:true!").unwrap().as_bool(), Ok(Some(true)));
+        assert_eq!(parse_lazy(b"3:abc,").unwrap().as_bytes(), Some(&b"abc"[..]));
+        assert_eq!(parse_lazy(b"3:abc,").unwrap().as_integer(), Ok(None));
+        assert!(parse_lazy(b"0:~").unwrap().is_null());
+    }
+
+    #[test]
+    fn only_parses_accessed_children() {
+        // "bad" is not a valid integer, but is never accessed
+        let input = b"34:3:bad,3:abc#4:list,11:1:1#1:x,0:~]}";
+        let entry = parse_lazy(input).unwrap();
+        let list = entry.get(b"list").unwrap();
+        let items = list[0].as_list().unwrap().unwrap();
+        assert_eq!(items.len(), 3);
+        assert_eq!(items[0].as_integer(), Ok(Some(1)));
+        assert_eq!(items[1].as_bytes(), Some(&b"x"[..]));
+        assert_eq!(entry.get(b"bad").unwrap()[0].as_integer(), Err(TNetStrError::CouldNotParseData));
+    }
+
+    #[test]
+    fn reports_container_errors_on_access() {
+        let entry = parse_lazy(b"4:1:a,}").unwrap();
+        assert_eq!(entry.as_dictionary().err(), Some(TNetStrError::UnbalancedDictionary));
+        let entry = parse_lazy(b"6:9:abc,]").unwrap();
+        assert_eq!(entry.as_list().err(), Some(TNetStrError::LengthWasNotAccurate));
+    }
+
+    #[test]
+    fn works_over_shared_buffer() {
+        let buffer: Rc<[u8]> = Rc::from(&b"12:1:a,5:hello,}"[..]);
+        let entry = LazyEntry::new(buffer).unwrap();
+        assert_eq!(entry.get(b"a").unwrap()[0].as_bytes(), Some(&b"hello"[..]));
+    }
+
+    #[test]
+    fn converts_to_entry() {
+        let input = b"24:4:true!6:0.4529^5:abcde,]";
+        let lazy = parse_lazy(input).unwrap();
+        let entry = lazy.to_entry().unwrap();
+        assert_eq!(entry, parse(input).unwrap());
+        assert_eq!(entry.data.as_list().map(|l| l.len()), Some(3));
+        assert_eq!(lazy.size(), 24);
+        assert_eq!(lazy.data_type(), &DataType::List);
+        assert_eq!(entry.data.as_list().unwrap()[2].data, TNetData::Bytes(b"abcde".to_vec()));
+    }
+}
@@ -1,18 +1,255 @@
+//! With the default `std` feature disabled the parser, value types and encoder only need `alloc`.
+//! Reading from `std::io` streams and writing to seekable sinks need `std`.
+//!
+//! With the `alloc` feature disabled as well nothing is allocated: `validate`, `find_path` and the
+//! `FixedEventParser` read tnetstrings in place, and the other modules are unavailable.
+//!
+//! # Canonical form
+//!
+//! `encode_canonical` produces a single encoding for each value, so that encodings can be compared,
+//! hashed or signed. `is_canonical` checks that some bytes are in this form, and
+//! `TNetEntry::canonicalize` rewrites an entry so that `encode` produces it. With the `digest` feature,
+//! `TNetEntry::digest` hashes the canonical encoding with any `digest::Digest` (such as
+//! `sha2::Sha256`) without building it in memory. The canonical form is a
+//! valid tnetstring with the following restrictions:
+//!
+//! * Lengths are written in decimal without leading zeros (`0` for empty data).
+//! * Integers are written in decimal without leading zeros or a `+` sign, with a `-` sign only for
+//!   negative numbers.
+//! * Floats are written as the integral digits without leading zeros, a `.`, and the fractional
+//!   digits without trailing zeros, keeping a single `0` on either side if there are no others
+//!   (e.g. `0.5`, `12.0`, `-0.05`). Values below `1e-5` or from `1e16` are written with a single
+//!   integral digit and an exponent instead (e.g. `1.0e-6`, `2.5e+16`). Zero is `0.0`, never
+//!   `-0.0`, and values that are not finite are `nan`, `inf` and `-inf`. Floats with more
+//!   significant digits than a `u64` holds are only stripped of trailing zeros.
+//! * Booleans are `true` or `false`, and null has empty data.
+//! * Dictionary pairs are ordered by their keys, comparing the bytes of the keys' data as unsigned
+//!   bytes, with a key that is a prefix of another ordered first.
+//! * Duplicate keys are kept. Pairs with equal keys are ordered by the canonical encoding of their
+//!   values, compared in the same way.
+//! * Nothing follows the tnetstring.
+//!
+//! # Ordering
+//!
+//! `TNetData`, `TNetEntry` and `TNetDictionary` implement `Ord`, so values can be sorted or kept in
+//! a `BTreeSet`. Values of different types are ordered null, boolean, integer, float, bytes, list,
+//! dictionary, and values of the same type by value. The ordering agrees with `Eq`, so floats with
+//! the same value but different text (`1.5` and `1.50`) are not equal, and dictionaries with their
+//! pairs in different orders are not equal. `TNetData::cmp_numeric` and `TNetData::eq_numeric`
+//! compare integers and floats by value instead, and `TNetData::eq_unordered` ignores the order of
+//! dictionary pairs.
+//!
+//! # Extensions
+//!
+//! Some dialects of tnetstrings use type tags outside the spec. `Extensions` registers extra tags,
+//! each with an optional decoder and encoder, and parses and encodes with them. Data with a
+//! registered tag and no decoder is kept as `TNetData::Extension`. Other parsers reject these tags
+//! with `UnrecognizedDataType`.
+#![cfg_attr(not(any(feature = "std", test)), no_std)]
+
+#[cfg(feature = "alloc")]
+extern crate alloc;
+
 pub mod errors;
 pub mod data_type;
 pub mod tnet;
 pub mod parse;
+#[cfg(feature = "alloc")]
+pub mod encode;
+#[cfg(feature = "alloc")]
+pub mod canonical;
+#[cfg(feature = "alloc")]
+pub mod diff;
+#[cfg(feature = "alloc")]
+pub mod patch;
+#[cfg(feature = "alloc")]
+pub mod merge;
+#[cfg(feature = "alloc")]
+mod order;
+#[cfg(feature = "alloc")]
+pub mod schema;
+#[cfg(feature = "alloc")]
+pub mod infer;
+#[cfg(feature = "alloc")]
+pub mod codegen;
+#[cfg(feature = "alloc")]
+pub mod convert;
+#[cfg(feature = "alloc")]
+pub mod extension;
+#[cfg(feature = "alloc")]
+pub mod writer;
+#[cfg(feature = "std")]
+pub mod backpatch;
+pub mod events;
+#[cfg(feature = "std")]
+pub mod stream;
+pub mod path;
+#[cfg(feature = "alloc")]
+pub mod lazy;
+#[cfg(feature = "alloc")]
+pub mod lossless;
+#[cfg(feature = "bytes")]
+pub mod shared;
+#[cfg(feature = "tokio")]
+pub mod codec;
+#[cfg(feature = "async")]
+pub mod async_io;
 
 pub use self::errors::TNetStrError;
-pub use self::data_type::DataType;
+pub use self::data_type::{
+    DataType,
+    TypeName
+};
+pub use self::tnet::TNetFloat;
+#[cfg(feature = "alloc")]
 pub use self::tnet::{
     TNetData,
     TNetList,
     TNetDictionary,
-    TNetEntry,
-    TNetFloat
+    TNetEntry
+};
+pub use self::parse::validate;
+#[cfg(feature = "alloc")]
+pub use self::parse::parse;
+#[cfg(feature = "std")]
+pub use self::parse::parse_stream;
+#[cfg(feature = "alloc")]
+pub use self::encode::{
+    encode,
+    encode_into,
+    encode_to,
+    try_encode,
+    try_encode_to
+};
+#[cfg(feature = "bytes")]
+pub use self::encode::encode_into_buf;
+#[cfg(feature = "alloc")]
+pub use self::canonical::{
+    encode_canonical,
+    is_canonical
+};
+#[cfg(feature = "alloc")]
+pub use self::diff::{
+    Change,
+    ChangeKind,
+    DiffPath,
+    PathSegment,
+    diff,
+    render_diff
+};
+#[cfg(feature = "alloc")]
+pub use self::patch::{
+    Patch,
+    PatchOperation,
+    apply_patch
+};
+#[cfg(feature = "alloc")]
+pub use self::merge::{
+    DuplicateKeys,
+    ListMerge,
+    MergePolicy,
+    TypeConflict,
+    merge
+};
+#[cfg(feature = "alloc")]
+pub use self::schema::{
+    Schema,
+    Violation,
+    ViolationKind
+};
+#[cfg(feature = "alloc")]
+pub use self::infer::infer_schema;
+#[cfg(feature = "alloc")]
+pub use self::codegen::{
+    CodegenOptions,
+    generate_rust
+};
+#[cfg(feature = "std")]
+pub use self::codegen::generate_rust_file;
+#[cfg(feature = "alloc")]
+pub use self::convert::{
+    ByteBuf,
+    FromTNet,
+    TNetValue,
+    ToTNet,
+    from_tnetstring,
+    to_tnetstring
+};
+#[cfg(feature = "alloc")]
+#[doc(hidden)]
+pub use self::convert::__private;
+#[cfg(feature = "derive")]
+pub use tnetstr_derive::{
+    FromTNet,
+    ToTNet
+};
+#[cfg(feature = "alloc")]
+pub use self::extension::{
+    Decoder,
+    Encoder,
+    Extensions
+};
+#[cfg(feature = "alloc")]
+pub use self::writer::{
+    encoded_len,
+    frame_len,
+    ByteSink,
+    TNetWriter,
+    MAX_DATA_LENGTH
+};
+#[cfg(feature = "std")]
+pub use self::writer::IoSink;
+#[cfg(feature = "std")]
+pub use self::backpatch::{
+    ListWriter,
+    DictWriter
+};
+pub use self::events::{
+    TNetEvent,
+    FixedEventParser
+};
+#[cfg(feature = "alloc")]
+pub use self::events::{
+    EventParser,
+    parse_events
+};
+#[cfg(feature = "std")]
+pub use self::events::StreamEventParser;
+#[cfg(feature = "std")]
+pub use self::stream::{
+    TNetReader,
+    BytesReader,
+    LargeFrame
+};
+pub use self::path::find_path;
+#[cfg(feature = "alloc")]
+pub use self::path::find_path_entry;
+#[cfg(feature = "alloc")]
+pub use self::lazy::{
+    LazyEntry,
+    LazyPair,
+    parse_lazy
+};
+#[cfg(feature = "alloc")]
+pub use self::lossless::{
+    LosslessData,
+    LosslessEntry,
+    LosslessPair,
+    parse_lossless
+};
+#[cfg(feature = "bytes")]
+pub use self::shared::{
+    SharedData,
+    SharedDictionary,
+    SharedEntry,
+    SharedList,
+    parse_shared
 };
-pub use self::parse::{
-    parse,
-    parse_stream
+#[cfg(feature = "tokio")]
+pub use self::codec::TNetCodec;
+#[cfg(feature = "async")]
+pub use self::async_io::{
+    parse_async,
+    write_async,
+    entries_async
 };
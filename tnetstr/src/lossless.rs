@@ -0,0 +1,336 @@
+use super::{
+    DataType,
+    TNetData,
+    TNetDictionary,
+    TNetEntry,
+    TNetFloat,
+    TNetStrError
+};
+use super::parse::{
+    items,
+    pairs,
+    parse_frame,
+    parse_float,
+    parse_val
+};
+use super::writer::{
+    float_len,
+    frame_len,
+    integer_len,
+    ByteSink,
+    TNetWriter,
+    MAX_DATA_LENGTH
+};
+use alloc::{
+    vec,
+    vec::Vec
+};
+use core::ops::Range;
+
+/// A (key, value) pair of a lossless dictionary
+pub type LosslessPair<B> = (LosslessEntry<B>, LosslessEntry<B>);
+
+/// The data of a `LosslessEntry`. This mirrors `TNetData`, with the children of containers being
+/// lossless entries themselves.
+#[derive(Debug, Clone)]
+pub enum LosslessData<B: AsRef<[u8]> + Clone> {
+    Bytes(Vec<u8>),
+    Integer(i64),
+    Float(TNetFloat),
+    Boolean(bool),
+    Null,
+    Dictionary(Vec<LosslessPair<B>>),
+    List(Vec<LosslessEntry<B>>),
+    Extension { tag: u8, payload: Vec<u8> }
+}
+
+impl<B: AsRef<[u8]> + Clone> LosslessData<B> {
+
+    pub fn data_type(&self) -> DataType {
+        match self {
+            LosslessData::Bytes(_) => DataType::Bytes,
+            LosslessData::Integer(_) => DataType::Integer,
+            LosslessData::Float(_) => DataType::Float,
+            LosslessData::Boolean(_) => DataType::Boolean,
+            LosslessData::Null => DataType::Null,
+            LosslessData::Dictionary(_) => DataType::Dictionary,
+            LosslessData::List(_) => DataType::List,
+            LosslessData::Extension { tag, .. } => DataType::Extension(*tag)
+        }
+    }
+}
+
+/// A fully parsed tnetstring that remembers where each of its entries came from, so that
+/// re-encoding it reproduces the original bytes exactly.
+///
+/// Parsing normalizes some things that are valid in a tnetstring, such as leading zeros in lengths
+/// and the text of floats. An unmodified entry is encoded by copying its original tnetstring from
+/// the buffer it was parsed from, while a modified entry is encoded from its data. Getting mutable
+/// access to an entry's data with `data_mut` counts as modifying it, and as every ancestor of an
+/// entry has to be modified to reach it, containers holding a modified entry are re-encoded
+/// around it while its unmodified siblings keep their original bytes.
+///
+/// As with `LazyEntry`, the buffer can be anything that can be cheaply cloned and viewed as bytes,
+/// and every entry holds its own clone of it.
+#[derive(Debug, Clone)]
+pub struct LosslessEntry<B: AsRef<[u8]> + Clone> {
+    data: LosslessData<B>,
+    /// The buffer and range of the entry's original tnetstring, until it is modified
+    source: Option<(B, Range<usize>)>
+}
+
+impl<B: AsRef<[u8]> + Clone> LosslessEntry<B> {
+
+    /// Parses the first tnetstring in the buffer
+    pub fn new(buffer: B) -> Result<LosslessEntry<B>, TNetStrError> {
+        let mut position = 0;
+        let limit = buffer.as_ref().len();
+        LosslessEntry::at(&buffer, &mut position, limit)
+    }
+
+    fn at(buffer: &B, position: &mut usize, limit: usize) -> Result<LosslessEntry<B>, TNetStrError> {
+        let start = *position;
+        let (t, data_bytes) = parse_frame(&buffer.as_ref()[..limit], position)?;
+        let data_end = *position - 1;
+        let data_start = data_end - data_bytes.len();
+
+        let data = match t {
+            DataType::Bytes => LosslessData::Bytes(data_bytes.to_vec()),
+            DataType::Null => LosslessData::Null,
+            DataType::Integer => LosslessData::Integer(parse_val(data_bytes)?),
+            DataType::Float => LosslessData::Float(parse_float(data_bytes)?),
+            DataType::Boolean => LosslessData::Boolean(parse_val(data_bytes)?),
+            DataType::Extension(tag) => return Err(TNetStrError::UnrecognizedDataType(tag)),
+            DataType::List => LosslessData::List(
+                items(data_start, data_end, |child| LosslessEntry::at(buffer, child, data_end)).collect::<Result<_, _>>()?
+            ),
+            DataType::Dictionary => LosslessData::Dictionary(
+                pairs(data_start, data_end, |child| LosslessEntry::at(buffer, child, data_end), |key: &LosslessEntry<B>| key.data.data_type())
+                    .collect::<Result<_, _>>()?
+            )
+        };
+
+        Ok(LosslessEntry {
+            data,
+            source: Some((buffer.clone(), start..*position))
+        })
+    }
+
+    pub fn data(&self) -> &LosslessData<B> {
+        &self.data
+    }
+
+    /// Gives mutable access to the entry's data, marking the entry as modified so that it will be
+    /// encoded from its data rather than copied from its source
+    pub fn data_mut(&mut self) -> &mut LosslessData<B> {
+        self.source = None;
+        &mut self.data
+    }
+
+    /// The original tnetstring of the entry, or `None` if it has been modified or was not parsed
+    pub fn source(&self) -> Option<&[u8]> {
+        self.source.as_ref().map(|(buffer, range)| &buffer.as_ref()[range.clone()])
+    }
+
+    /// All values with the given key, if this is a dictionary
+    pub fn get(&self, key: &[u8]) -> Vec<&LosslessEntry<B>> {
+        match &self.data {
+            LosslessData::Dictionary(d) => d.iter()
+                .filter(|(k, _)| matches!(&k.data, LosslessData::Bytes(b) if b.as_slice() == key))
+                .map(|(_, v)| v)
+                .collect(),
+            _ => vec![]
+        }
+    }
+
+    /// The encoded length of the entry's data, as it would be written
+    fn data_len(&self) -> usize {
+        if let Some(source) = self.source() {
+            let mut position = 0;
+            return parse_frame(source, &mut position).expect("Bug - source was not a tnetstring").1.len()
+        }
+        match &self.data {
+            LosslessData::Bytes(b) | LosslessData::Extension { payload: b, .. } => b.len(),
+            LosslessData::Integer(n) => integer_len(*n),
+            LosslessData::Float(n) => float_len(n),
+            LosslessData::Boolean(b) => if *b { 4 } else { 5 },
+            LosslessData::Null => 0,
+            LosslessData::Dictionary(d) => d.iter()
+                .map(|(key, value)| key.encoded_len() + value.encoded_len())
+                .sum(),
+            LosslessData::List(l) => l.iter()
+                .map(|entry| entry.encoded_len())
+                .sum()
+        }
+    }
+
+    /// The number of bytes `encode` will produce
+    pub fn encoded_len(&self) -> usize {
+        match self.source() {
+            Some(source) => source.len(),
+            None => frame_len(self.data_len())
+        }
+    }
+
+    fn write_to<W: ByteSink>(&self, writer: &mut TNetWriter<W>) -> Result<(), TNetStrError> {
+        if let Some(source) = self.source() {
+            return writer.write_raw(source)
+        }
+        match &self.data {
+            LosslessData::Bytes(b) => writer.write_bytes(b),
+            LosslessData::Integer(n) => writer.write_integer(*n),
+            LosslessData::Float(n) => writer.write_float(n),
+            LosslessData::Boolean(b) => writer.write_bool(*b),
+            LosslessData::Null => writer.write_null(),
+            LosslessData::Extension { tag, payload } => writer.write_scalar(DataType::Extension(*tag), payload),
+            LosslessData::Dictionary(d) => {
+                writer.start_dictionary(self.data_len())?;
+                for (key, value) in d {
+                    key.write_to(writer)?;
+                    value.write_to(writer)?;
+                }
+                writer.end()
+            },
+            LosslessData::List(l) => {
+                writer.start_list(self.data_len())?;
+                for entry in l {
+                    entry.write_to(writer)?;
+                }
+                writer.end()
+            }
+        }
+    }
+
+    /// Appends the entry's tnetstring to `output`, copying the original bytes of unmodified entries
+    ///
+    /// # Panics
+    ///
+    /// If the data of a modified value is longer than `MAX_DATA_LENGTH`; `try_encode_to` returns
+    /// an error instead.
+    pub fn encode_to(&self, output: &mut Vec<u8>) {
+        self.try_encode_to(output).expect("Data was longer than the spec allows")
+    }
+
+    /// Encodes the entry, copying the original bytes of unmodified entries
+    ///
+    /// # Panics
+    ///
+    /// If the data of a modified value is longer than `MAX_DATA_LENGTH`; `try_encode` returns an
+    /// error instead.
+    pub fn encode(&self) -> Vec<u8> {
+        self.try_encode().expect("Data was longer than the spec allows")
+    }
+
+    /// Appends the entry's tnetstring to `output`, as `encode_to` does. If the data of a value is
+    /// longer than `MAX_DATA_LENGTH` nothing is written, and `LengthTooLong` is returned.
+    pub fn try_encode_to(&self, output: &mut Vec<u8>) -> Result<(), TNetStrError> {
+        if self.encoded_len() > frame_len(MAX_DATA_LENGTH) {
+            return Err(TNetStrError::LengthTooLong)
+        }
+        self.write_to(&mut TNetWriter::new(output))
+    }
+
+    /// Encodes the entry, as `encode` does, returning `LengthTooLong` if the data of a value is
+    /// longer than `MAX_DATA_LENGTH`
+    pub fn try_encode(&self) -> Result<Vec<u8>, TNetStrError> {
+        let len = self.encoded_len();
+        if len > frame_len(MAX_DATA_LENGTH) {
+            return Err(TNetStrError::LengthTooLong)
+        }
+        let mut output = Vec::with_capacity(len);
+        self.write_to(&mut TNetWriter::new(&mut output))?;
+        Ok(output)
+    }
+
+    /// Converts the entry into a `TNetEntry`, losing its source
+    pub fn to_entry(&self) -> TNetEntry {
+        let data = match &self.data {
+            LosslessData::Bytes(b) => TNetData::Bytes(b.clone()),
+            LosslessData::Integer(n) => TNetData::Integer(*n),
+            LosslessData::Float(n) => TNetData::Float(n.clone()),
+            LosslessData::Boolean(b) => TNetData::Boolean(*b),
+            LosslessData::Null => TNetData::Null,
+            LosslessData::Extension { tag, payload } => TNetData::Extension { tag: *tag, payload: payload.clone() },
+            LosslessData::List(l) => TNetData::List(l.iter().map(LosslessEntry::to_entry).collect()),
+            LosslessData::Dictionary(d) => TNetData::Dictionary(TNetDictionary {
+                entries: d.iter()
+                    .map(|(k, v)| (k.to_entry(), v.to_entry()))
+                    .collect()
+            })
+        };
+        TNetEntry {
+            size: self.data_len() as u32,
+            data_type: self.data.data_type(),
+            data
+        }
+    }
+}
+
+/// A new entry, with no source, to be inserted into a lossless tree
+impl<B: AsRef<[u8]> + Clone> From<TNetEntry> for LosslessEntry<B> {
+    fn from(entry: TNetEntry) -> LosslessEntry<B> {
+        let data = match entry.data {
+            TNetData::Bytes(b) => LosslessData::Bytes(b),
+            TNetData::Integer(n) => LosslessData::Integer(n),
+            TNetData::Float(n) => LosslessData::Float(n),
+            TNetData::Boolean(b) => LosslessData::Boolean(b),
+            TNetData::Null => LosslessData::Null,
+            TNetData::Extension { tag, payload } => LosslessData::Extension { tag, payload },
+            TNetData::List(l) => LosslessData::List(l.into_iter().map(LosslessEntry::from).collect()),
+            TNetData::Dictionary(d) => LosslessData::Dictionary(d.entries.into_iter()
+                .map(|(k, v)| (LosslessEntry::from(k), LosslessEntry::from(v)))
+                .collect())
+        };
+        LosslessEntry {
+            data,
+            source: None
+        }
+    }
+}
+
+/// Parses the first tnetstring in a slice of bytes, remembering its original bytes so that it can be
+/// re-encoded exactly
+pub fn parse_lossless(data: &[u8]) -> Result<LosslessEntry<&[u8]>, TNetStrError> {
+    LosslessEntry::new(data)
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+    use super::super::{
+        encode,
+        parse
+    };
+
+    const INPUT: &[u8] = b"000040:4:name,05:hello,4:tags,13:1:a,6:1.0500^]}";
+
+    #[test]
+    fn reencodes_original_bytes() {
+        let entry = parse_lossless(INPUT).unwrap();
+        assert_eq!(entry.encode(), INPUT);
+        assert_eq!(entry.encoded_len(), INPUT.len());
+        assert_eq!(entry.to_entry(), parse(INPUT).unwrap());
+        assert_ne!(encode(&parse(INPUT).unwrap()), INPUT);
+    }
+
+    #[test]
+    fn reencodes_modified_entries_from_data() {
+        let mut entry = parse_lossless(INPUT).unwrap();
+        if let LosslessData::Dictionary(d) = entry.data_mut() {
+            if let LosslessData::List(tags) = d[1].1.data_mut() {
+                tags.push(LosslessEntry::from(parse(b"1:b,").unwrap()));
+            }
+        }
+        assert_eq!(entry.encode(), &b"44:4:name,05:hello,4:tags,17:1:a,6:1.0500^1:b,]}"[..]);
+        assert_eq!(entry.get(b"name")[0].source(), Some(&b"05:hello,"[..]));
+        assert_eq!(entry.get(b"tags")[0].source(), None);
+        assert_eq!(parse(&entry.encode()).unwrap(), entry.to_entry());
+    }
+
+    #[test]
+    fn reports_parse_errors() {
+        assert_eq!(parse_lossless(b"8:1:1#1:a,}").unwrap_err(), TNetStrError::DictionaryKeyWasNotBytes);
+        assert_eq!(parse_lossless(b"4:1:a,}").unwrap_err(), TNetStrError::UnbalancedDictionary);
+    }
+}
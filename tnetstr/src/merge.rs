@@ -0,0 +1,247 @@
+use super::{
+    TNetData,
+    TNetDictionary,
+    TNetEntry,
+    TNetStrError
+};
+use super::diff::{
+    DiffPath,
+    PathSegment
+};
+use super::patch::new_entry;
+use alloc::{
+    string::ToString,
+    vec,
+    vec::Vec
+};
+
+/// How `merge` combines a list in the base with a list in the overlay
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum ListMerge {
+    /// The overlay's list replaces the base's
+    Replace,
+    /// The overlay's items are added after the base's
+    Append,
+    /// Items at the same index are merged, and the overlay's extra items are appended
+    ByIndex
+}
+
+/// What `merge` does when the base and overlay have values of different types at the same path
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum TypeConflict {
+    /// Fails with `TNetStrError::MergeConflict`
+    Error,
+    /// The overlay's value replaces the base's
+    OverlayWins
+}
+
+/// How `merge` matches up dictionary keys that appear more than once
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum DuplicateKeys {
+    /// The nth pair with a key in the overlay is merged with the nth pair with that key in the
+    /// base, and extra pairs in the overlay are added
+    ByOccurrence,
+    /// The overlay's pairs with a key replace all of the base's pairs with that key
+    Replace,
+    /// The overlay's pairs are added alongside the base's pairs with the same key
+    Append
+}
+
+/// The rules followed by `merge`. The default replaces lists, fails on type conflicts, treats Null
+/// as deleting a key and matches duplicate keys by occurrence.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct MergePolicy {
+    pub lists: ListMerge,
+    pub type_conflict: TypeConflict,
+    /// Whether a Null value in an overlay dictionary removes the pair from the base rather than
+    /// being merged as a value. With `DuplicateKeys::ByOccurrence` only the matching occurrence is
+    /// removed, and otherwise every pair with the key is.
+    pub null_deletes: bool,
+    pub duplicate_keys: DuplicateKeys
+}
+
+impl Default for MergePolicy {
+    fn default() -> MergePolicy {
+        MergePolicy {
+            lists: ListMerge::Replace,
+            type_conflict: TypeConflict::Error,
+            null_deletes: true,
+            duplicate_keys: DuplicateKeys::ByOccurrence
+        }
+    }
+}
+
+/// The values of a dictionary grouped by key, in the order each key first appears
+fn key_groups(dict: TNetDictionary) -> Vec<(Vec<u8>, Vec<TNetEntry>)> {
+    let mut groups: Vec<(Vec<u8>, Vec<TNetEntry>)> = vec![];
+    for (key, value) in dict.entries {
+        let key = key.data.as_bytes().unwrap_or(&[]).to_vec();
+        match groups.iter_mut().find(|(k, _)| *k == key) {
+            Some((_, values)) => values.push(value),
+            None => groups.push((key, vec![value]))
+        }
+    }
+    groups
+}
+
+struct Merger {
+    policy: MergePolicy,
+    path: Vec<PathSegment>
+}
+
+impl Merger {
+
+    fn deletes(&self, value: &TNetEntry) -> bool {
+        self.policy.null_deletes && value.data.is_null()
+    }
+
+    fn within(&mut self, segment: PathSegment, base: &mut TNetEntry, overlay: TNetEntry) -> Result<(), TNetStrError> {
+        self.path.push(segment);
+        self.entries(base, overlay)?;
+        self.path.pop();
+        Ok(())
+    }
+
+    fn entries(&mut self, base: &mut TNetEntry, overlay: TNetEntry) -> Result<(), TNetStrError> {
+        if base.data.data_type() != overlay.data.data_type() {
+            return match self.policy.type_conflict {
+                TypeConflict::Error => Err(TNetStrError::MergeConflict(DiffPath(self.path.clone()).to_string())),
+                TypeConflict::OverlayWins => {
+                    *base = overlay;
+                    Ok(())
+                }
+            }
+        }
+        match (&mut base.data, overlay.data) {
+            (TNetData::Dictionary(b), TNetData::Dictionary(o)) => self.dictionaries(b, o),
+            (TNetData::List(b), TNetData::List(o)) => self.lists(b, o),
+            (data, overlay) => {
+                *data = overlay;
+                Ok(())
+            }
+        }
+    }
+
+    fn dictionaries(&mut self, base: &mut TNetDictionary, overlay: TNetDictionary) -> Result<(), TNetStrError> {
+        let mut deleted = vec![false; base.entries.len()];
+        let mut added = vec![];
+        for (key, values) in key_groups(overlay) {
+            let indexes: Vec<usize> = base.entries.iter()
+                .enumerate()
+                .filter(|(_, (k, _))| k.data.as_bytes() == Some(&key))
+                .map(|(i, _)| i)
+                .collect();
+            match self.policy.duplicate_keys {
+                DuplicateKeys::ByOccurrence => for (occurrence, value) in values.into_iter().enumerate() {
+                    match indexes.get(occurrence) {
+                        Some(&i) if self.deletes(&value) => deleted[i] = true,
+                        Some(&i) => self.within(PathSegment::Key { key: key.clone(), occurrence }, &mut base.entries[i].1, value)?,
+                        None if self.deletes(&value) => {},
+                        None => added.push((new_entry(TNetData::Bytes(key.clone())), value))
+                    }
+                },
+                DuplicateKeys::Replace | DuplicateKeys::Append => {
+                    if self.policy.duplicate_keys == DuplicateKeys::Replace || values.iter().any(|value| self.deletes(value)) {
+                        indexes.iter().for_each(|&i| deleted[i] = true);
+                    }
+                    for value in values {
+                        if !self.deletes(&value) {
+                            added.push((new_entry(TNetData::Bytes(key.clone())), value));
+                        }
+                    }
+                }
+            }
+        }
+        let mut deleted = deleted.into_iter();
+        base.entries.retain(|_| !deleted.next().expect("Bug - a pair was not marked"));
+        base.entries.extend(added);
+        Ok(())
+    }
+
+    fn lists(&mut self, base: &mut Vec<TNetEntry>, overlay: Vec<TNetEntry>) -> Result<(), TNetStrError> {
+        match self.policy.lists {
+            ListMerge::Replace => *base = overlay,
+            ListMerge::Append => base.extend(overlay),
+            ListMerge::ByIndex => for (i, item) in overlay.into_iter().enumerate() {
+                match base.get_mut(i) {
+                    Some(existing) => self.within(PathSegment::Index(i), existing, item)?,
+                    None => base.push(item)
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Merges `overlay` into `base`, for layering configuration.
+///
+/// Dictionaries are merged recursively: keys only in the base are kept, keys only in the overlay
+/// are added after the base's pairs, and keys in both have their values merged. Lists are combined
+/// as `policy.lists` says, and any other value in the overlay replaces the base's. If a merge fails
+/// the base is left unchanged. On success the sizes of the base are updated.
+pub fn merge(base: &mut TNetEntry, overlay: TNetEntry, policy: MergePolicy) -> Result<(), TNetStrError> {
+    let mut merged = base.clone();
+    let mut merger = Merger {
+        policy,
+        path: vec![]
+    };
+    merger.entries(&mut merged, overlay)?;
+    merged.update_sizes();
+    *base = merged;
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+    use super::super::parse;
+
+    fn merged(base: &[u8], overlay: &[u8], policy: MergePolicy) -> Result<TNetEntry, TNetStrError> {
+        let mut base = parse(base).unwrap();
+        merge(&mut base, parse(overlay).unwrap(), policy)?;
+        Ok(base)
+    }
+
+    #[test]
+    fn merges_nested_dictionaries() {
+        let result = merged(
+            b"58:4:name,3:bob,2:db,22:4:host,1:a,4:port,1:1#}4:tags,4:1:x,]}",
+            b"55:4:name,0:~2:db,22:4:port,1:2#4:user,1:u,}4:tags,4:1:y,]}",
+            MergePolicy::default()
+        );
+        assert_eq!(result, parse(b"56:2:db,33:4:host,1:a,4:port,1:2#4:user,1:u,}4:tags,4:1:y,]}"));
+    }
+
+    #[test]
+    fn combines_lists_by_policy() {
+        let with = |lists| MergePolicy { lists, ..MergePolicy::default() };
+        let (base, overlay) = (b"12:1:a,1:b,1:c,]", b"4:1:x,]");
+        assert_eq!(merged(base, overlay, with(ListMerge::Replace)), parse(b"4:1:x,]"));
+        assert_eq!(merged(base, overlay, with(ListMerge::Append)), parse(b"16:1:a,1:b,1:c,1:x,]"));
+        assert_eq!(merged(base, overlay, with(ListMerge::ByIndex)), parse(b"12:1:x,1:b,1:c,]"));
+    }
+
+    #[test]
+    fn reports_or_overrides_type_conflicts() {
+        let mut base = parse(b"8:1:a,1:1#}").unwrap();
+        let overlay = parse(b"8:1:a,1:s,}").unwrap();
+        assert_eq!(merge(&mut base, overlay.clone(), MergePolicy::default()), Err(TNetStrError::MergeConflict("$[\"a\"]".to_string())));
+        assert_eq!(base, parse(b"8:1:a,1:1#}").unwrap());
+        let policy = MergePolicy { type_conflict: TypeConflict::OverlayWins, ..MergePolicy::default() };
+        assert_eq!(merge(&mut base, overlay.clone(), policy), Ok(()));
+        assert_eq!(base, overlay);
+        let keep_nulls = MergePolicy { null_deletes: false, ..MergePolicy::default() };
+        assert!(merged(b"8:1:a,1:1#}", b"7:1:a,0:~}", keep_nulls).is_err());
+    }
+
+    #[test]
+    fn handles_duplicate_keys_by_policy() {
+        let with = |duplicate_keys| MergePolicy { duplicate_keys, ..MergePolicy::default() };
+        let base = b"16:1:k,1:1#1:k,1:2#}";
+        assert_eq!(merged(base, b"8:1:k,1:3#}", with(DuplicateKeys::ByOccurrence)), parse(b"16:1:k,1:3#1:k,1:2#}"));
+        assert_eq!(merged(base, b"8:1:k,1:3#}", with(DuplicateKeys::Replace)), parse(b"8:1:k,1:3#}"));
+        assert_eq!(merged(base, b"8:1:k,1:3#}", with(DuplicateKeys::Append)), parse(b"24:1:k,1:1#1:k,1:2#1:k,1:3#}"));
+        assert_eq!(merged(base, b"7:1:k,0:~}", with(DuplicateKeys::ByOccurrence)), parse(b"8:1:k,1:2#}"));
+    }
+}
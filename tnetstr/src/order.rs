@@ -0,0 +1,158 @@
+use super::{
+    TNetData,
+    TNetDictionary
+};
+use super::diff::{
+    equal_data,
+    equal_dictionaries
+};
+use core::cmp::Ordering;
+
+impl TNetData {
+
+    /// Compares values as `Ord` does, except that integers and floats are ranked together and
+    /// compared by value, so `1`, `1.0` and `1.00` are equal. This applies within lists and
+    /// dictionaries too. It is a total order on values up to this equality, so it can be given to
+    /// `sort_by` and `dedup_by`, but unlike `Ord` it does not agree with `Eq` or `Hash`.
+    pub fn cmp_numeric(&self, other: &TNetData) -> Ordering {
+        match (self, other) {
+            (TNetData::Integer(a), TNetData::Float(b)) => b.cmp_integer(*a).reverse(),
+            (TNetData::Float(a), TNetData::Integer(b)) => a.cmp_integer(*b),
+            (TNetData::Float(a), TNetData::Float(b)) => a.cmp_value(b),
+            (TNetData::List(a), TNetData::List(b)) => a.iter()
+                .zip(b)
+                .map(|(a, b)| a.data.cmp_numeric(&b.data))
+                .find(|ordering| ordering.is_ne())
+                .unwrap_or_else(|| a.len().cmp(&b.len())),
+            (TNetData::Dictionary(a), TNetData::Dictionary(b)) => a.entries.iter()
+                .zip(&b.entries)
+                .map(|((a_key, a_value), (b_key, b_value))| a_key.data.cmp(&b_key.data)
+                    .then_with(|| a_value.data.cmp_numeric(&b_value.data)))
+                .find(|ordering| ordering.is_ne())
+                .unwrap_or_else(|| a.entries.len().cmp(&b.entries.len())),
+            (a, b) => a.cmp(b)
+        }
+    }
+
+    /// Whether `cmp_numeric` finds the values equal
+    pub fn eq_numeric(&self, other: &TNetData) -> bool {
+        self.cmp_numeric(other).is_eq()
+    }
+
+    /// Whether the values are equal regardless of the order of dictionary pairs, here and in
+    /// anything they contain. The values of a repeated key are still compared in order, and the
+    /// `size` of entries is ignored.
+    pub fn eq_unordered(&self, other: &TNetData) -> bool {
+        equal_data(self, other)
+    }
+}
+
+impl TNetDictionary {
+
+    /// Whether the dictionaries have the same pairs regardless of their order, as
+    /// `TNetData::eq_unordered`
+    pub fn eq_unordered(&self, other: &TNetDictionary) -> bool {
+        equal_dictionaries(self, other)
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+    use super::super::{
+        parse,
+        TNetFloat
+    };
+    use alloc::{
+        collections::BTreeSet,
+        vec,
+        vec::Vec
+    };
+
+    fn float(s: &str) -> TNetData {
+        TNetData::Float(TNetFloat::from_decimal_str(s).unwrap())
+    }
+
+    fn data(input: &[u8]) -> TNetData {
+        parse(input).unwrap().data
+    }
+
+    #[test]
+    fn orders_by_type_then_value() {
+        let set: BTreeSet<TNetData> = vec![
+            data(b"4:1:a,]"),
+            TNetData::Bytes(b"b".to_vec()),
+            float("2.0"),
+            TNetData::Integer(3),
+            data(b"8:1:a,1:1#}"),
+            TNetData::Bytes(b"a".to_vec()),
+            float("1.50"),
+            float("1.5"),
+            float("1.25"),
+            TNetData::Boolean(true),
+            TNetData::Null,
+            TNetData::Integer(-4),
+            TNetData::Boolean(false),
+            float("1.5")
+        ].into_iter().collect();
+        let expected = vec![
+            TNetData::Null,
+            TNetData::Boolean(false),
+            TNetData::Boolean(true),
+            TNetData::Integer(-4),
+            TNetData::Integer(3),
+            float("1.25"),
+            float("1.5"),
+            float("1.50"),
+            float("2.0"),
+            TNetData::Bytes(b"a".to_vec()),
+            TNetData::Bytes(b"b".to_vec()),
+            data(b"4:1:a,]"),
+            data(b"8:1:a,1:1#}")
+        ];
+        assert_eq!(set.into_iter().collect::<Vec<_>>(), expected);
+    }
+
+    #[test]
+    fn compares_integers_and_floats_numerically() {
+        assert!(TNetData::Integer(1).eq_numeric(&float("1.0")));
+        assert!(float("1.50").eq_numeric(&float("1.5")));
+        assert_eq!(TNetData::Integer(2).cmp_numeric(&float("1.5")), Ordering::Greater);
+        assert_eq!(TNetData::Integer(-1).cmp_numeric(&float("0.0")), Ordering::Less);
+        assert_eq!(float("1.5").cmp_numeric(&TNetData::Integer(2)), Ordering::Less);
+        assert!(!data(b"11:1:1#4:1.50^]").eq_numeric(&data(b"10:3:1.0^1:2#]")));
+        assert!(data(b"11:1:1#4:1.50^]").eq_numeric(&data(b"12:3:1.0^3:1.5^]")));
+
+        let mut values = vec![float("2.5"), TNetData::Integer(2), float("2.0"), TNetData::Bytes(b"x".to_vec())];
+        values.sort_by(TNetData::cmp_numeric);
+        values.dedup_by(|a, b| a.eq_numeric(b));
+        assert_eq!(values, vec![TNetData::Integer(2), float("2.5"), TNetData::Bytes(b"x".to_vec())]);
+    }
+
+    #[test]
+    fn compares_leading_fractional_zeros() {
+        assert!(!float("0.05").eq_numeric(&float("0.5")));
+        assert_eq!(float("0.05").cmp_numeric(&float("0.5")), Ordering::Less);
+        assert!(!float("1.05").eq_numeric(&float("1.5")));
+        assert_eq!(float("1.05").cmp_numeric(&float("1.5")), Ordering::Less);
+        assert!(float("1.050").eq_numeric(&float("1.05")));
+        assert_eq!(float("0.5").cmp_numeric(&float("0.05")), Ordering::Greater);
+        assert_eq!(float("0.001").cmp_numeric(&float("0.01")), Ordering::Less);
+        assert_eq!(float("10.0").cmp_numeric(&float("9.99")), Ordering::Greater);
+        assert!(float("0.0").eq_numeric(&float("0.00")));
+        assert!(float("0.00").eq_numeric(&TNetData::Integer(0)));
+        assert_eq!(float("1.05"), data(b"4:1.05^"));
+        assert!(float("1.05") < float("1.5"));
+    }
+
+    #[test]
+    fn compares_dictionaries_regardless_of_order() {
+        let a = data(b"32:1:a,1:1#1:b,16:1:x,1:y,1:z,1:w,}}");
+        let b = data(b"32:1:b,16:1:z,1:w,1:x,1:y,}1:a,1:1#}");
+        assert_ne!(a, b);
+        assert!(a.eq_unordered(&b));
+        assert!(a.as_dictionary().unwrap().eq_unordered(b.as_dictionary().unwrap()));
+        assert!(!a.eq_unordered(&data(b"32:1:b,16:1:z,1:w,1:x,1:y,}1:a,1:2#}")));
+    }
+}
@@ -1,27 +1,35 @@
 use super::{
-    TNetEntry,
     TNetStrError,
+    TNetFloat,
+    DataType
+};
+#[cfg(feature = "alloc")]
+use super::{
+    TNetEntry,
     TNetList,
     TNetDictionary,
-    TNetFloat,
-    DataType,
     TNetData
 };
-use std::{
-    io::Read,
-    str
+#[cfg(feature = "alloc")]
+use super::extension::Extensions;
+#[cfg(feature = "std")]
+use alloc::{
+    string::ToString,
+    vec,
+    vec::Vec
 };
-
-const INVALID_LENGTH_VALUE: u32 = 1111111111 as u32;
+use core::str;
+#[cfg(feature = "std")]
+use std::io::{ErrorKind, Read};
 
 fn parse_string(bytes: &[u8]) -> Result<&str, TNetStrError> {
-    match str::from_utf8(&bytes) {
+    match str::from_utf8(bytes) {
         Err(_) => Err(TNetStrError::DataNotUTF8Compatible),
         Ok(s) => Ok(s)
     }
 }
 
-fn parse_val<T>(bytes: &[u8]) -> Result<T, TNetStrError> where T: std::str::FromStr {
+pub(crate) fn parse_val<T>(bytes: &[u8]) -> Result<T, TNetStrError> where T: str::FromStr {
     let s = parse_string(bytes)?;
     match s.parse() {
         Err(_) => Err(TNetStrError::CouldNotParseData),
@@ -29,75 +37,155 @@ fn parse_val<T>(bytes: &[u8]) -> Result<T, TNetStrError> where T: std::str::From
     }
 }
 
-fn parse_float(bytes: &[u8]) -> Result<TNetFloat, TNetStrError> {
+pub(crate) fn parse_float(bytes: &[u8]) -> Result<TNetFloat, TNetStrError> {
     TNetFloat::from_decimal_str(parse_string(bytes)?)
 }
 
-fn parse_list(bytes: &[u8]) -> Result<Vec<TNetEntry>, TNetStrError> {
-    let mut position = 0;
-    let mut list: TNetList = vec![];
-    while position < bytes.len() {
-        let tnetstr = parse_entry(bytes, &mut position)?;
-        list.push(tnetstr);
+/// The items of list or dictionary data, each read by a function that advances the position past
+/// it. Reading stops after the first error.
+pub(crate) struct Items<F> {
+    position: usize,
+    end: usize,
+    read: F
+}
+
+/// Reads the items of container data running from `start` to `end` with `read`
+pub(crate) fn items<T, F>(start: usize, end: usize, read: F) -> Items<F>
+where
+    F: FnMut(&mut usize) -> Result<T, TNetStrError>
+{
+    Items {
+        position: start,
+        end,
+        read
+    }
+}
+
+impl<T, F> Iterator for Items<F>
+where
+    F: FnMut(&mut usize) -> Result<T, TNetStrError>
+{
+    type Item = Result<T, TNetStrError>;
+
+    fn next(&mut self) -> Option<Result<T, TNetStrError>> {
+        if self.position >= self.end {
+            return None
+        }
+        let item = (self.read)(&mut self.position);
+        if item.is_err() {
+            self.position = self.end;
+        }
+        Some(item)
+    }
+}
+
+/// The key and value pairs of dictionary data. A key without a value, or whose type is not bytes,
+/// is an error.
+pub(crate) struct Pairs<F, K> {
+    items: Items<F>,
+    key_type: K
+}
+
+/// Reads the pairs of dictionary data running from `start` to `end` with `read`, using
+/// `key_type` to check that each key is bytes
+pub(crate) fn pairs<T, F, K>(start: usize, end: usize, read: F, key_type: K) -> Pairs<F, K>
+where
+    F: FnMut(&mut usize) -> Result<T, TNetStrError>,
+    K: Fn(&T) -> DataType
+{
+    Pairs {
+        items: items(start, end, read),
+        key_type
     }
-    Ok(list)
 }
 
-fn parse_dictionary(bytes: &[u8]) -> Result<TNetDictionary, TNetStrError> {
-    let mut position = 0;
-    let mut dict = TNetDictionary::new();
-    let len = bytes.len();
-    while position < len {
-        let key = parse_entry(bytes, &mut position)?;
-        if !(position < len) {
+impl<T, F, K> Pairs<F, K>
+where
+    F: FnMut(&mut usize) -> Result<T, TNetStrError>,
+    K: Fn(&T) -> DataType
+{
+    fn value(&mut self, key: T) -> Result<(T, T), TNetStrError> {
+        let items = &mut self.items;
+        if items.position >= items.end {
             return Err(TNetStrError::UnbalancedDictionary)
         }
-        let value = parse_entry(bytes, &mut position)?;
-        dict.add(key, value)?;
+        let value = (items.read)(&mut items.position)?;
+        if (self.key_type)(&key) != DataType::Bytes {
+            return Err(TNetStrError::DictionaryKeyWasNotBytes)
+        }
+        Ok((key, value))
     }
-    Ok(dict)
 }
 
-fn read_length_byte(byte: &u8, bytes_read: &mut Vec<u8>) -> Result<Option<u32>, TNetStrError> {
+impl<T, F, K> Iterator for Pairs<F, K>
+where
+    F: FnMut(&mut usize) -> Result<T, TNetStrError>,
+    K: Fn(&T) -> DataType
+{
+    type Item = Result<(T, T), TNetStrError>;
+
+    fn next(&mut self) -> Option<Result<(T, T), TNetStrError>> {
+        let pair = match self.items.next()? {
+            Ok(key) => self.value(key),
+            Err(e) => Err(e)
+        };
+        if pair.is_err() {
+            self.items.position = self.items.end;
+        }
+        Some(pair)
+    }
+}
+
+#[cfg(feature = "alloc")]
+fn parse_list(bytes: &[u8], extensions: &Extensions) -> Result<TNetList, TNetStrError> {
+    items(0, bytes.len(), |position| parse_entry(bytes, position, extensions)).collect()
+}
+
+#[cfg(feature = "alloc")]
+fn parse_dictionary(bytes: &[u8], extensions: &Extensions) -> Result<TNetDictionary, TNetStrError> {
+    Ok(TNetDictionary {
+        entries: pairs(0, bytes.len(), |position| parse_entry(bytes, position, extensions), |key: &TNetEntry| key.data_type)
+            .collect::<Result<_, _>>()?
+    })
+}
+
+/// The digits of a length field read so far
+#[derive(Default)]
+pub(crate) struct LengthDigits {
+    count: usize,
+    value: u32
+}
+
+pub(crate) fn read_length_byte(byte: &u8, digits: &mut LengthDigits) -> Result<Option<u32>, TNetStrError> {
     match byte {
         b':' => {
-            match bytes_read.len() > 0 /*length must be specified*/ {
+            match digits.count > 0 /*length must be specified*/ {
                 false => Err(TNetStrError::NoLengthSpecified),
-                true => {
-                    match str::from_utf8(bytes_read) {
-                        Err(_) => Err(TNetStrError::LengthWasNotValidUTF8),
-                        Ok(len_str) => match len_str.parse::<u32>() {
-                            Err(_) => {
-                                println!("Failed to parse: {}", len_str);
-                                Err(TNetStrError::CouldNotParseLength)
-                            },
-                            Ok(len) => Ok(Some(len))
-                        }
-                    }
-                }
+                true => Ok(Some(digits.value))
             }
         }
         b'0'|b'1'|b'2'|b'3'|b'4'|b'5'|b'6'|b'7'|b'8'|b'9' => {
-            bytes_read.push(byte.clone());
-            if bytes_read.len() > 9 /*more than 9 digits not allowed by spec*/ {
+            digits.count += 1;
+            if digits.count > 9 /*more than 9 digits not allowed by spec*/ {
                 Err(TNetStrError::LengthTooLong)
             } else {
+                digits.value = digits.value * 10 + u32::from(byte - b'0');
                 Ok(None)
             }
         },
         u => {
-            Err(TNetStrError::NonASCIINumericValueInLengthField(u.clone()))
+            Err(TNetStrError::NonASCIINumericValueInLengthField(*u))
         }
     }
 }
 
-fn parse_length(input: &[u8], position: &mut usize) -> Result<u32, TNetStrError> {
+pub(crate) fn parse_length(input: &[u8], position: &mut usize) -> Result<u32, TNetStrError> {
     let mut index: usize = 0;
-    let mut bytes_read: Vec<u8> = Vec::with_capacity(9);
+    let mut digits = LengthDigits::default();
     let rest = &input[*position ..];
     for byte in rest {
         index += 1;
-        match read_length_byte(byte, &mut bytes_read)? {
+        match read_length_byte(byte, &mut digits)? {
             Some(len) => {
                 *position += index;
                 return Ok(len)
@@ -109,96 +197,181 @@ fn parse_length(input: &[u8], position: &mut usize) -> Result<u32, TNetStrError>
     Err(TNetStrError::LengthTerminatorNotFound)
 }
 
-fn parse_entry(input: &[u8], position: &mut usize) -> Result<TNetEntry, TNetStrError> {
-    let len = parse_length(input, position)?;
-    let data_bytes: Vec<u8> = if len > 0 {
-        input[*position..((len as usize) + *position)]
-            .iter()
-            .map(|b| b.clone())
-            .collect::<Vec<u8>>()
-    } else {
-        vec![]
-    };
+/// Reads the length, data and type tag of the tnetstring at `position`, without interpreting the data.
+/// On success `position` is moved past the type tag.
+pub(crate) fn parse_frame<'a>(input: &'a [u8], position: &mut usize) -> Result<(DataType, &'a [u8]), TNetStrError> {
+    parse_frame_with(input, position, DataType::from_byte)
+}
 
-    *position += len as usize;
-    let t = match input.get(*position) {
+/// As `parse_frame`, reading the type tag with `data_type`
+pub(crate) fn parse_frame_with<'a, F>(input: &'a [u8], position: &mut usize, data_type: F) -> Result<(DataType, &'a [u8]), TNetStrError>
+where
+    F: Fn(&u8) -> Result<DataType, TNetStrError>
+{
+    let len = parse_length(input, position)? as usize;
+    let start = *position;
+    let end = start + len;
+    let t = match input.get(end) {
         Some(t) => t,
         None => return Err(TNetStrError::LengthWasNotAccurate)
     };
 
-    let t = DataType::from_byte(t)?;
-    *position += 1;
+    let t = data_type(t)?;
+    *position = end + 1;
+    Ok((t, &input[start..end]))
+}
+
+#[cfg(feature = "alloc")]
+fn parse_entry(input: &[u8], position: &mut usize, extensions: &Extensions) -> Result<TNetEntry, TNetStrError> {
+    let (t, data_bytes) = parse_frame_with(input, position, |c| extensions.data_type(c))?;
 
     let data = match t {
-        DataType::Bytes => TNetData::Bytes(data_bytes),
+        DataType::Bytes => TNetData::Bytes(data_bytes.to_vec()),
         DataType::Null => TNetData::Null,
-        DataType::Dictionary => TNetData::Dictionary(parse_dictionary(&data_bytes)?),
-        DataType::List => TNetData::List(parse_list(&data_bytes)?),
-        DataType::Integer => TNetData::Integer(parse_val(&data_bytes)?),
-        DataType::Float => TNetData::Float(parse_float(&data_bytes)?),
-        DataType::Boolean => TNetData::Boolean(parse_val(&data_bytes)?)
+        DataType::Dictionary => TNetData::Dictionary(parse_dictionary(data_bytes, extensions)?),
+        DataType::List => TNetData::List(parse_list(data_bytes, extensions)?),
+        DataType::Integer => TNetData::Integer(parse_val(data_bytes)?),
+        DataType::Float => TNetData::Float(parse_float(data_bytes)?),
+        DataType::Boolean => TNetData::Boolean(parse_val(data_bytes)?),
+        DataType::Extension(tag) => match extensions.decode(tag, data_bytes)? {
+            data @ TNetData::Extension { .. } => data,
+            data => {
+                let mut entry = TNetEntry::from(data);
+                entry.update_sizes();
+                return Ok(entry)
+            }
+        }
     };
 
     Ok(TNetEntry {
-        size: len,
+        size: data_bytes.len() as u32,
         data_type: t,
-        data: data
+        data
     })
 }
 
 /// Attempts to read the first tnetstring from a slice of bytes
+#[cfg(feature = "alloc")]
 pub fn parse(data: &[u8]) -> Result<TNetEntry, TNetStrError> {
+    parse_with(data, &Extensions::new())
+}
+
+#[cfg(feature = "alloc")]
+pub(crate) fn parse_with(data: &[u8], extensions: &Extensions) -> Result<TNetEntry, TNetStrError> {
     let mut pos = 0;
-    parse_entry(data, &mut pos)
+    parse_entry(data, &mut pos, extensions)
 }
 
-/// Attempts to read the next tnetstring from a byte stream
-pub fn parse_stream(reader: &mut Read) -> Result<TNetEntry, TNetStrError>
-{
-    let mut len_chars: Vec<u8> = Vec::with_capacity(9);
-    let mut len_buff:[u8; 1] = [0];
-    let data_length: u32;
-    loop {
-        match reader.read_exact(&mut len_buff) {
-            Err(e) => return Err(TNetStrError::StreamReadFailed(format!("{:?}", e))),
-            Ok(_) => {
-                match read_length_byte(&len_buff[0], &mut len_chars)? {
-                    Some(l) => {
-                        data_length = l;
-                        break;
-                    },
-                    None => continue
-                }
+fn validate_entry(input: &[u8], position: &mut usize) -> Result<DataType, TNetStrError> {
+    let (t, data_bytes) = parse_frame(input, position)?;
+
+    match t {
+        DataType::Bytes | DataType::Null => {},
+        DataType::Integer => { parse_val::<i64>(data_bytes)?; },
+        DataType::Float => { parse_float(data_bytes)?; },
+        DataType::Boolean => { parse_val::<bool>(data_bytes)?; },
+        DataType::Extension(tag) => return Err(TNetStrError::UnrecognizedDataType(tag)),
+        DataType::List => {
+            for item in items(0, data_bytes.len(), |position| validate_entry(data_bytes, position)) {
+                item?;
+            }
+        },
+        DataType::Dictionary => {
+            for pair in pairs(0, data_bytes.len(), |position| validate_entry(data_bytes, position), |key| *key) {
+                pair?;
             }
         }
     };
+    Ok(t)
+}
 
-    if data_length == INVALID_LENGTH_VALUE {
-        return Err(TNetStrError::StreamReadFailed("Reached end of stream without reading a full length value".to_string()))
-    }
-    // create full tnetentry buffer
-    let mut all_buff: Vec<u8> = vec![0; len_chars.len() + (data_length + 2) as usize];
+/// Checks that the first tnetstring in a slice of bytes is well formed, applying the same rules as
+/// `parse` to all nested values without allocating. Returns the length of the tnetstring in bytes.
+pub fn validate(data: &[u8]) -> Result<usize, TNetStrError> {
+    let mut pos = 0;
+    validate_entry(data, &mut pos)?;
+    Ok(pos)
+}
+
+#[cfg(feature = "std")]
+/// Adds a byte read from a stream to the length characters in `buffer`, returning the data length
+/// once the terminator is reached. Shared by the sync and async stream readers.
+pub(crate) fn push_length_byte(byte: u8, digits: &mut LengthDigits, buffer: &mut Vec<u8>) -> Result<Option<u32>, TNetStrError> {
+    buffer.push(byte);
+    read_length_byte(&byte, digits)
+}
 
-    // copy length characters in
-    for i in 0..len_chars.len() {
-        all_buff[i] = len_chars[i].clone();
+#[cfg(feature = "std")]
+/// Makes room after the length characters in `buffer` for the data and type tag of a tnetstring,
+/// returning the part of the buffer to read them into
+pub(crate) fn frame_data_buffer(buffer: &mut Vec<u8>, data_length: u32) -> &mut [u8] {
+    let header_length = buffer.len();
+    // the data content, + 1 for the type declaration
+    buffer.resize(header_length + (data_length + 1) as usize, 0);
+    &mut buffer[header_length..]
+}
+
+#[cfg(feature = "std")]
+/// Reads the length of the next tnetstring from a byte stream, replacing the contents of `buffer`
+/// with the length characters and terminator.
+/// Returns `Ok(None)` if the stream ended cleanly before the first byte of the length.
+pub(crate) fn read_frame_length(reader: &mut dyn Read, buffer: &mut Vec<u8>) -> Result<Option<u32>, TNetStrError> {
+    let mut digits = LengthDigits::default();
+    let mut len_buff:[u8; 1] = [0];
+
+    // the first byte is read separately, so that the end of the stream can be told apart from a truncated length
+    loop {
+        match reader.read(&mut len_buff) {
+            Ok(0) => return Ok(None),
+            Ok(_) => break,
+            Err(ref e) if e.kind() == ErrorKind::Interrupted => continue,
+            Err(e) => return Err(TNetStrError::from(e))
+        }
+    }
+    buffer.clear();
+    loop {
+        if let Some(data_length) = push_length_byte(len_buff[0], &mut digits, buffer)? {
+            return Ok(Some(data_length))
+        }
+        reader.read_exact(&mut len_buff)?;
     }
-    // set the length terminator byte
-    all_buff[len_chars.len()] = b':';
+}
 
-    // read the expected number of bytes for the data content, + 1 for the type declaration
-    match reader.read_exact(&mut all_buff[len_chars.len() + 1..]) {
-        Err(e) => return Err(TNetStrError::StreamReadFailed(format!("{:?}", e))),
-        Ok(_) => {
-            let mut pos = 0;
-            parse_entry(&all_buff, &mut pos)
+#[cfg(feature = "std")]
+/// Reads the data and type tag of a tnetstring with the given length from a byte stream, appending
+/// them to `buffer`
+pub(crate) fn read_frame_data(reader: &mut dyn Read, data_length: u32, buffer: &mut Vec<u8>) -> Result<(), TNetStrError> {
+    reader.read_exact(frame_data_buffer(buffer, data_length))?;
+    Ok(())
+}
+
+#[cfg(feature = "std")]
+/// Reads the next whole tnetstring from a byte stream into `buffer`, replacing its contents.
+/// Returns `Ok(false)` if the stream ended cleanly before the first byte of the length.
+pub(crate) fn read_frame(reader: &mut dyn Read, buffer: &mut Vec<u8>) -> Result<bool, TNetStrError> {
+    match read_frame_length(reader, buffer)? {
+        None => Ok(false),
+        Some(data_length) => {
+            read_frame_data(reader, data_length, buffer)?;
+            Ok(true)
         }
     }
 }
 
+#[cfg(feature = "std")]
+/// Attempts to read the next tnetstring from a byte stream
+pub fn parse_stream(reader: &mut dyn Read) -> Result<TNetEntry, TNetStrError>
+{
+    let mut buffer = vec![];
+    if !read_frame(reader, &mut buffer)? {
+        return Err(TNetStrError::StreamReadFailed("Reached end of stream without reading a full length value".to_string()))
+    }
+    parse(&buffer)
+}
+
 
 
-#[cfg(test)]
+#[cfg(all(test, feature = "alloc"))]
 mod test {
 
     use super::*;
@@ -243,7 +416,7 @@ mod test {
     }
 
     fn check(input: &[u8], expected: &TNetEntry) {
-        match parse_list(input) {
+        match parse_list(input, &Extensions::new()) {
             Err(e) => panic!("Failed to parse: {}", e),
             Ok(entries) => {
                 assert_eq!(entries.len(), 1);
@@ -273,7 +446,7 @@ mod test {
         check(input, &TNetEntry {
             size: 10,
             data_type: DataType::Bytes,
-            data: TNetData::Bytes("aaaaaaaaaa".as_bytes().iter().map(|b|b.clone()).collect())
+            data: TNetData::Bytes(b"aaaaaaaaaa".to_vec())
         });
     }
 
@@ -295,7 +468,9 @@ mod test {
             data_type: DataType::Float,
             data: TNetData::Float(TNetFloat{
                 integral: 12,
-                fractional: 543
+                fractional: 543,
+                leading_zeros: 0,
+                ..TNetFloat::default()
             })
         });
     }
@@ -325,12 +500,12 @@ mod test {
                 TNetEntry {
                     size: 6,
                     data_type: DataType::Float,
-                    data: TNetData::Float(TNetFloat{integral: 0, fractional: 4529})
+                    data: TNetData::Float(TNetFloat{integral: 0, fractional: 4529, leading_zeros: 0, ..TNetFloat::default()})
                 },
                 TNetEntry {
                     size: 5,
                     data_type: DataType::Bytes,
-                    data: TNetData::Bytes("abcde".as_bytes().iter().map(|b|b.clone()).collect())
+                    data: TNetData::Bytes(b"abcde".to_vec())
                 }
             ])
         });
@@ -346,7 +521,7 @@ mod test {
                 TNetEntry {
                     size: 1,
                     data_type: DataType::Bytes,
-                    data: TNetData::Bytes("a".as_bytes().iter().map(|b|b.clone()).collect())
+                    data: TNetData::Bytes(b"a".to_vec())
                 },
                 TNetEntry {
                     size: 1,
@@ -356,24 +531,45 @@ mod test {
                 TNetEntry {
                     size: 3,
                     data_type: DataType::Bytes,
-                    data: TNetData::Bytes("bbb".as_bytes().iter().map(|b|b.clone()).collect())
+                    data: TNetData::Bytes(b"bbb".to_vec())
                 },
                 TNetEntry {
                     size: 2,
                     data_type: DataType::Bytes,
-                    data: TNetData::Bytes("hi".as_bytes().iter().map(|b|b.clone()).collect())
+                    data: TNetData::Bytes(b"hi".to_vec())
                 }
             ]).unwrap())
         });
     }
 
+    #[test]
+    fn validates_nested_tnetstring() {
+        let input = "38:4:list,16:4:true!6:0.4529^]3:int,2:-1#}trailing".as_bytes();
+        assert_eq!(validate(input), Ok(42));
+        assert_eq!(validate("0:~".as_bytes()), Ok(3));
+    }
+
+    #[test]
+    fn validation_errors_match_parse() {
+        let inputs: Vec<&[u8]> = vec![
+            b":abc,", b"z:abc,", b"9999999991:abc,", b"11", b"2:a,", b"5:a,",
+            b"4:1:a,}", b"8:1:1#1:a,}", b"3:abc#", b"4:yes!!", b"3:1.2.3^", b"8:1:a,1:x#]"
+        ];
+        for input in inputs {
+            let mut pos = 0;
+            let expected = parse_entry(input, &mut pos, &Extensions::new()).unwrap_err();
+            assert_eq!(validate(input), Err(expected));
+        }
+    }
+
+    #[cfg(feature = "std")]
     #[test]
     fn parses_stream() {
         let mut input = "10:aaaaaaaaaa,".as_bytes();
         let expected = TNetEntry {
             size: 10,
             data_type: DataType::Bytes,
-            data: TNetData::Bytes(b"aaaaaaaaaa".iter().map(|b| b.clone()).collect())
+            data: TNetData::Bytes(b"aaaaaaaaaa".to_vec())
         };
         let actual = parse_stream(&mut input).unwrap();
 
@@ -382,7 +578,7 @@ mod test {
 
     fn expect_error(test: &str, input: &[u8], expected_error: TNetStrError) {
         let mut pos = 0;
-        match parse_entry(input, &mut pos) {
+        match parse_entry(input, &mut pos, &Extensions::new()) {
             Err(e) => assert_eq!(e, expected_error),
             _ => panic!("Did not error on {}", test)
         }
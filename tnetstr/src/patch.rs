@@ -0,0 +1,379 @@
+use super::{
+    TNetData,
+    TNetDictionary,
+    TNetEntry,
+    TNetStrError
+};
+use super::diff::{
+    equal,
+    DiffPath,
+    PathSegment
+};
+use alloc::{
+    format,
+    string::ToString,
+    vec,
+    vec::Vec
+};
+
+/// A single step of a `Patch`. Paths are the same as those reported by `diff`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum PatchOperation {
+    /// Adds a value. A list index may be the length of the list to append to it, and otherwise
+    /// inserts before the item at that index. A dictionary key replaces the value of an existing
+    /// pair, or adds a pair if its occurrence is the number of pairs with that key.
+    Add { path: DiffPath, value: TNetEntry },
+    /// Removes an existing dictionary pair or list item
+    Remove { path: DiffPath },
+    /// Replaces an existing value
+    Replace { path: DiffPath, value: TNetEntry },
+    /// Removes the value at `from` and adds it at `path`, as `Remove` then `Add`
+    Move { from: DiffPath, path: DiffPath },
+    /// Checks that the value at `path` equals `value`, ignoring `size` and dictionary order
+    Test { path: DiffPath, value: TNetEntry }
+}
+
+/// A list of operations to apply to a `TNetEntry`, in the manner of a JSON Patch (RFC 6902).
+///
+/// A patch is itself represented as a tnetstring by `to_entry` and `from_entry`: a list of
+/// dictionaries, each with an `op` of `add`, `remove`, `replace`, `move` or `test`, a `path`, and a
+/// `value` or `from` where the operation needs one. A path is a list whose items are Bytes for a
+/// dictionary key, an Integer for a list index, or a list of a key and an Integer occurrence for a
+/// repeated key.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Patch {
+    pub operations: Vec<PatchOperation>
+}
+
+pub(crate) fn new_entry(data: TNetData) -> TNetEntry {
+    TNetEntry {
+        size: 0,
+        data_type: data.data_type(),
+        data
+    }
+}
+
+fn path_to_entry(path: &DiffPath) -> TNetEntry {
+    new_entry(TNetData::List(path.0.iter()
+        .map(|segment| match segment {
+            PathSegment::Key { key, occurrence: 0 } => new_entry(TNetData::Bytes(key.clone())),
+            PathSegment::Key { key, occurrence } => new_entry(TNetData::List(vec![
+                new_entry(TNetData::Bytes(key.clone())),
+                new_entry(TNetData::Integer(*occurrence as i64))
+            ])),
+            PathSegment::Index(i) => new_entry(TNetData::Integer(*i as i64))
+        })
+        .collect()))
+}
+
+fn invalid(message: &str) -> TNetStrError {
+    TNetStrError::InvalidPatch(message.to_string())
+}
+
+fn count_from_entry(entry: &TNetEntry) -> Result<usize, TNetStrError> {
+    match entry.data {
+        TNetData::Integer(n) if n >= 0 => Ok(n as usize),
+        _ => Err(invalid("path indexes must be non-negative integers"))
+    }
+}
+
+fn path_from_entry(entry: &TNetEntry) -> Result<DiffPath, TNetStrError> {
+    let segments = entry.data.as_list().ok_or_else(|| invalid("a path was not a list"))?;
+    segments.iter()
+        .map(|segment| match &segment.data {
+            TNetData::Bytes(key) => Ok(PathSegment::Key { key: key.clone(), occurrence: 0 }),
+            TNetData::Integer(_) => Ok(PathSegment::Index(count_from_entry(segment)?)),
+            TNetData::List(l) => match (l.first().map(|k| &k.data), l.get(1), l.len()) {
+                (Some(TNetData::Bytes(key)), Some(occurrence), 2) => Ok(PathSegment::Key {
+                    key: key.clone(),
+                    occurrence: count_from_entry(occurrence)?
+                }),
+                _ => Err(invalid("a repeated key was not a list of a key and an occurrence"))
+            },
+            _ => Err(invalid("a path segment was not a key, index or repeated key"))
+        })
+        .collect::<Result<Vec<_>, _>>()
+        .map(DiffPath)
+}
+
+/// The only value of `key` in an operation dictionary
+fn field<'e>(operation: &'e TNetDictionary, key: &str) -> Result<&'e TNetEntry, TNetStrError> {
+    match operation.get(key.as_bytes()).as_slice() {
+        [value] => Ok(value),
+        [] => Err(TNetStrError::InvalidPatch(format!("an operation had no '{}'", key))),
+        _ => Err(TNetStrError::InvalidPatch(format!("an operation had more than one '{}'", key)))
+    }
+}
+
+impl Patch {
+
+    pub fn new() -> Patch {
+        Patch {
+            operations: vec![]
+        }
+    }
+
+    /// Represents the patch as a tnetstring, which can be encoded with `encode`
+    pub fn to_entry(&self) -> TNetEntry {
+        let pair = |key: &str, value: TNetEntry| (new_entry(TNetData::Bytes(key.as_bytes().to_vec())), value);
+        let op = |name: &str| pair("op", new_entry(TNetData::Bytes(name.as_bytes().to_vec())));
+        let operations = self.operations.iter()
+            .map(|operation| {
+                let entries = match operation {
+                    PatchOperation::Add { path, value } => vec![op("add"), pair("path", path_to_entry(path)), pair("value", value.clone())],
+                    PatchOperation::Remove { path } => vec![op("remove"), pair("path", path_to_entry(path))],
+                    PatchOperation::Replace { path, value } => vec![op("replace"), pair("path", path_to_entry(path)), pair("value", value.clone())],
+                    PatchOperation::Move { from, path } => vec![op("move"), pair("from", path_to_entry(from)), pair("path", path_to_entry(path))],
+                    PatchOperation::Test { path, value } => vec![op("test"), pair("path", path_to_entry(path)), pair("value", value.clone())]
+                };
+                new_entry(TNetData::Dictionary(TNetDictionary { entries }))
+            })
+            .collect();
+        let mut entry = new_entry(TNetData::List(operations));
+        entry.update_sizes();
+        entry
+    }
+
+    /// Reads a patch from its tnetstring representation
+    pub fn from_entry(entry: &TNetEntry) -> Result<Patch, TNetStrError> {
+        let operations = entry.data.as_list().ok_or_else(|| invalid("a patch was not a list"))?;
+        let operations = operations.iter()
+            .map(|operation| {
+                let operation = operation.data.as_dictionary().ok_or_else(|| invalid("an operation was not a dictionary"))?;
+                let path = || path_from_entry(field(operation, "path")?);
+                let value = || field(operation, "value").cloned();
+                Ok(match field(operation, "op")?.data.as_bytes() {
+                    Some(b"add") => PatchOperation::Add { path: path()?, value: value()? },
+                    Some(b"remove") => PatchOperation::Remove { path: path()? },
+                    Some(b"replace") => PatchOperation::Replace { path: path()?, value: value()? },
+                    Some(b"move") => PatchOperation::Move { from: path_from_entry(field(operation, "from")?)?, path: path()? },
+                    Some(b"test") => PatchOperation::Test { path: path()?, value: value()? },
+                    _ => return Err(invalid("an operation's op was not recognized"))
+                })
+            })
+            .collect::<Result<Vec<_>, TNetStrError>>()?;
+        Ok(Patch {
+            operations
+        })
+    }
+}
+
+/// The index in `dict.entries` of the `occurrence`th pair with `key`
+fn pair_index(dict: &TNetDictionary, key: &[u8], occurrence: usize) -> Option<usize> {
+    dict.entries.iter()
+        .enumerate()
+        .filter(|(_, (k, _))| k.data.as_bytes() == Some(key))
+        .nth(occurrence)
+        .map(|(i, _)| i)
+}
+
+fn get<'e>(entry: &'e TNetEntry, path: &[PathSegment]) -> Option<&'e TNetEntry> {
+    let mut entry = entry;
+    for segment in path {
+        entry = match (&entry.data, segment) {
+            (TNetData::Dictionary(d), PathSegment::Key { key, occurrence }) => &d.entries[pair_index(d, key, *occurrence)?].1,
+            (TNetData::List(l), PathSegment::Index(i)) => l.get(*i)?,
+            _ => return None
+        };
+    }
+    Some(entry)
+}
+
+fn get_mut<'e>(entry: &'e mut TNetEntry, path: &[PathSegment]) -> Option<&'e mut TNetEntry> {
+    let mut entry = entry;
+    for segment in path {
+        entry = match (&mut entry.data, segment) {
+            (TNetData::Dictionary(d), PathSegment::Key { key, occurrence }) => {
+                let index = pair_index(d, key, *occurrence)?;
+                &mut d.entries[index].1
+            },
+            (TNetData::List(l), PathSegment::Index(i)) => l.get_mut(*i)?,
+            _ => return None
+        };
+    }
+    Some(entry)
+}
+
+fn not_found(path: &DiffPath) -> TNetStrError {
+    TNetStrError::PathNotFound(path.to_string())
+}
+
+fn add(target: &mut TNetEntry, path: &DiffPath, value: TNetEntry) -> Result<(), TNetStrError> {
+    let (last, parent) = match path.0.split_last() {
+        Some(split) => split,
+        None => {
+            *target = value;
+            return Ok(())
+        }
+    };
+    let parent = get_mut(target, parent).ok_or_else(|| not_found(path))?;
+    match (&mut parent.data, last) {
+        (TNetData::Dictionary(d), PathSegment::Key { key, occurrence }) => {
+            let count = d.entries.iter().filter(|(k, _)| k.data.as_bytes() == Some(key)).count();
+            if *occurrence < count {
+                let index = pair_index(d, key, *occurrence).expect("Bug - counted pair was not found");
+                d.entries[index].1 = value;
+            } else if *occurrence == count {
+                d.entries.push((new_entry(TNetData::Bytes(key.clone())), value));
+            } else {
+                return Err(not_found(path))
+            }
+        },
+        (TNetData::List(l), PathSegment::Index(i)) if *i <= l.len() => l.insert(*i, value),
+        _ => return Err(not_found(path))
+    }
+    Ok(())
+}
+
+fn remove(target: &mut TNetEntry, path: &DiffPath) -> Result<TNetEntry, TNetStrError> {
+    let (last, parent) = path.0.split_last().ok_or_else(|| invalid("the outermost value cannot be removed"))?;
+    let parent = get_mut(target, parent).ok_or_else(|| not_found(path))?;
+    match (&mut parent.data, last) {
+        (TNetData::Dictionary(d), PathSegment::Key { key, occurrence }) => match pair_index(d, key, *occurrence) {
+            Some(index) => Ok(d.entries.remove(index).1),
+            None => Err(not_found(path))
+        },
+        (TNetData::List(l), PathSegment::Index(i)) if *i < l.len() => Ok(l.remove(*i)),
+        _ => Err(not_found(path))
+    }
+}
+
+fn apply_operation(target: &mut TNetEntry, operation: &PatchOperation) -> Result<(), TNetStrError> {
+    match operation {
+        PatchOperation::Add { path, value } => add(target, path, value.clone()),
+        PatchOperation::Remove { path } => remove(target, path).map(|_| ()),
+        PatchOperation::Replace { path, value } => {
+            *get_mut(target, &path.0).ok_or_else(|| not_found(path))? = value.clone();
+            Ok(())
+        },
+        PatchOperation::Move { from, path } => {
+            if path.0.len() > from.0.len() && path.0.starts_with(&from.0) {
+                return Err(invalid("a value cannot be moved into itself"))
+            }
+            let value = remove(target, from)?;
+            add(target, path, value)
+        },
+        PatchOperation::Test { path, value } => match get(target, &path.0) {
+            Some(found) if equal(found, value) => Ok(()),
+            Some(_) => Err(TNetStrError::PatchTestFailed(path.to_string())),
+            None => Err(not_found(path))
+        }
+    }
+}
+
+/// Applies every operation of a patch in order. If any operation fails the target is left
+/// unchanged and the error is returned. On success the sizes of the target are updated.
+pub fn apply_patch(target: &mut TNetEntry, patch: &Patch) -> Result<(), TNetStrError> {
+    let mut patched = target.clone();
+    for operation in &patch.operations {
+        apply_operation(&mut patched, operation)?;
+    }
+    patched.update_sizes();
+    *target = patched;
+    Ok(())
+}
+
+/// The segment for the first pair with `key`
+impl From<&str> for PathSegment {
+    fn from(key: &str) -> PathSegment {
+        PathSegment::Key {
+            key: key.as_bytes().to_vec(),
+            occurrence: 0
+        }
+    }
+}
+
+impl From<usize> for PathSegment {
+    fn from(index: usize) -> PathSegment {
+        PathSegment::Index(index)
+    }
+}
+
+impl DiffPath {
+
+    /// The path of the outermost value
+    pub fn root() -> DiffPath {
+        DiffPath(vec![])
+    }
+
+    /// Extends the path by one segment
+    pub fn then<S: Into<PathSegment>>(mut self, segment: S) -> DiffPath {
+        self.0.push(segment.into());
+        self
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+    use super::super::{
+        encode,
+        parse
+    };
+
+    fn entry(input: &[u8]) -> TNetEntry {
+        parse(input).unwrap()
+    }
+
+    #[test]
+    fn applies_operations_in_order() {
+        let mut target = entry(b"35:4:name,3:bob,4:tags,11:1:a,4:1:b,]]}");
+        let patch = Patch {
+            operations: vec![
+                PatchOperation::Test { path: DiffPath::root().then("name"), value: entry(b"3:bob,") },
+                PatchOperation::Replace { path: DiffPath::root().then("name"), value: entry(b"5:alice,") },
+                PatchOperation::Add { path: DiffPath::root().then("tags").then(0), value: entry(b"1:z,") },
+                PatchOperation::Remove { path: DiffPath::root().then("tags").then(2) },
+                PatchOperation::Move { from: DiffPath::root().then("tags").then(1), path: DiffPath::root().then("first") }
+            ]
+        };
+        apply_patch(&mut target, &patch).unwrap();
+        assert_eq!(target, entry(b"41:4:name,5:alice,4:tags,4:1:z,]5:first,1:a,}"));
+    }
+
+    #[test]
+    fn failed_patches_leave_target_unchanged() {
+        let input = b"19:1:a,1:1#3:bbb,2:hi,}";
+        let mut target = entry(input);
+        let patch = Patch {
+            operations: vec![
+                PatchOperation::Remove { path: DiffPath::root().then("a") },
+                PatchOperation::Test { path: DiffPath::root().then("bbb"), value: entry(b"2:ho,") }
+            ]
+        };
+        assert_eq!(apply_patch(&mut target, &patch), Err(TNetStrError::PatchTestFailed("$[\"bbb\"]".to_string())));
+        assert_eq!(encode(&target), input);
+        let missing = Patch {
+            operations: vec![PatchOperation::Replace { path: DiffPath::root().then("x").then(0), value: entry(b"0:~") }]
+        };
+        assert_eq!(apply_patch(&mut target, &missing), Err(TNetStrError::PathNotFound("$[\"x\"][0]".to_string())));
+    }
+
+    #[test]
+    fn adds_repeated_keys() {
+        let mut target = entry(b"8:1:k,1:1#}");
+        let patch = Patch {
+            operations: vec![
+                PatchOperation::Add { path: DiffPath(vec![PathSegment::Key { key: b"k".to_vec(), occurrence: 1 }]), value: entry(b"1:2#") },
+                PatchOperation::Add { path: DiffPath::root().then("k"), value: entry(b"1:3#") }
+            ]
+        };
+        apply_patch(&mut target, &patch).unwrap();
+        assert_eq!(encode(&target), b"16:1:k,1:3#1:k,1:2#}");
+    }
+
+    #[test]
+    fn round_trips_through_tnetstring() {
+        let patch = Patch {
+            operations: vec![
+                PatchOperation::Add { path: DiffPath(vec![PathSegment::Key { key: b"k".to_vec(), occurrence: 2 }]), value: entry(b"1:2#") },
+                PatchOperation::Move { from: DiffPath::root().then("a").then(3), path: DiffPath::root() },
+                PatchOperation::Remove { path: DiffPath::root().then("b") }
+            ]
+        };
+        let encoded = encode(&patch.to_entry());
+        assert_eq!(Patch::from_entry(&parse(&encoded).unwrap()), Ok(patch));
+        assert!(Patch::from_entry(&entry(b"26:22:2:op,4:nope,4:path,0:]}]")).is_err());
+    }
+}
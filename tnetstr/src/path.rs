@@ -0,0 +1,110 @@
+use super::{
+    DataType,
+    TNetStrError
+};
+#[cfg(feature = "alloc")]
+use super::TNetEntry;
+use super::parse::{
+    pairs,
+    parse_frame
+};
+#[cfg(feature = "alloc")]
+use super::parse::parse;
+
+/// Searches the dictionary data in `bytes` for the first entry with the given key, skipping over
+/// every other key and value using only its length. Returns the whole tnetstring of the value.
+fn find_key<'a>(bytes: &'a [u8], key: &[u8]) -> Result<Option<&'a [u8]>, TNetStrError> {
+    let read = |position: &mut usize| {
+        let start = *position;
+        let (data_type, data) = parse_frame(bytes, position)?;
+        Ok((data_type, data, &bytes[start..*position]))
+    };
+    for pair in pairs(0, bytes.len(), read, |key: &(DataType, &[u8], &[u8])| key.0) {
+        let ((_, key_data, _), (_, _, value)) = pair?;
+        if key_data == key {
+            return Ok(Some(value))
+        }
+    }
+    Ok(None)
+}
+
+/// Finds the value at `path` in the first tnetstring of `input`, where each element of the path is
+/// a dictionary key. Returns the slice of `input` holding the whole tnetstring of the value, or
+/// `None` if a key is missing or a value along the path is not a dictionary.
+///
+/// Only the framing of the dictionaries along the path is read: sibling keys and values are skipped
+/// using their length prefixes without being parsed or validated. If a dictionary has duplicate keys
+/// the first matching entry is followed.
+pub fn find_path<'a, K: AsRef<[u8]>>(input: &'a [u8], path: &[K]) -> Result<Option<&'a [u8]>, TNetStrError> {
+    let mut position = 0;
+    parse_frame(input, &mut position)?;
+    let mut found = &input[..position];
+
+    for key in path {
+        let mut position = 0;
+        let (t, data) = parse_frame(found, &mut position)?;
+        if t != DataType::Dictionary {
+            return Ok(None)
+        }
+        found = match find_key(data, key.as_ref())? {
+            Some(value) => value,
+            None => return Ok(None)
+        };
+    }
+    Ok(Some(found))
+}
+
+/// Finds the value at `path` in the first tnetstring of `input`, as `find_path`, and parses only that
+/// value into a `TNetEntry`
+#[cfg(feature = "alloc")]
+pub fn find_path_entry<K: AsRef<[u8]>>(input: &[u8], path: &[K]) -> Result<Option<TNetEntry>, TNetStrError> {
+    match find_path(input, path)? {
+        None => Ok(None),
+        Some(value) => parse(value).map(Some)
+    }
+}
+
+#[cfg(all(test, feature = "alloc"))]
+mod test {
+
+    use super::*;
+    use super::super::TNetData;
+
+    const INPUT: &[u8] = b"50:4:meta,23:4:skip,3:1:x]2:id,2:42#}4:body,6:3:a\xffb,]}";
+
+    #[test]
+    fn finds_nested_value_slice() {
+        assert_eq!(find_path(INPUT, &["meta", "id"]).unwrap(), Some(&b"2:42#"[..]));
+        assert_eq!(find_path(INPUT, &["meta", "skip"]).unwrap(), Some(&b"3:1:x]"[..]));
+    }
+
+    #[test]
+    fn empty_path_returns_whole_tnetstring() {
+        assert_eq!(find_path::<&str>(b"1:a,trailing", &[]).unwrap(), Some(&b"1:a,"[..]));
+    }
+
+    #[test]
+    fn missing_keys_are_none() {
+        assert_eq!(find_path(INPUT, &["meta", "missing"]).unwrap(), None);
+        assert_eq!(find_path(INPUT, &["meta", "id", "deeper"]).unwrap(), None);
+    }
+
+    #[test]
+    fn does_not_parse_skipped_values() {
+        // the skipped value under "bad" is not a valid integer
+        let input = b"25:3:bad,3:abc#4:good,3:yes,}";
+        assert_eq!(find_path(input, &["good"]).unwrap(), Some(&b"3:yes,"[..]));
+    }
+
+    #[test]
+    fn parses_found_entry() {
+        let entry = find_path_entry(INPUT, &["meta", "id"]).unwrap().unwrap();
+        assert_eq!(entry.data, TNetData::Integer(42));
+    }
+
+    #[test]
+    fn reports_framing_errors_on_path() {
+        assert_eq!(find_path(b"4:1:a,}", &["b"]), Err(TNetStrError::UnbalancedDictionary));
+        assert_eq!(find_path(b"9:1:a,9:b,}", &["a"]), Err(TNetStrError::LengthWasNotAccurate));
+    }
+}
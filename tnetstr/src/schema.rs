@@ -0,0 +1,422 @@
+use super::{
+    DataType,
+    TNetData,
+    TNetDictionary,
+    TNetEntry,
+    TNetStrError
+};
+use super::diff::{
+    DiffPath,
+    PathSegment
+};
+use super::patch::new_entry;
+use alloc::{
+    boxed::Box,
+    format,
+    string::ToString,
+    vec,
+    vec::Vec
+};
+use core::fmt::{
+    Display,
+    Error,
+    Formatter
+};
+
+/// The expected shape of a tnetstring document.
+///
+/// A schema is written as a tnetstring dictionary, read with `from_entry` and written with
+/// `to_entry`. Every key is optional, and a schema with no keys accepts any value:
+///
+/// * `type`: the name of a type (`bytes`, `integer`, `float`, `boolean`, `null`, `dictionary`,
+///   `list`, or `extension:` followed by an extension's tag), or a list of names, that the value
+///   must have
+/// * `min`, `max`: an integer or float that an integer or float value must not be below or above,
+///   compared numerically
+/// * `min_length`, `max_length`: bounds on the number of bytes of a bytes value, or of items in a list
+/// * `utf8`: a boolean, true if a bytes value must be valid UTF-8
+/// * `enum`: a list of the bytes values allowed
+/// * `items`: the schema of every item in a list
+/// * `keys`: a dictionary from each key a dictionary may have to the schema of its value. Every
+///   value of a repeated key is checked.
+/// * `optional`: in the schema of a key, true if the key may be missing
+/// * `additional_keys`: false if a dictionary may only have the keys in `keys`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Schema {
+    pub types: Option<Vec<DataType>>,
+    pub min: Option<TNetData>,
+    pub max: Option<TNetData>,
+    pub min_length: Option<usize>,
+    pub max_length: Option<usize>,
+    pub utf8: bool,
+    pub allowed: Option<Vec<Vec<u8>>>,
+    pub items: Option<Box<Schema>>,
+    pub keys: Vec<(Vec<u8>, Schema)>,
+    pub optional: bool,
+    pub additional_keys: bool
+}
+
+impl Default for Schema {
+    fn default() -> Schema {
+        Schema {
+            types: None,
+            min: None,
+            max: None,
+            min_length: None,
+            max_length: None,
+            utf8: false,
+            allowed: None,
+            items: None,
+            keys: vec![],
+            optional: false,
+            additional_keys: true
+        }
+    }
+}
+
+/// How a value failed to match its schema
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum ViolationKind {
+    WrongType { expected: Vec<DataType>, found: DataType },
+    BelowMinimum,
+    AboveMaximum,
+    TooShort { min: usize, length: usize },
+    TooLong { max: usize, length: usize },
+    NotUtf8,
+    NotAllowed,
+    /// A required key was missing. The path ends with the key.
+    Missing,
+    /// A key not in the schema was found where `additional_keys` is false
+    Unexpected
+}
+
+/// A value that did not match its schema, found by `Schema::validate`
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Violation {
+    pub path: DiffPath,
+    pub kind: ViolationKind
+}
+
+impl Display for Violation {
+    fn fmt(&self, f: &mut Formatter) -> Result<(), Error> {
+        write!(f, "{}: ", self.path)?;
+        match &self.kind {
+            ViolationKind::WrongType { expected, found } => {
+                write!(f, "expected ")?;
+                for (i, t) in expected.iter().enumerate() {
+                    write!(f, "{}{}", if i == 0 { "" } else { " or " }, t.name())?;
+                }
+                write!(f, " but found {}", found.name())
+            },
+            ViolationKind::BelowMinimum => write!(f, "below the minimum"),
+            ViolationKind::AboveMaximum => write!(f, "above the maximum"),
+            ViolationKind::TooShort { min, length } => write!(f, "length {} is less than {}", length, min),
+            ViolationKind::TooLong { max, length } => write!(f, "length {} is more than {}", length, max),
+            ViolationKind::NotUtf8 => write!(f, "not valid UTF-8"),
+            ViolationKind::NotAllowed => write!(f, "not one of the allowed values"),
+            ViolationKind::Missing => write!(f, "missing"),
+            ViolationKind::Unexpected => write!(f, "not in the schema")
+        }
+    }
+}
+
+fn invalid(message: &str) -> TNetStrError {
+    TNetStrError::InvalidSchema(message.to_string())
+}
+
+fn length_from_entry(entry: &TNetEntry) -> Result<usize, TNetStrError> {
+    match entry.data {
+        TNetData::Integer(n) if n >= 0 => Ok(n as usize),
+        _ => Err(invalid("a length was not a non-negative integer"))
+    }
+}
+
+fn number_from_entry(entry: &TNetEntry) -> Result<TNetData, TNetStrError> {
+    match entry.data {
+        TNetData::Integer(_) | TNetData::Float(_) => Ok(entry.data.clone()),
+        _ => Err(invalid("a minimum or maximum was not a number"))
+    }
+}
+
+fn bool_from_entry(entry: &TNetEntry) -> Result<bool, TNetStrError> {
+    entry.data.as_bool().ok_or_else(|| invalid("a flag was not a boolean"))
+}
+
+fn type_from_entry(entry: &TNetEntry) -> Result<DataType, TNetStrError> {
+    entry.data.as_bytes()
+        .and_then(DataType::from_name)
+        .ok_or_else(|| invalid("a type was not a type name"))
+}
+
+fn bytes_entry(bytes: &[u8]) -> TNetEntry {
+    new_entry(TNetData::Bytes(bytes.to_vec()))
+}
+
+impl Schema {
+
+    /// Reads a schema from its tnetstring representation
+    pub fn from_entry(entry: &TNetEntry) -> Result<Schema, TNetStrError> {
+        let dict = entry.data.as_dictionary().ok_or_else(|| invalid("a schema was not a dictionary"))?;
+        let mut schema = Schema::default();
+        for (key, value) in &dict.entries {
+            let key = key.data.as_bytes().ok_or_else(|| invalid("a schema key was not bytes"))?;
+            match key {
+                b"type" => schema.types = Some(match &value.data {
+                    TNetData::List(l) => l.iter().map(type_from_entry).collect::<Result<_, _>>()?,
+                    _ => vec![type_from_entry(value)?]
+                }),
+                b"min" => schema.min = Some(number_from_entry(value)?),
+                b"max" => schema.max = Some(number_from_entry(value)?),
+                b"min_length" => schema.min_length = Some(length_from_entry(value)?),
+                b"max_length" => schema.max_length = Some(length_from_entry(value)?),
+                b"utf8" => schema.utf8 = bool_from_entry(value)?,
+                b"enum" => schema.allowed = Some(value.data.as_list()
+                    .ok_or_else(|| invalid("an enum was not a list"))?
+                    .iter()
+                    .map(|item| item.data.as_bytes().map(<[u8]>::to_vec).ok_or_else(|| invalid("an enum value was not bytes")))
+                    .collect::<Result<_, _>>()?),
+                b"items" => schema.items = Some(Box::new(Schema::from_entry(value)?)),
+                b"keys" => schema.keys = value.data.as_dictionary()
+                    .ok_or_else(|| invalid("keys was not a dictionary"))?
+                    .entries.iter()
+                    .map(|(k, v)| match k.data.as_bytes() {
+                        Some(k) => Ok((k.to_vec(), Schema::from_entry(v)?)),
+                        None => Err(invalid("a key in keys was not bytes"))
+                    })
+                    .collect::<Result<_, TNetStrError>>()?,
+                b"optional" => schema.optional = bool_from_entry(value)?,
+                b"additional_keys" => schema.additional_keys = bool_from_entry(value)?,
+                _ => return Err(TNetStrError::InvalidSchema(format!("unknown schema key '{}'", key.escape_ascii())))
+            }
+        }
+        Ok(schema)
+    }
+
+    /// Represents the schema as a tnetstring, leaving out anything that has its default value
+    pub fn to_entry(&self) -> TNetEntry {
+        let mut entries = vec![];
+        let mut pair = |key: &str, value: TNetEntry| entries.push((bytes_entry(key.as_bytes()), value));
+        match self.types.as_deref() {
+            Some([t]) => pair("type", bytes_entry(t.name().as_bytes())),
+            Some(types) => pair("type", new_entry(TNetData::List(types.iter().map(|t| bytes_entry(t.name().as_bytes())).collect()))),
+            None => {}
+        }
+        if let Some(min) = &self.min {
+            pair("min", new_entry(min.clone()));
+        }
+        if let Some(max) = &self.max {
+            pair("max", new_entry(max.clone()));
+        }
+        if let Some(min_length) = self.min_length {
+            pair("min_length", new_entry(TNetData::Integer(min_length as i64)));
+        }
+        if let Some(max_length) = self.max_length {
+            pair("max_length", new_entry(TNetData::Integer(max_length as i64)));
+        }
+        if self.utf8 {
+            pair("utf8", new_entry(TNetData::Boolean(true)));
+        }
+        if let Some(allowed) = &self.allowed {
+            pair("enum", new_entry(TNetData::List(allowed.iter().map(|value| bytes_entry(value)).collect())));
+        }
+        if let Some(items) = &self.items {
+            pair("items", items.to_entry());
+        }
+        if !self.keys.is_empty() {
+            pair("keys", new_entry(TNetData::Dictionary(TNetDictionary {
+                entries: self.keys.iter().map(|(key, schema)| (bytes_entry(key), schema.to_entry())).collect()
+            })));
+        }
+        if self.optional {
+            pair("optional", new_entry(TNetData::Boolean(true)));
+        }
+        if !self.additional_keys {
+            pair("additional_keys", new_entry(TNetData::Boolean(false)));
+        }
+        let mut entry = new_entry(TNetData::Dictionary(TNetDictionary { entries }));
+        entry.update_sizes();
+        entry
+    }
+
+    /// Checks a value against the schema, returning every violation found. A value of the wrong
+    /// type is reported without checking anything else about it.
+    pub fn validate(&self, entry: &TNetEntry) -> Vec<Violation> {
+        let mut validator = Validator {
+            path: vec![],
+            violations: vec![]
+        };
+        validator.check(self, entry);
+        validator.violations
+    }
+}
+
+struct Validator {
+    path: Vec<PathSegment>,
+    violations: Vec<Violation>
+}
+
+impl Validator {
+
+    fn push(&mut self, kind: ViolationKind) {
+        self.violations.push(Violation {
+            path: DiffPath(self.path.clone()),
+            kind
+        });
+    }
+
+    fn at(&mut self, segment: PathSegment, kind: ViolationKind) {
+        self.path.push(segment);
+        self.push(kind);
+        self.path.pop();
+    }
+
+    fn within(&mut self, segment: PathSegment, schema: &Schema, entry: &TNetEntry) {
+        self.path.push(segment);
+        self.check(schema, entry);
+        self.path.pop();
+    }
+
+    fn length(&mut self, schema: &Schema, length: usize) {
+        match (schema.min_length, schema.max_length) {
+            (Some(min), _) if length < min => self.push(ViolationKind::TooShort { min, length }),
+            (_, Some(max)) if length > max => self.push(ViolationKind::TooLong { max, length }),
+            _ => {}
+        }
+    }
+
+    fn check(&mut self, schema: &Schema, entry: &TNetEntry) {
+        let found = entry.data.data_type();
+        if let Some(expected) = &schema.types {
+            if !expected.contains(&found) {
+                return self.push(ViolationKind::WrongType { expected: expected.clone(), found })
+            }
+        }
+        match &entry.data {
+            TNetData::Integer(_) | TNetData::Float(_) => {
+                if schema.min.as_ref().is_some_and(|min| entry.data.cmp_numeric(min).is_lt()) {
+                    self.push(ViolationKind::BelowMinimum);
+                }
+                if schema.max.as_ref().is_some_and(|max| entry.data.cmp_numeric(max).is_gt()) {
+                    self.push(ViolationKind::AboveMaximum);
+                }
+            },
+            TNetData::Bytes(b) => {
+                self.length(schema, b.len());
+                if schema.utf8 && core::str::from_utf8(b).is_err() {
+                    self.push(ViolationKind::NotUtf8);
+                }
+                if schema.allowed.as_ref().is_some_and(|allowed| !allowed.contains(b)) {
+                    self.push(ViolationKind::NotAllowed);
+                }
+            },
+            TNetData::List(l) => {
+                self.length(schema, l.len());
+                if let Some(items) = &schema.items {
+                    for (i, item) in l.iter().enumerate() {
+                        self.within(PathSegment::Index(i), items, item);
+                    }
+                }
+            },
+            TNetData::Dictionary(dict) => self.dictionary(schema, dict),
+            _ => {}
+        }
+    }
+
+    fn dictionary(&mut self, schema: &Schema, dict: &TNetDictionary) {
+        for (key, key_schema) in &schema.keys {
+            let values = dict.get(key);
+            if values.is_empty() && !key_schema.optional {
+                self.at(PathSegment::Key { key: key.clone(), occurrence: 0 }, ViolationKind::Missing);
+            }
+            for (occurrence, value) in values.into_iter().enumerate() {
+                self.within(PathSegment::Key { key: key.clone(), occurrence }, key_schema, value);
+            }
+        }
+        if schema.additional_keys {
+            return
+        }
+        for (i, (key, _)) in dict.entries.iter().enumerate() {
+            let key = key.data.as_bytes().unwrap_or(&[]);
+            if schema.keys.iter().all(|(k, _)| k != key) {
+                let occurrence = dict.entries[..i].iter().filter(|(k, _)| k.data.as_bytes() == Some(key)).count();
+                self.at(PathSegment::Key { key: key.to_vec(), occurrence }, ViolationKind::Unexpected);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+    use super::super::parse;
+    use alloc::string::String;
+
+    const SCHEMA: &[u8] = b"396:4:type,10:dictionary,4:keys,336:4:name,65:4:type,5:bytes,4:utf8,4:true!10:min_length,1:1#10:max_length,1:8#}3:age,39:4:type,7:integer,3:min,1:0#3:max,3:150#}4:role,59:4:type,5:bytes,4:enum,15:5:admin,4:user,]8:optional,4:true!}4:tags,59:4:type,4:list,5:items,15:4:type,5:bytes,}10:max_length,1:2#}5:score,59:4:type,18:7:integer,5:float,]3:max,3:1.5^8:optional,4:true!}}15:additional_keys,5:false!}";
+
+    fn schema() -> Schema {
+        Schema::from_entry(&parse(SCHEMA).unwrap()).unwrap()
+    }
+
+    fn rendered(violations: &[Violation]) -> Vec<String> {
+        violations.iter().map(Violation::to_string).collect()
+    }
+
+    #[test]
+    fn accepts_matching_documents() {
+        assert_eq!(schema().validate(&parse(b"50:4:name,3:bob,3:age,2:30#4:tags,4:1:a,]5:score,1:1#}").unwrap()), vec![]);
+    }
+
+    #[test]
+    fn reports_every_violation_with_its_path() {
+        let violations = schema().validate(&parse(b"83:4:name,0:,3:age,2:-1#4:role,4:root,4:tags,12:1:a,1:1#1:c,]5:score,3:2.0^5:extra,0:~}").unwrap());
+        assert_eq!(rendered(&violations), vec![
+            "$[\"name\"]: length 0 is less than 1",
+            "$[\"age\"]: below the minimum",
+            "$[\"role\"]: not one of the allowed values",
+            "$[\"tags\"]: length 3 is more than 2",
+            "$[\"tags\"][1]: expected bytes but found integer",
+            "$[\"score\"]: above the maximum",
+            "$[\"extra\"]: not in the schema"
+        ]);
+        let violations = schema().validate(&parse(b"11:4:name,1:\xff,}").unwrap());
+        assert_eq!(rendered(&violations), vec![
+            "$[\"name\"]: not valid UTF-8",
+            "$[\"age\"]: missing",
+            "$[\"tags\"]: missing"
+        ]);
+    }
+
+    #[test]
+    fn round_trips_through_tnetstring() {
+        let schema = schema();
+        assert_eq!(Schema::from_entry(&schema.to_entry()), Ok(schema));
+        assert_eq!(
+            Schema::from_entry(&parse(b"15:4:tpye,5:bytes,}").unwrap()),
+            Err(TNetStrError::InvalidSchema("unknown schema key 'tpye'".to_string()))
+        );
+        let extension = Schema { types: Some(vec![DataType::Extension(b';')]), ..Schema::default() };
+        assert_eq!(extension.to_entry(), parse(b"22:4:type,11:extension:;,}").unwrap());
+        assert_eq!(Schema::from_entry(&extension.to_entry()), Ok(extension));
+    }
+
+    #[test]
+    fn rejects_keys_that_are_not_bytes() {
+        let dictionary = |key: TNetData, value: TNetEntry| new_entry(TNetData::Dictionary(TNetDictionary {
+            entries: vec![(new_entry(key), value)]
+        }));
+        let keys = dictionary(TNetData::Bytes(b"keys".to_vec()), dictionary(TNetData::Integer(1), dictionary(TNetData::Bytes(b"optional".to_vec()), new_entry(TNetData::Boolean(true)))));
+        assert_eq!(Schema::from_entry(&keys), Err(TNetStrError::InvalidSchema("a key in keys was not bytes".to_string())));
+        let top = dictionary(TNetData::Null, new_entry(TNetData::Boolean(true)));
+        assert_eq!(Schema::from_entry(&top), Err(TNetStrError::InvalidSchema("a schema key was not bytes".to_string())));
+    }
+
+    #[test]
+    fn compares_bounds_numerically() {
+        let schema = Schema::from_entry(&parse(b"13:3:max,4:0.05^}").unwrap()).unwrap();
+        assert_eq!(schema.validate(&parse(b"3:0.3^").unwrap()), vec![Violation { path: DiffPath(vec![]), kind: ViolationKind::AboveMaximum }]);
+        assert_eq!(schema.validate(&parse(b"4:0.05^").unwrap()), vec![]);
+        assert_eq!(schema.validate(&parse(b"5:0.049^").unwrap()), vec![]);
+        assert_eq!(schema.validate(&parse(b"1:0#").unwrap()), vec![]);
+    }
+}
@@ -0,0 +1,227 @@
+use super::{
+    DataType,
+    TNetData,
+    TNetDictionary,
+    TNetEntry,
+    TNetFloat,
+    TNetStrError
+};
+use super::parse::{
+    items,
+    pairs,
+    parse_frame,
+    parse_float,
+    parse_val
+};
+use alloc::{
+    vec,
+    vec::Vec
+};
+use bytes::Bytes;
+
+pub type SharedList = Vec<SharedEntry>;
+
+/// The data of a `SharedEntry`. This mirrors `TNetData`, except that Bytes values are
+/// reference counted slices of the buffer the entry was parsed from.
+#[derive(Eq, PartialEq, Hash, Debug)]
+pub enum SharedData {
+    Bytes(Bytes),
+    Integer(i64),
+    Float(TNetFloat),
+    Boolean(bool),
+    Null,
+    Dictionary(SharedDictionary),
+    List(SharedList)
+}
+
+/// A dictionary of `SharedEntry` pairs. As with `TNetDictionary`, duplicate keys are supported.
+#[derive(Debug, Default, Hash, Eq, PartialEq)]
+pub struct SharedDictionary {
+    pub entries: Vec<(SharedEntry, SharedEntry)>
+}
+
+/// Represents a tnetstring whose Bytes values share the buffer it was parsed from, so it can be
+/// owned (and sent between threads) without copying any payloads
+#[derive(Eq, PartialEq, Hash, Debug)]
+pub struct SharedEntry {
+    /// The data content of the tnetstring
+    pub data: SharedData,
+    /// The number of bytes in the tnetstring data
+    pub size: u32,
+    /// The type of the tnetstring data
+    pub data_type: DataType
+}
+
+impl SharedData {
+
+    pub fn as_bytes(&self) -> Option<&Bytes> {
+        match self {
+            SharedData::Bytes(b) => Some(b),
+            _ => None
+        }
+    }
+
+    pub fn as_integer(&self) -> Option<i64> {
+        match self {
+            SharedData::Integer(n) => Some(*n),
+            _ => None
+        }
+    }
+
+    pub fn as_float(&self) -> Option<&TNetFloat> {
+        match self {
+            SharedData::Float(n) => Some(n),
+            _ => None
+        }
+    }
+
+    pub fn as_bool(&self) -> Option<bool> {
+        match self {
+            SharedData::Boolean(b) => Some(*b),
+            _ => None
+        }
+    }
+
+    pub fn is_null(&self) -> bool {
+        matches!(self, SharedData::Null)
+    }
+
+    pub fn as_dictionary(&self) -> Option<&SharedDictionary> {
+        match self {
+            SharedData::Dictionary(d) => Some(d),
+            _ => None
+        }
+    }
+
+    pub fn as_list(&self) -> Option<&SharedList> {
+        match self {
+            SharedData::List(l) => Some(l),
+            _ => None
+        }
+    }
+}
+
+impl SharedDictionary {
+
+    pub fn new() -> SharedDictionary {
+        SharedDictionary {
+            entries: vec![]
+        }
+    }
+
+    pub fn add(&mut self, key: SharedEntry, value: SharedEntry) -> Result<(), TNetStrError> {
+        match key.data_type {
+            DataType::Bytes => {
+                self.entries.push((key, value));
+                Ok(())
+            },
+            _ => Err(TNetStrError::DictionaryKeyWasNotBytes)
+        }
+    }
+
+    pub fn get(&self, key: &[u8]) -> Vec<&SharedEntry> {
+        self.entries.iter()
+            .filter(|(k, _)| k.data.as_bytes().map(|b| b.as_ref()) == Some(key))
+            .map(|(_, v)| v)
+            .collect()
+    }
+}
+
+impl From<SharedEntry> for TNetEntry {
+    fn from(entry: SharedEntry) -> TNetEntry {
+        let data = match entry.data {
+            SharedData::Bytes(b) => TNetData::Bytes(b.to_vec()),
+            SharedData::Integer(n) => TNetData::Integer(n),
+            SharedData::Float(n) => TNetData::Float(n),
+            SharedData::Boolean(b) => TNetData::Boolean(b),
+            SharedData::Null => TNetData::Null,
+            SharedData::List(l) => TNetData::List(l.into_iter().map(TNetEntry::from).collect()),
+            SharedData::Dictionary(d) => TNetData::Dictionary(TNetDictionary {
+                entries: d.entries.into_iter()
+                    .map(|(k, v)| (TNetEntry::from(k), TNetEntry::from(v)))
+                    .collect()
+            })
+        };
+        TNetEntry {
+            data,
+            size: entry.size,
+            data_type: entry.data_type
+        }
+    }
+}
+
+fn parse_shared_list(buffer: &Bytes, bytes: &[u8]) -> Result<SharedList, TNetStrError> {
+    items(0, bytes.len(), |position| parse_shared_entry(buffer, bytes, position)).collect()
+}
+
+fn parse_shared_dictionary(buffer: &Bytes, bytes: &[u8]) -> Result<SharedDictionary, TNetStrError> {
+    Ok(SharedDictionary {
+        entries: pairs(0, bytes.len(), |position| parse_shared_entry(buffer, bytes, position), |key: &SharedEntry| key.data_type)
+            .collect::<Result<_, _>>()?
+    })
+}
+
+/// `input` must be a slice of `buffer`
+fn parse_shared_entry(buffer: &Bytes, input: &[u8], position: &mut usize) -> Result<SharedEntry, TNetStrError> {
+    let (t, data_bytes) = parse_frame(input, position)?;
+
+    let data = match t {
+        DataType::Bytes => SharedData::Bytes(buffer.slice_ref(data_bytes)),
+        DataType::Null => SharedData::Null,
+        DataType::Dictionary => SharedData::Dictionary(parse_shared_dictionary(buffer, data_bytes)?),
+        DataType::List => SharedData::List(parse_shared_list(buffer, data_bytes)?),
+        DataType::Integer => SharedData::Integer(parse_val(data_bytes)?),
+        DataType::Float => SharedData::Float(parse_float(data_bytes)?),
+        DataType::Boolean => SharedData::Boolean(parse_val(data_bytes)?),
+        DataType::Extension(tag) => return Err(TNetStrError::UnrecognizedDataType(tag))
+    };
+
+    Ok(SharedEntry {
+        size: data_bytes.len() as u32,
+        data_type: t,
+        data
+    })
+}
+
+/// Attempts to read the first tnetstring from a shared buffer. Bytes values in the result
+/// are slices of `data` rather than copies.
+pub fn parse_shared(data: &Bytes) -> Result<SharedEntry, TNetStrError> {
+    let mut pos = 0;
+    parse_shared_entry(data, data, &mut pos)
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+    use super::super::parse;
+
+    #[test]
+    fn bytes_values_share_the_buffer() {
+        let buffer = Bytes::from_static(b"19:1:a,1:1#3:bbb,2:hi,}");
+        let entry = parse_shared(&buffer).unwrap();
+        let dict = entry.data.as_dictionary().unwrap();
+        let value = dict.get(b"bbb")[0].data.as_bytes().unwrap();
+        assert_eq!(value.as_ref(), b"hi");
+        assert_eq!(value.as_ptr(), buffer[19..].as_ptr());
+    }
+
+    #[test]
+    fn converts_to_entry() {
+        let input: &[u8] = b"24:4:true!6:0.4529^5:abcde,]";
+        let entry = TNetEntry::from(parse_shared(&Bytes::from(input)).unwrap());
+        assert_eq!(entry, parse(input).unwrap());
+    }
+
+    #[test]
+    fn reports_parse_errors() {
+        assert_eq!(parse_shared(&Bytes::from_static(b"8:1:1#1:a,}")), Err(TNetStrError::DictionaryKeyWasNotBytes));
+    }
+
+    #[test]
+    fn entries_can_be_sent_between_threads() {
+        let entry = parse_shared(&Bytes::from(b"5:hello,".to_vec())).unwrap();
+        let handle = std::thread::spawn(move || entry.data.as_bytes().map(|b| b.len()));
+        assert_eq!(handle.join().unwrap(), Some(5));
+    }
+}
@@ -0,0 +1,369 @@
+use super::{
+    DataType,
+    TNetData,
+    TNetDictionary,
+    TNetEntry,
+    TNetStrError
+};
+use super::parse::{
+    parse,
+    read_frame_data,
+    read_frame_length
+};
+use std::io::{
+    self,
+    Read,
+    Write
+};
+
+/// A tnetstring longer than the threshold given to `TNetReader::read_entry_streaming`, whose type
+/// is not known until its data has been read
+#[derive(Debug, PartialEq)]
+pub struct LargeFrame<'a> {
+    /// The index of the tnetstring among its siblings in each enclosing container, outermost
+    /// first. This is empty for the top level tnetstring.
+    pub position: &'a [usize],
+    /// The sibling before the tnetstring, if it is at an odd index and is Bytes that were read
+    /// into memory, as the key of a dictionary value is
+    pub key: Option<&'a [u8]>,
+    /// The length of the tnetstring's data
+    pub len: u32
+}
+
+/// Reads successive tnetstrings from a byte stream, with the option of streaming the data of large
+/// Bytes values rather than holding them in memory.
+///
+/// The type of a tnetstring is only known once all of its data has been read, so the caller must
+/// know in advance which tnetstrings to stream. `read_bytes_to` and `read_bytes` stream a top level
+/// tnetstring, and `read_entry_streaming` streams the large Bytes values inside a message, such
+/// as an upload's payload in a dictionary of metadata.
+pub struct TNetReader<R: Read> {
+    reader: R,
+    buffer: Vec<u8>
+}
+
+impl<R: Read> TNetReader<R> {
+
+    pub fn new(reader: R) -> TNetReader<R> {
+        TNetReader {
+            reader,
+            buffer: vec![]
+        }
+    }
+
+    /// Returns the underlying reader
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+
+    fn read_length(&mut self) -> Result<u32, TNetStrError> {
+        match read_frame_length(&mut self.reader, &mut self.buffer)? {
+            Some(len) => Ok(len),
+            None => Err(TNetStrError::StreamReadFailed("Reached end of stream without reading a full length value".to_string()))
+        }
+    }
+
+    fn read_entry_data(&mut self, data_length: u32) -> Result<TNetEntry, TNetStrError> {
+        read_frame_data(&mut self.reader, data_length, &mut self.buffer)?;
+        parse(&self.buffer)
+    }
+
+    /// Reads the next tnetstring into memory, as `parse_stream`
+    pub fn read_entry(&mut self) -> Result<TNetEntry, TNetStrError> {
+        let data_length = self.read_length()?;
+        self.read_entry_data(data_length)
+    }
+
+    /// Reads the next tnetstring, which must be Bytes, copying its data to `sink`.
+    /// Returns the number of bytes copied.
+    ///
+    /// The data reaches `sink` before the type tag after it is read, so if the tnetstring is not
+    /// Bytes, `UnexpectedDataType` is returned after all of its data has been copied, and the
+    /// caller should discard what was written.
+    pub fn read_bytes_to<W: Write + ?Sized>(&mut self, sink: &mut W) -> Result<u64, TNetStrError> {
+        let mut bytes = self.read_bytes()?;
+        let copied = bytes.copy_to(sink)?;
+        bytes.finish()?;
+        Ok(copied)
+    }
+
+    /// Reads the next tnetstring, passing each tnetstring in it whose data is longer than
+    /// `threshold` bytes to `stream`, at any depth. Everything else is read into memory.
+    ///
+    /// `stream` returns `true` if the tnetstring is Bytes, having read as much of its data as it
+    /// needs; the rest is skipped and the type tag checked. The value is left in the entry as empty
+    /// Bytes whose `size` is the length of the data. `stream` returns `false`, without reading, if
+    /// the tnetstring is a list or dictionary, which is then read one item at a time in the same
+    /// way. A declined tnetstring that is not a container is an error, usually a framing error
+    /// from reading its data as items, and the rest of the message is lost, so `stream` should
+    /// accept every large Bytes value, as identified by its `key` or `position`.
+    pub fn read_entry_streaming<F>(&mut self, threshold: u32, mut stream: F) -> Result<TNetEntry, TNetStrError>
+    where
+        F: FnMut(&LargeFrame, &mut BytesReader<'_, dyn Read + '_>) -> Result<bool, TNetStrError>
+    {
+        let data_length = self.read_length()?;
+        if data_length <= threshold {
+            return self.read_entry_data(data_length)
+        }
+        let mut frame = Frame {
+            threshold,
+            buffer: &mut self.buffer,
+            position: vec![],
+            stream: &mut stream
+        };
+        frame.read_large(&mut self.reader, None, data_length)
+    }
+
+    /// Reads the length of the next tnetstring, which must be Bytes, and returns a reader over its
+    /// data. `BytesReader::finish` must be called once the data has been read, to check the type tag.
+    pub fn read_bytes(&mut self) -> Result<BytesReader<'_, R>, TNetStrError> {
+        let data_length = self.read_length()?;
+        Ok(BytesReader {
+            reader: &mut self.reader,
+            remaining: u64::from(data_length)
+        })
+    }
+}
+
+/// The state of `TNetReader::read_entry_streaming` while it reads the items of large tnetstrings
+struct Frame<'a, F> {
+    threshold: u32,
+    buffer: &'a mut Vec<u8>,
+    position: Vec<usize>,
+    stream: &'a mut F
+}
+
+impl<F> Frame<'_, F>
+where
+    F: FnMut(&LargeFrame, &mut BytesReader<'_, dyn Read + '_>) -> Result<bool, TNetStrError>
+{
+    /// Reads a tnetstring longer than the threshold whose length has been read from `reader`
+    fn read_large(&mut self, reader: &mut dyn Read, key: Option<&[u8]>, len: u32) -> Result<TNetEntry, TNetStrError> {
+        let frame = LargeFrame {
+            position: &self.position,
+            key,
+            len
+        };
+        let mut data = BytesReader {
+            reader,
+            remaining: u64::from(len)
+        };
+        if (self.stream)(&frame, &mut data)? {
+            data.finish()?;
+            return Ok(TNetEntry {
+                data: TNetData::Bytes(vec![]),
+                size: len,
+                data_type: DataType::Bytes
+            })
+        }
+        if data.remaining() != u64::from(len) {
+            return Err(TNetStrError::StreamReadFailed("Data was read from a tnetstring that was not streamed".to_string()))
+        }
+
+        let mut items: Vec<TNetEntry> = vec![];
+        while data.remaining() > 0 {
+            let item_length = match read_frame_length(&mut data, self.buffer)? {
+                Some(item_length) => item_length,
+                None => return Err(TNetStrError::LengthWasNotAccurate)
+            };
+            let item = if item_length <= self.threshold {
+                read_frame_data(&mut data, item_length, self.buffer)?;
+                parse(self.buffer)?
+            } else {
+                let key = match items.last() {
+                    Some(TNetEntry { data: TNetData::Bytes(key), .. }) if items.len() % 2 == 1 => Some(key.as_slice()),
+                    _ => None
+                };
+                self.position.push(items.len());
+                let item = self.read_large(&mut data, key, item_length)?;
+                self.position.pop();
+                item
+            };
+            items.push(item);
+        }
+
+        let mut tag = [0];
+        data.reader.read_exact(&mut tag)?;
+        let data_type = DataType::from_byte(&tag[0])?;
+        let data = match data_type {
+            DataType::List => TNetData::List(items),
+            DataType::Dictionary => {
+                let mut dict = TNetDictionary::new();
+                let mut items = items.into_iter();
+                while let Some(key) = items.next() {
+                    let value = items.next().ok_or(TNetStrError::UnbalancedDictionary)?;
+                    dict.add(key, value)?;
+                }
+                TNetData::Dictionary(dict)
+            },
+            t => return Err(TNetStrError::UnexpectedDataType(DataType::List.to_byte(), t.to_byte()))
+        };
+        Ok(TNetEntry {
+            data,
+            size: len,
+            data_type
+        })
+    }
+}
+
+/// Reads the data of a single Bytes tnetstring from the underlying stream
+pub struct BytesReader<'a, R: Read + ?Sized> {
+    reader: &'a mut R,
+    remaining: u64
+}
+
+impl<'a, R: Read + ?Sized> BytesReader<'a, R> {
+
+    /// The number of bytes of data not yet read
+    pub fn remaining(&self) -> u64 {
+        self.remaining
+    }
+
+    /// Copies the unread data to `sink`, returning the number of bytes copied. Failures to read
+    /// are `StreamReadFailed` and failures to write `StreamWriteFailed`.
+    pub fn copy_to<W: Write + ?Sized>(&mut self, sink: &mut W) -> Result<u64, TNetStrError> {
+        let mut buffer = [0; 8192];
+        let mut copied = 0;
+        loop {
+            let read = match self.read(&mut buffer) {
+                Ok(0) => return Ok(copied),
+                Ok(read) => read,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e.into())
+            };
+            sink.write_all(&buffer[..read]).map_err(|e| TNetStrError::StreamWriteFailed(format!("{:?}", e)))?;
+            copied += read as u64;
+        }
+    }
+
+    /// Skips any unread data, then reads the type tag and checks that it is Bytes
+    pub fn finish(mut self) -> Result<(), TNetStrError> {
+        io::copy(&mut self, &mut io::sink())?;
+        let mut tag = [0];
+        self.reader.read_exact(&mut tag)?;
+        match DataType::from_byte(&tag[0])? {
+            DataType::Bytes => Ok(()),
+            t => Err(TNetStrError::UnexpectedDataType(DataType::Bytes.to_byte(), t.to_byte()))
+        }
+    }
+}
+
+impl<'a, R: Read + ?Sized> Read for BytesReader<'a, R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.remaining == 0 {
+            return Ok(0)
+        }
+        let max = self.remaining.min(buf.len() as u64) as usize;
+        let read = self.reader.read(&mut buf[..max])?;
+        if read == 0 {
+            return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "stream ended before the end of the tnetstring data"))
+        }
+        self.remaining -= read as u64;
+        Ok(read)
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+    use super::super::TNetData;
+
+    #[test]
+    fn copies_bytes_to_sink() {
+        let mut reader = TNetReader::new(&b"10:aaaaaaaaaa,3:123#"[..]);
+        let mut sink = vec![];
+        assert_eq!(reader.read_bytes_to(&mut sink), Ok(10));
+        assert_eq!(sink, b"aaaaaaaaaa");
+        assert_eq!(reader.read_entry().unwrap().data, TNetData::Integer(123));
+    }
+
+    #[test]
+    fn reads_bytes_through_adapter() {
+        let mut reader = TNetReader::new(&b"5:hello,0:~"[..]);
+        let mut bytes = reader.read_bytes().unwrap();
+        let mut start = [0; 2];
+        bytes.read_exact(&mut start).unwrap();
+        assert_eq!(&start, b"he");
+        assert_eq!(bytes.remaining(), 3);
+        bytes.finish().unwrap();
+        assert_eq!(reader.read_entry().unwrap().data, TNetData::Null);
+    }
+
+    #[test]
+    fn streams_large_bytes_in_a_message() {
+        let input = [&b"43:2:id,1:7#7:payload,20:xxxxxxxxxxxxxxxxxxxx,}"[..], b"3:abc,"].concat();
+        let mut reader = TNetReader::new(&input[..]);
+        let mut sink = vec![];
+        let entry = reader.read_entry_streaming(10, |frame, data| match frame.key {
+            Some(b"payload") => data.copy_to(&mut sink).map(|_| true),
+            _ => Ok(false)
+        }).unwrap();
+        assert_eq!(sink, [b'x'; 20]);
+        let dict = entry.data.as_dictionary().unwrap();
+        assert_eq!(dict.get(b"id")[0].data, TNetData::Integer(7));
+        let payload = dict.get(b"payload")[0];
+        assert_eq!((&payload.data, payload.size), (&TNetData::Bytes(vec![]), 20));
+        assert_eq!(reader.read_entry_streaming(10, |_, _| Ok(true)).unwrap(), parse(b"3:abc,").unwrap());
+    }
+
+    #[test]
+    fn streams_bytes_in_nested_containers() {
+        let mut reader = TNetReader::new(&b"36:5:parts,24:16:yyyyyyyyyyyyyyyy,1:b,]}"[..]);
+        let mut positions = vec![];
+        let mut sink = vec![];
+        let entry = reader.read_entry_streaming(8, |frame, data| {
+            positions.push(frame.position.to_vec());
+            match frame.position {
+                [1, 0] => data.copy_to(&mut sink).map(|_| true),
+                _ => Ok(false)
+            }
+        }).unwrap();
+        assert_eq!(positions, vec![vec![], vec![1], vec![1, 0]]);
+        assert_eq!(sink, [b'y'; 16]);
+        let parts = entry.data.as_dictionary().unwrap().get(b"parts")[0].data.as_list().unwrap();
+        assert_eq!(parts[1].data, TNetData::Bytes(b"b".to_vec()));
+    }
+
+    #[test]
+    fn rejects_declined_bytes() {
+        let mut reader = TNetReader::new(&b"12:aaaaaaaaaaaa,"[..]);
+        assert_eq!(reader.read_entry_streaming(4, |_, _| Ok(false)), Err(TNetStrError::NonASCIINumericValueInLengthField(b'a')));
+    }
+
+    #[test]
+    fn checks_type_tag_after_data() {
+        let mut reader = TNetReader::new(&b"3:123#"[..]);
+        assert_eq!(reader.read_bytes_to(&mut vec![]), Err(TNetStrError::UnexpectedDataType(b',', b'#')));
+    }
+
+    struct FullSink;
+
+    impl Write for FullSink {
+        fn write(&mut self, _: &[u8]) -> io::Result<usize> {
+            Err(io::Error::new(io::ErrorKind::WriteZero, "full"))
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn reports_sink_failures_as_write_failures() {
+        let mut reader = TNetReader::new(&b"5:hello,"[..]);
+        match reader.read_entry_streaming(1, |_, data| data.copy_to(&mut FullSink).map(|_| true)) {
+            Err(TNetStrError::StreamWriteFailed(_)) => {},
+            other => panic!("Expected a write failure, got {:?}", other)
+        }
+    }
+
+    #[test]
+    fn reports_truncated_data() {
+        let mut reader = TNetReader::new(&b"10:abc"[..]);
+        match reader.read_bytes_to(&mut vec![]) {
+            Err(TNetStrError::StreamReadFailed(_)) => {},
+            other => panic!("Expected a read failure, got {:?}", other)
+        }
+    }
+}
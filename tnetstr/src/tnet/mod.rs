@@ -1,10 +1,20 @@
 pub mod tnetfloat;
+#[cfg(feature = "alloc")]
 pub mod tnetdata;
+#[cfg(feature = "alloc")]
 pub mod tnetdict;
+#[cfg(feature = "alloc")]
 pub mod tnetentry;
 
+#[cfg(feature = "alloc")]
+use alloc::vec::Vec;
+
 pub use self::tnetfloat::TNetFloat;
+#[cfg(feature = "alloc")]
 pub use self::tnetdict::TNetDictionary;
+#[cfg(feature = "alloc")]
 pub use self::tnetdata::TNetData;
+#[cfg(feature = "alloc")]
 pub use self::tnetentry::TNetEntry;
+#[cfg(feature = "alloc")]
 pub type TNetList = Vec<TNetEntry>;
\ No newline at end of file
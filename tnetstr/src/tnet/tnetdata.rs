@@ -1,4 +1,13 @@
-use std::{
+use alloc::{
+    format,
+    string::{
+        String,
+        ToString
+    },
+    vec::Vec
+};
+use core::{
+    cmp::Ordering,
     fmt::{
         Display,
         Formatter,
@@ -10,8 +19,9 @@ use super::{
     TNetDictionary,
     TNetList
 };
+use super::super::DataType;
 
-#[derive(Eq, PartialEq, Hash, Debug)]
+#[derive(Clone, Eq, PartialEq, Hash, Debug)]
 pub enum TNetData {
     Bytes(Vec<u8>),
     Integer(i64),
@@ -19,14 +29,116 @@ pub enum TNetData {
     Boolean(bool),
     Null,
     Dictionary(TNetDictionary),
-    List(TNetList)
+    List(TNetList),
+    /// Data with a type tag outside the spec, kept as it was read (see `Extensions`)
+    Extension { tag: u8, payload: Vec<u8> }
+}
+
+impl TNetData {
+
+    /// The type tag this data is encoded with
+    pub fn data_type(&self) -> DataType {
+        match self {
+            TNetData::Bytes(_) => DataType::Bytes,
+            TNetData::Integer(_) => DataType::Integer,
+            TNetData::Float(_) => DataType::Float,
+            TNetData::Boolean(_) => DataType::Boolean,
+            TNetData::Null => DataType::Null,
+            TNetData::Dictionary(_) => DataType::Dictionary,
+            TNetData::List(_) => DataType::List,
+            TNetData::Extension { tag, .. } => DataType::Extension(*tag)
+        }
+    }
+
+    pub fn as_bytes(&self) -> Option<&[u8]> {
+        match self {
+            TNetData::Bytes(b) => Some(b),
+            _ => None
+        }
+    }
+
+    pub fn as_integer(&self) -> Option<i64> {
+        match self {
+            TNetData::Integer(n) => Some(*n),
+            _ => None
+        }
+    }
+
+    pub fn as_float(&self) -> Option<&TNetFloat> {
+        match self {
+            TNetData::Float(n) => Some(n),
+            _ => None
+        }
+    }
+
+    pub fn as_bool(&self) -> Option<bool> {
+        match self {
+            TNetData::Boolean(b) => Some(*b),
+            _ => None
+        }
+    }
+
+    pub fn is_null(&self) -> bool {
+        matches!(self, TNetData::Null)
+    }
+
+    pub fn as_dictionary(&self) -> Option<&TNetDictionary> {
+        match self {
+            TNetData::Dictionary(d) => Some(d),
+            _ => None
+        }
+    }
+
+    pub fn as_list(&self) -> Option<&TNetList> {
+        match self {
+            TNetData::List(l) => Some(l),
+            _ => None
+        }
+    }
+
+    /// The tag and payload of extension data
+    pub fn as_extension(&self) -> Option<(u8, &[u8])> {
+        match self {
+            TNetData::Extension { tag, payload } => Some((*tag, payload)),
+            _ => None
+        }
+    }
+}
+
+/// Values are ordered by type first, in the order null, boolean, integer, float, bytes, list,
+/// dictionary, extensions (see `DataType`), then by value: booleans with `false` first, integers
+/// and floats numerically, bytes as unsigned bytes with a prefix first, lists item by item with a
+/// prefix first, dictionaries pair by pair in the order they were written, and extensions by tag
+/// then payload. Floats and integers never
+/// compare equal here; see `TNetData::cmp_numeric` for that.
+impl Ord for TNetData {
+    fn cmp(&self, other: &TNetData) -> Ordering {
+        match (self, other) {
+            (TNetData::Bytes(a), TNetData::Bytes(b)) => a.cmp(b),
+            (TNetData::Integer(a), TNetData::Integer(b)) => a.cmp(b),
+            (TNetData::Float(a), TNetData::Float(b)) => a.cmp(b),
+            (TNetData::Boolean(a), TNetData::Boolean(b)) => a.cmp(b),
+            (TNetData::Null, TNetData::Null) => Ordering::Equal,
+            (TNetData::Dictionary(a), TNetData::Dictionary(b)) => a.cmp(b),
+            (TNetData::List(a), TNetData::List(b)) => a.cmp(b),
+            (TNetData::Extension { tag: a, payload: x }, TNetData::Extension { tag: b, payload: y }) =>
+                a.cmp(b).then_with(|| x.cmp(y)),
+            (a, b) => a.data_type().cmp(&b.data_type())
+        }
+    }
+}
+
+impl PartialOrd for TNetData {
+    fn partial_cmp(&self, other: &TNetData) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
 }
 
 impl Display for TNetData {
     fn fmt(&self, f: &mut Formatter) -> Result<(), Error> {
         match self {
             TNetData::Null => write!(f, ""),
-            TNetData::Bytes(b) => {
+            TNetData::Bytes(b) | TNetData::Extension { payload: b, .. } => {
                 let c = b.iter().map(|c| *c as char).collect::<String>();
                 write!(f, "{}", c)
             },
@@ -36,7 +148,7 @@ impl Display for TNetData {
             TNetData::Dictionary(dict) => {
                 let s = dict.entries.iter()
                     .map(|e| format!("{}{}", e.0, e.1))
-                    .fold("".to_string(), |a, v| a + &format!("{}", v));
+                    .fold("".to_string(), |a, v| a + &v);
                 write!(f, "{}", s)
             },
             TNetData::List(l) => {
@@ -1,3 +1,7 @@
+use alloc::{
+    vec,
+    vec::Vec
+};
 use super::{
     TNetEntry,
     TNetData
@@ -10,7 +14,8 @@ use super::super::{
 /// A tnetdictionary, containing pairs of (Key: Bytes, Value AnyData)
 /// It is unclear from the spec whether duplicate keys are permitted, so
 /// this is implemented so as to support them.
-#[derive(Debug, Hash, Eq, PartialEq)]
+/// Dictionaries are ordered by comparing their pairs in order, as `Eq` compares them.
+#[derive(Debug, Clone, Default, Hash, Eq, PartialEq, Ord, PartialOrd)]
 pub struct TNetDictionary {
     pub entries: Vec<(TNetEntry, TNetEntry)>
 }
@@ -26,7 +31,7 @@ impl TNetDictionary {
     pub fn from_vec(mut input: Vec<TNetEntry>) -> Result<TNetDictionary, TNetStrError> {
         let mut d = TNetDictionary::new();
 
-        while input.len() > 0 {
+        while !input.is_empty() {
             let mut pairs: Vec<_> = input.drain(0..2).collect();
             let value = pairs.pop().unwrap();
             let key = match pairs.pop() {
@@ -40,7 +45,10 @@ impl TNetDictionary {
 
     pub fn add(&mut self, key: TNetEntry, value: TNetEntry) -> Result<(), TNetStrError> {
         match key.data_type {
-            DataType::Bytes => Ok(self.entries.push((key, value))),
+            DataType::Bytes => {
+                self.entries.push((key, value));
+                Ok(())
+            },
             _ => Err(TNetStrError::DictionaryKeyWasNotBytes)
         }
     }
@@ -49,10 +57,9 @@ impl TNetDictionary {
         let mut r : Vec<&TNetEntry> = vec![];
         for entry in &self.entries {
             match &entry.0.data {
-                TNetData::Bytes(b) => match b.as_slice() == key {
-                    true => r.push(&entry.1),
-                    false => {}
-                } ,
+                TNetData::Bytes(b) => if b.as_slice() == key {
+                    r.push(&entry.1)
+                },
                 _ => panic!("TNetDictionary had non-byte key")
             }
         };
@@ -1,4 +1,4 @@
-use std::fmt::{
+use core::fmt::{
     Display,
     Formatter,
     Error
@@ -7,9 +7,13 @@ use super::super::{
     TNetData,
     DataType
 };
+use super::super::writer::{
+    frame_len,
+    scalar_len
+};
 
-/// Represents a tnetstring
-#[derive(Eq, PartialEq, Hash, Debug)]
+/// Represents a tnetstring. Entries are ordered by their data, then by their size.
+#[derive(Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug)]
 pub struct TNetEntry {
     /// The data content of the tnetstring
     pub data: TNetData,
@@ -19,6 +23,54 @@ pub struct TNetEntry {
     pub data_type: DataType
 }
 
+impl TNetEntry {
+
+    /// Sets the `size` and `data_type` of the entry and everything in it from their data, as
+    /// they would be after encoding and parsing it. Entries built or modified by hand may not
+    /// have accurate sizes, which `PartialEq` compares.
+    pub fn update_sizes(&mut self) {
+        let size = match &mut self.data {
+            TNetData::Dictionary(dict) => dict.entries.iter_mut()
+                .map(|(key, value)| {
+                    key.update_sizes();
+                    value.update_sizes();
+                    frame_len(key.size as usize) + frame_len(value.size as usize)
+                })
+                .sum(),
+            TNetData::List(l) => l.iter_mut()
+                .map(|entry| {
+                    entry.update_sizes();
+                    frame_len(entry.size as usize)
+                })
+                .sum(),
+            data => scalar_len(data).expect("Bug - container length was not calculated")
+        };
+        self.size = size as u32;
+        self.data_type = self.data.data_type();
+    }
+}
+
+/// An entry for the data, with its `size` and `data_type` set from the data. The children of a
+/// container are assumed to have accurate sizes already, so only the outermost entry is measured.
+impl From<TNetData> for TNetEntry {
+    fn from(data: TNetData) -> TNetEntry {
+        let size = match &data {
+            TNetData::Dictionary(dict) => dict.entries.iter()
+                .map(|(key, value)| frame_len(key.size as usize) + frame_len(value.size as usize))
+                .sum(),
+            TNetData::List(l) => l.iter()
+                .map(|entry| frame_len(entry.size as usize))
+                .sum(),
+            data => scalar_len(data).expect("Bug - container length was not calculated")
+        };
+        TNetEntry {
+            size: size as u32,
+            data_type: data.data_type(),
+            data
+        }
+    }
+}
+
 impl Display for TNetEntry {
     fn fmt(&self, f: &mut Formatter) -> Result<(), Error> {
         write!(f, "{}:{}{}", self.size, self.data, self.data_type)
@@ -1,46 +1,400 @@
-use std::{
+#[cfg(feature = "alloc")]
+use alloc::{
+    format,
+    string::ToString
+};
+use core::{
+    cmp::Ordering,
     fmt::{
         Display,
         Error,
         Formatter
     }
 };
+#[cfg(feature = "alloc")]
+use core::{
+    convert::TryFrom,
+    fmt::LowerExp
+};
 use super::super::TNetStrError;
 
+/// The values a float can have besides a decimal number
+#[derive(Debug, Clone, Copy, Hash, Eq, PartialEq)]
+pub enum NonFinite {
+    /// Infinity, negative if the float's `negative` is set, written `inf` or `-inf`
+    Infinity,
+    /// Not a number, written `nan`
+    NaN
+}
+
 // A hashable, equatable float
-#[derive(Debug, Hash, Eq, PartialEq)]
+//
+// The value is `integral.fractional` with `leading_zeros` zeros after the `.`, negated if
+// `negative` is set and multiplied by ten to the power of `exponent`, unless `non_finite` is set.
+#[derive(Debug, Clone, Default, Hash, Eq, PartialEq)]
 pub struct TNetFloat {
+    pub negative: bool,
     pub integral: u64,
     pub fractional: u64,
+    /// The number of zeros between the `.` and the digits of `fractional`, such as 1 for `0.05`
+    pub leading_zeros: u32,
+    /// The power of ten the number is multiplied by, written after an `e` if it is not 0
+    pub exponent: i32,
+    pub non_finite: Option<NonFinite>
+}
+
+/// The error for text that is not a valid float. Without `alloc` the text cannot be kept.
+#[cfg(feature = "alloc")]
+fn float_parse_error(s: &str) -> TNetStrError {
+    TNetStrError::FloatParseError(s.to_string())
+}
+
+#[cfg(not(feature = "alloc"))]
+fn float_parse_error(_: &str) -> TNetStrError {
+    TNetStrError::CouldNotParseData
+}
+
+/// The number of decimal digits in `n`, or 0 for 0
+fn digit_count(n: u64) -> u32 {
+    match n {
+        0 => 0,
+        n => n.ilog10() + 1
+    }
+}
+
+/// The digit `index` places from the left of `n`, which has `count` digits
+fn digit_of(n: u64, count: u32, index: u32) -> u8 {
+    (n / 10u64.pow(count - 1 - index) % 10) as u8
 }
 
 impl TNetFloat {
 
-    /// Parses a TNetFloat from a string representing it's decimal value (e.g. 10.21)
+    /// Parses a TNetFloat from a string representing it's decimal value (e.g. `10.21`, `-0.05`
+    /// or `1.5e-7`), or `inf`, `-inf` or `nan`. There must be a `.` or an exponent.
     pub fn from_decimal_str(s: &str) -> Result<TNetFloat, TNetStrError> {
-        let parts: Vec<&str> = s.split('.').collect();
-        if parts.len() != 2 {
-            Err(TNetStrError::FloatParseError(s.to_string()))
-        } else {
-            match parts[0].parse::<u64>() {
-                Err(_) => Err(TNetStrError::FloatParseError(s.to_string())),
-                Ok(integral) => match parts[1].parse::<u64>() {
-                    Err(_) => Err(TNetStrError::FloatParseError(s.to_string())),
-                    Ok(fractional) => Ok(TNetFloat { integral, fractional })
+        let (negative, unsigned) = match s.strip_prefix('-') {
+            Some(unsigned) => (true, unsigned),
+            None => (false, s)
+        };
+        match unsigned {
+            "inf" => return Ok(TNetFloat { negative, non_finite: Some(NonFinite::Infinity), ..TNetFloat::default() }),
+            "nan" if !negative => return Ok(TNetFloat { non_finite: Some(NonFinite::NaN), ..TNetFloat::default() }),
+            _ => {}
+        }
+        let digits = |text: &str| !text.is_empty() && text.bytes().all(|c| c.is_ascii_digit());
+        let (number, exponent) = match unsigned.split_once(['e', 'E']) {
+            Some((number, exponent)) => {
+                let unsigned_exponent = exponent.strip_prefix(['+', '-']).unwrap_or(exponent);
+                if !digits(unsigned_exponent) {
+                    return Err(float_parse_error(s))
                 }
-            }
+                (number, Some(exponent.parse::<i32>().map_err(|_| float_parse_error(s))?))
+            },
+            None => (unsigned, None)
+        };
+        let (integral, fractional) = match (number.split_once('.'), exponent) {
+            (Some((integral, fractional)), _) if digits(fractional) => (integral, fractional),
+            (None, Some(_)) => (number, "0"),
+            _ => return Err(float_parse_error(s))
+        };
+        if !digits(integral) {
+            return Err(float_parse_error(s))
         }
+        let integral = integral.parse::<u64>().map_err(|_| float_parse_error(s))?;
+        // an all zero fractional part keeps all but one of its zeros, as `fractional` is written as `0`
+        let significant = fractional.trim_start_matches('0');
+        let leading_zeros = (fractional.len() - significant.len().max(1)) as u32;
+        let fractional = match significant {
+            "" => 0,
+            significant => significant.parse::<u64>().map_err(|_| float_parse_error(s))?
+        };
+        Ok(TNetFloat {
+            negative,
+            integral,
+            fractional,
+            leading_zeros,
+            exponent: exponent.unwrap_or(0),
+            non_finite: None
+        })
+    }
+
+    /// The shortest float that reads back as `n`: with an exponent if `n` is below `1e-5` or at
+    /// least `1e16`, as Python writes floats, and without one otherwise
+    #[cfg(feature = "alloc")]
+    fn from_shortest<N: Display + LowerExp>(n: N, magnitude: f64) -> TNetFloat {
+        let text = match magnitude == 0.0 || (1e-5..1e16).contains(&magnitude) {
+            true => format!("{}", n),
+            false => format!("{:e}", n)
+        };
+        let text = match text.contains(['.', 'e']) {
+            true => text,
+            false => text + ".0"
+        };
+        TNetFloat::from_decimal_str(&text).expect("Bug - formatted float was not read")
+    }
+
+    /// The float that reads back as `n`, with as few digits as possible
+    #[cfg(feature = "alloc")]
+    pub fn from_f64(n: f64) -> TNetFloat {
+        match n {
+            n if n.is_nan() => TNetFloat { non_finite: Some(NonFinite::NaN), ..TNetFloat::default() },
+            n if n.is_infinite() => TNetFloat { negative: n < 0.0, non_finite: Some(NonFinite::Infinity), ..TNetFloat::default() },
+            n => TNetFloat::from_shortest(n, n.abs())
+        }
+    }
+
+    /// The float that reads back as `n`, with as few digits as possible
+    #[cfg(feature = "alloc")]
+    pub fn from_f32(n: f32) -> TNetFloat {
+        match n {
+            n if !n.is_finite() => TNetFloat::from_f64(n as f64),
+            n => TNetFloat::from_shortest(n, n.abs() as f64)
+        }
+    }
+
+    /// The digit at `index` of the float's digits with the `.` removed, leaving out the `0`
+    /// written for a zero integral or fractional part, or 0 past the end
+    fn digit(&self, index: u64) -> u8 {
+        let integral_digits = digit_count(self.integral) as u64;
+        let fractional_start = integral_digits + self.leading_zeros as u64;
+        let fractional_digits = digit_count(self.fractional);
+        if index < integral_digits {
+            digit_of(self.integral, integral_digits as u32, index as u32)
+        } else if index >= fractional_start && index < fractional_start + fractional_digits as u64 {
+            digit_of(self.fractional, fractional_digits, (index - fractional_start) as u32)
+        } else {
+            0
+        }
+    }
+
+    /// The indexes (see `digit`) of the first and last digits that are not zero, with the power
+    /// of ten of the first digit plus one, or `None` for zero and values that are not finite
+    fn significant_digits(&self) -> Option<(u64, u64, i64)> {
+        if self.non_finite.is_some() {
+            return None
+        }
+        let integral_digits = digit_count(self.integral) as u64;
+        let fractional_end = integral_digits + self.leading_zeros as u64 + digit_count(self.fractional) as u64;
+        let (first, end) = match (self.integral, self.fractional) {
+            (0, 0) => return None,
+            (0, _) => (self.leading_zeros as u64, fractional_end),
+            (_, 0) => (0, integral_digits),
+            (_, _) => (0, fractional_end)
+        };
+        let last = (first..end).rev().find(|i| self.digit(*i) != 0).expect("Bug - no significant digit");
+        Some((first, last, integral_digits as i64 - first as i64 + self.exponent as i64))
+    }
+
+    /// Compares the sizes of finite floats, ignoring their signs
+    fn cmp_magnitude(&self, other: &TNetFloat) -> Ordering {
+        let (a, b) = match (self.significant_digits(), other.significant_digits()) {
+            (None, None) => return Ordering::Equal,
+            (None, Some(_)) => return Ordering::Less,
+            (Some(_), None) => return Ordering::Greater,
+            (Some(a), Some(b)) => (a, b)
+        };
+        a.2.cmp(&b.2)
+            .then_with(|| (0..=(a.1 - a.0).max(b.1 - b.0))
+                .map(|i| self.digit(a.0 + i).cmp(&other.digit(b.0 + i)))
+                .find(|ordering| ordering.is_ne())
+                .unwrap_or(Ordering::Equal))
+    }
+
+    /// -1, 0 or 1 for negative values, zero and positive values, or 2 for NaN
+    fn sign(&self) -> i8 {
+        match self.non_finite {
+            Some(NonFinite::NaN) => 2,
+            Some(NonFinite::Infinity) => if self.negative { -1 } else { 1 },
+            None if self.significant_digits().is_none() => 0,
+            None => if self.negative { -1 } else { 1 }
+        }
+    }
+
+    /// Compares floats by value, so `1.5`, `1.50` and `15.0e-1` are equal, as are `0.0` and
+    /// `-0.0`. NaN is greater than every other value and equal to itself.
+    pub(crate) fn cmp_value(&self, other: &TNetFloat) -> Ordering {
+        let infinite = |n: &TNetFloat| n.non_finite == Some(NonFinite::Infinity);
+        self.sign().cmp(&other.sign()).then_with(|| {
+            let magnitude = match (infinite(self), infinite(other)) {
+                (true, true) => Ordering::Equal,
+                (true, false) => Ordering::Greater,
+                (false, true) => Ordering::Less,
+                (false, false) => self.cmp_magnitude(other)
+            };
+            match self.sign() {
+                -1 => magnitude.reverse(),
+                _ => magnitude
+            }
+        })
+    }
+
+    /// Compares the float with an integer by value
+    #[cfg(feature = "alloc")]
+    pub(crate) fn cmp_integer(&self, n: i64) -> Ordering {
+        self.cmp_value(&TNetFloat { negative: n < 0, integral: n.unsigned_abs(), ..TNetFloat::default() })
+    }
+
+    /// The number formed by the digits from `start` to before `end`, if it fits in a u64
+    #[cfg(feature = "alloc")]
+    fn digits_between(&self, start: u64, end: u64) -> Option<u64> {
+        (start..end).try_fold(0u64, |n, i| n.checked_mul(10)?.checked_add(self.digit(i) as u64))
+    }
+
+    /// The float with the significant digits from `first` to before `end`, `point` of them before
+    /// the `.`, and the given exponent, if its parts fit in their fields
+    #[cfg(feature = "alloc")]
+    fn with_point(&self, first: u64, end: u64, point: i64, exponent: i64) -> Option<TNetFloat> {
+        let (integral, fraction_start, leading_zeros) = match point {
+            point if point <= 0 => (0, first, u32::try_from(-point).ok()?),
+            point => {
+                let fraction_start = first + point as u64;
+                let zeros = (fraction_start..end).take_while(|i| self.digit(*i) == 0).count() as u32;
+                (self.digits_between(first, fraction_start)?, fraction_start + zeros as u64, zeros)
+            }
+        };
+        let fractional = self.digits_between(fraction_start, end)?;
+        Some(TNetFloat {
+            negative: self.negative,
+            integral,
+            fractional,
+            leading_zeros: if fractional == 0 { 0 } else { leading_zeros },
+            exponent: i32::try_from(exponent).ok()?,
+            non_finite: None
+        })
+    }
+
+    /// The canonical form of the float, which is the same for every float of the same value: `nan`,
+    /// `inf` and `-inf` as they are, zero as `0.0`, and other values without an exponent if it
+    /// would be from -5 to 15 and with a single integral digit and the exponent otherwise, dropping
+    /// every zero that is not needed. Floats with too many digits for this are only stripped of
+    /// trailing zeros.
+    #[cfg(feature = "alloc")]
+    pub(crate) fn canonical(&self) -> TNetFloat {
+        let (first, last, magnitude) = match (self.non_finite, self.significant_digits()) {
+            (Some(NonFinite::NaN), _) => return TNetFloat { non_finite: Some(NonFinite::NaN), ..TNetFloat::default() },
+            (Some(NonFinite::Infinity), _) => return TNetFloat { negative: self.negative, non_finite: self.non_finite, ..TNetFloat::default() },
+            (None, None) => return TNetFloat::default(),
+            (None, Some(digits)) => digits
+        };
+        let end = last + 1;
+        let shortest = match (-5..16).contains(&(magnitude - 1)) {
+            true => self.with_point(first, end, magnitude, 0),
+            false => self.with_point(first, end, 1, magnitude - 1)
+        };
+        shortest.unwrap_or_else(|| {
+            let mut fractional = self.fractional;
+            while fractional != 0 && fractional.is_multiple_of(10) {
+                fractional /= 10;
+            }
+            TNetFloat {
+                fractional,
+                leading_zeros: if fractional == 0 { 0 } else { self.leading_zeros },
+                ..self.clone()
+            }
+        })
     }
 
     /// Returns the TNetFloat's value as an f64
+    #[cfg(feature = "alloc")]
     pub fn to_f64(&self) -> f64 {
-        format!("{}.{}", self.integral, self.fractional).parse().expect("Bug - parse to f64 failed")
+        self.to_string().parse().expect("Bug - parse to f64 failed")
+    }
+
+    /// Returns the TNetFloat's value as an f32, rounded once from the decimal value
+    #[cfg(feature = "alloc")]
+    pub fn to_f32(&self) -> f32 {
+        self.to_string().parse().expect("Bug - parse to f32 failed")
+    }
+}
+
+/// Floats are ordered by value, with NaN last and floats of equal value but different text
+/// (`1.5` and `1.50`) ordered by their fields, so that the ordering agrees with `Eq`
+impl Ord for TNetFloat {
+    fn cmp(&self, other: &TNetFloat) -> Ordering {
+        let fields = |n: &TNetFloat| (n.negative, n.integral, n.fractional, n.leading_zeros, n.exponent);
+        self.cmp_value(other).then_with(|| fields(self).cmp(&fields(other)))
+    }
+}
+
+impl PartialOrd for TNetFloat {
+    fn partial_cmp(&self, other: &TNetFloat) -> Option<Ordering> {
+        Some(self.cmp(other))
     }
 }
 
+/// Writes the float as it is encoded in a tnetstring
 impl Display for TNetFloat {
 
     fn fmt(&self, f: &mut Formatter) -> Result<(), Error> {
-        write!(f, "{:.32}", self.to_f64())
+        match self.non_finite {
+            Some(NonFinite::NaN) => return write!(f, "nan"),
+            Some(NonFinite::Infinity) => return write!(f, "{}inf", if self.negative { "-" } else { "" }),
+            None => {}
+        }
+        if self.negative {
+            write!(f, "-")?;
+        }
+        write!(f, "{}.", self.integral)?;
+        for _ in 0..self.leading_zeros {
+            write!(f, "0")?;
+        }
+        write!(f, "{}", self.fractional)?;
+        match self.exponent {
+            0 => Ok(()),
+            e if e < 0 => write!(f, "e{}", e),
+            e => write!(f, "e+{}", e)
+        }
+    }
+}
+
+#[cfg(all(test, feature = "alloc"))]
+mod test {
+
+    use super::*;
+    use alloc::vec;
+
+    fn float(s: &str) -> TNetFloat {
+        TNetFloat::from_decimal_str(s).unwrap()
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn reads_and_writes_signs_exponents_and_non_finite_values() {
+        for text in vec!["-1.5", "0.05", "3.01", "1.0e+300", "1.5e-7", "-0.0", "inf", "-inf", "nan", "1.00"] {
+            assert_eq!(float(text).to_string(), text);
+        }
+        assert_eq!(float("1e300"), float("1.0e+300"));
+        assert_eq!(float("2.5E-3").to_string(), "2.5e-3");
+        for text in vec!["1", "1.", ".5", "+1.0", "1.5e", "1.5e+", "-nan", "1.-5", "1.0x"] {
+            assert!(TNetFloat::from_decimal_str(text).is_err(), "{}", text);
+        }
+    }
+
+    #[test]
+    fn converts_f64_without_loss() {
+        for n in vec![-1.5, 0.05, 3.01, 1e300, -1e-300, 1.5e-7, 0.1, 123456789.125, f64::MAX, f64::MIN_POSITIVE, 5e-324, 0.0] {
+            assert_eq!(TNetFloat::from_f64(n).to_f64(), n);
+        }
+        assert_eq!(TNetFloat::from_f64(0.05).to_string(), "0.05");
+        assert_eq!(TNetFloat::from_f64(2.0).to_string(), "2.0");
+        assert_eq!(TNetFloat::from_f64(1e300).to_string(), "1.0e+300");
+        assert_eq!(TNetFloat::from_f32(0.1).to_string(), "0.1");
+        assert!(TNetFloat::from_f64(-0.0).to_f64().is_sign_negative());
+        assert!(TNetFloat::from_f64(f64::NAN).to_f64().is_nan());
+        assert_eq!(TNetFloat::from_f64(f64::NEG_INFINITY).to_f64(), f64::NEG_INFINITY);
+    }
+
+    #[test]
+    fn compares_by_value() {
+        assert_eq!(float("1.5e1").cmp_value(&float("15.0")), Ordering::Equal);
+        assert_eq!(float("-0.0").cmp_value(&float("0.0")), Ordering::Equal);
+        assert_eq!(float("-2.0").cmp_value(&float("-1.5")), Ordering::Less);
+        assert_eq!(float("-0.05").cmp_value(&float("-0.5")), Ordering::Greater);
+        assert_eq!(float("9.9e-1").cmp_value(&float("1.0")), Ordering::Less);
+        assert_eq!(float("-inf").cmp_value(&float("-1.0e+300")), Ordering::Less);
+        assert_eq!(float("inf").cmp_value(&float("1.0e+300")), Ordering::Greater);
+        assert_eq!(float("nan").cmp_value(&float("inf")), Ordering::Greater);
+        assert_eq!(float("-1.5").cmp_integer(-2), Ordering::Greater);
+        assert_eq!(float("1.0e+19").cmp_integer(i64::MAX), Ordering::Greater);
+        assert!(float("1.5") < float("1.50"));
+    }
+}
@@ -0,0 +1,446 @@
+use super::{
+    DataType,
+    TNetData,
+    TNetEntry,
+    TNetFloat,
+    TNetStrError
+};
+use alloc::{
+    string::ToString,
+    vec,
+    vec::Vec
+};
+use core::fmt::{
+    self,
+    Write
+};
+
+/// A destination for the bytes written by `TNetWriter`.
+///
+/// This is implemented for `Vec<u8>`, for `&mut [u8]` (which, like `io::Write`, advances past the
+/// bytes written) and for mutable references to other sinks. With the `std` feature, `IoSink`
+/// adapts any `std::io::Write`.
+pub trait ByteSink {
+    /// Writes all of `bytes` to the sink
+    fn put_bytes(&mut self, bytes: &[u8]) -> Result<(), TNetStrError>;
+}
+
+impl ByteSink for Vec<u8> {
+    fn put_bytes(&mut self, bytes: &[u8]) -> Result<(), TNetStrError> {
+        self.extend_from_slice(bytes);
+        Ok(())
+    }
+}
+
+/// Nothing is written if the slice is too short for `bytes`
+impl ByteSink for &mut [u8] {
+    fn put_bytes(&mut self, bytes: &[u8]) -> Result<(), TNetStrError> {
+        if self.len() < bytes.len() {
+            return Err(TNetStrError::BufferTooSmall { needed: bytes.len() })
+        }
+        let (head, tail) = core::mem::take(self).split_at_mut(bytes.len());
+        head.copy_from_slice(bytes);
+        *self = tail;
+        Ok(())
+    }
+}
+
+impl<S: ByteSink + ?Sized> ByteSink for &mut S {
+    fn put_bytes(&mut self, bytes: &[u8]) -> Result<(), TNetStrError> {
+        (**self).put_bytes(bytes)
+    }
+}
+
+/// Writes to a `std::io::Write`, such as a file or socket
+#[cfg(feature = "std")]
+#[derive(Debug)]
+pub struct IoSink<W: std::io::Write>(pub W);
+
+#[cfg(feature = "std")]
+impl<W: std::io::Write> ByteSink for IoSink<W> {
+    fn put_bytes(&mut self, bytes: &[u8]) -> Result<(), TNetStrError> {
+        self.0.write_all(bytes)?;
+        Ok(())
+    }
+}
+
+/// The number of characters in the decimal representation of `n`
+pub(crate) fn decimal_len(mut n: u64) -> usize {
+    let mut len = 1;
+    while n >= 10 {
+        n /= 10;
+        len += 1;
+    }
+    len
+}
+
+/// Writes the decimal representation of `n` into the end of `buffer`, returning the written part
+pub(crate) fn format_decimal(mut n: u64, buffer: &mut [u8; 20]) -> &[u8] {
+    let mut start = buffer.len();
+    loop {
+        start -= 1;
+        buffer[start] = b'0' + (n % 10) as u8;
+        n /= 10;
+        if n == 0 {
+            break
+        }
+    }
+    &buffer[start..]
+}
+
+pub(crate) fn integer_len(n: i64) -> usize {
+    decimal_len(n.unsigned_abs()) + if n < 0 { 1 } else { 0 }
+}
+
+pub(crate) fn float_len(n: &TNetFloat) -> usize {
+    let mut counter = ByteCounter(0);
+    write!(counter, "{}", n).expect("Bug - counting float failed");
+    counter.0
+}
+
+/// Counts the bytes of formatted text
+struct ByteCounter(usize);
+
+impl fmt::Write for ByteCounter {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.0 += s.len();
+        Ok(())
+    }
+}
+
+/// Writes formatted text to a `TNetWriter`, keeping the sink's error, which `fmt::Error` cannot
+/// carry
+struct FormatSink<'a, W: ByteSink> {
+    writer: &'a mut TNetWriter<W>,
+    error: Option<TNetStrError>
+}
+
+impl<W: ByteSink> fmt::Write for FormatSink<'_, W> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.writer.put(s.as_bytes()).map_err(|e| {
+            self.error = Some(e);
+            fmt::Error
+        })
+    }
+}
+
+/// The longest data the spec allows, as lengths have at most nine digits
+pub const MAX_DATA_LENGTH: usize = 999_999_999;
+
+/// The number of bytes in a whole tnetstring with `data_len` bytes of data
+pub fn frame_len(data_len: usize) -> usize {
+    decimal_len(data_len as u64) + 1 + data_len + 1
+}
+
+/// The encoded data length of a scalar, or `None` for a container
+pub(crate) fn scalar_len(data: &TNetData) -> Option<usize> {
+    match data {
+        TNetData::Null => Some(0),
+        TNetData::Bytes(b) | TNetData::Extension { payload: b, .. } => Some(b.len()),
+        TNetData::Integer(n) => Some(integer_len(*n)),
+        TNetData::Float(n) => Some(float_len(n)),
+        TNetData::Boolean(b) => Some(if *b { 4 } else { 5 }),
+        TNetData::Dictionary(_) | TNetData::List(_) => None
+    }
+}
+
+/// Calculates the encoded data length of `data`, pushing the data length of every container
+/// onto `sizes` in the order the containers start
+fn size_data(data: &TNetData, sizes: &mut Vec<usize>) -> usize {
+    if let Some(len) = scalar_len(data) {
+        return len
+    }
+    let index = sizes.len();
+    sizes.push(0);
+    let len = match data {
+        TNetData::Dictionary(dict) => dict.entries.iter()
+            .map(|(key, value)| frame_len(size_data(&key.data, sizes)) + frame_len(size_data(&value.data, sizes)))
+            .sum(),
+        TNetData::List(l) => l.iter()
+            .map(|entry| frame_len(size_data(&entry.data, sizes)))
+            .sum(),
+        _ => unreachable!()
+    };
+    sizes[index] = len;
+    len
+}
+
+/// Calculates the encoded data length of `data` without recording container lengths
+fn data_len(data: &TNetData) -> usize {
+    match data {
+        TNetData::Dictionary(dict) => dict.entries.iter()
+            .map(|(key, value)| frame_len(data_len(&key.data)) + frame_len(data_len(&value.data)))
+            .sum(),
+        TNetData::List(l) => l.iter()
+            .map(|entry| frame_len(data_len(&entry.data)))
+            .sum(),
+        _ => scalar_len(data).expect("Bug - container length was not calculated")
+    }
+}
+
+/// Returns the number of bytes in the tnetstring encoding of an entry. This does not allocate.
+pub fn encoded_len(entry: &TNetEntry) -> usize {
+    frame_len(data_len(&entry.data))
+}
+
+struct OpenContainer {
+    data_type: DataType,
+    /// The number of bytes written before the container's data started
+    data_start: usize,
+    data_len: usize
+}
+
+/// Writes tnetstrings directly to a sink, without buffering their data.
+///
+/// As the length of a tnetstring comes before its data, containers must be started with the
+/// length of their data already known. `write_entry` calculates these lengths for a `TNetEntry` in
+/// a sizing pass before writing anything. Other callers, such as serializers, can do the same and
+/// write values piece by piece with `start_list`/`start_dictionary`, the scalar methods and `end`.
+pub struct TNetWriter<W: ByteSink> {
+    writer: W,
+    written: usize,
+    open: Vec<OpenContainer>
+}
+
+impl<W: ByteSink> TNetWriter<W> {
+
+    pub fn new(writer: W) -> TNetWriter<W> {
+        TNetWriter {
+            writer,
+            written: 0,
+            open: vec![]
+        }
+    }
+
+    /// Returns the underlying sink
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+
+    /// The number of bytes written so far
+    pub fn written(&self) -> usize {
+        self.written
+    }
+
+    fn put(&mut self, bytes: &[u8]) -> Result<(), TNetStrError> {
+        self.writer.put_bytes(bytes)?;
+        self.written += bytes.len();
+        Ok(())
+    }
+
+    fn put_decimal(&mut self, n: u64) -> Result<(), TNetStrError> {
+        let mut buffer = [0; 20];
+        self.put(format_decimal(n, &mut buffer))
+    }
+
+    /// Writes a length, which must be no more than the spec allows
+    fn put_header(&mut self, data_len: usize) -> Result<(), TNetStrError> {
+        if data_len > MAX_DATA_LENGTH {
+            return Err(TNetStrError::LengthTooLong)
+        }
+        self.put_decimal(data_len as u64)?;
+        self.put(b":")
+    }
+
+    /// Writes a whole tnetstring that is already encoded
+    pub(crate) fn write_raw(&mut self, tnetstring: &[u8]) -> Result<(), TNetStrError> {
+        self.put(tnetstring)
+    }
+
+    /// Writes a whole tnetstring with the given type tag and already encoded data
+    pub fn write_scalar(&mut self, data_type: DataType, data: &[u8]) -> Result<(), TNetStrError> {
+        self.put_header(data.len())?;
+        self.put(data)?;
+        self.put(&[data_type.to_byte()])
+    }
+
+    pub fn write_bytes(&mut self, v: &[u8]) -> Result<(), TNetStrError> {
+        self.write_scalar(DataType::Bytes, v)
+    }
+
+    pub fn write_integer(&mut self, v: i64) -> Result<(), TNetStrError> {
+        self.put_header(integer_len(v))?;
+        if v < 0 {
+            self.put(b"-")?;
+        }
+        self.put_decimal(v.unsigned_abs())?;
+        self.put(b"#")
+    }
+
+    /// Writes an integer too large for an i64. Note that `parse` reads integers as i64.
+    pub fn write_unsigned(&mut self, v: u64) -> Result<(), TNetStrError> {
+        self.put_header(decimal_len(v))?;
+        self.put_decimal(v)?;
+        self.put(b"#")
+    }
+
+    pub fn write_float(&mut self, v: &TNetFloat) -> Result<(), TNetStrError> {
+        self.put_header(float_len(v))?;
+        let mut sink = FormatSink { writer: self, error: None };
+        if write!(sink, "{}", v).is_err() {
+            return Err(sink.error.expect("Bug - formatting float failed"))
+        }
+        self.put(b"^")
+    }
+
+    pub fn write_bool(&mut self, v: bool) -> Result<(), TNetStrError> {
+        self.write_scalar(DataType::Boolean, if v { b"true" } else { b"false" })
+    }
+
+    pub fn write_null(&mut self) -> Result<(), TNetStrError> {
+        self.write_scalar(DataType::Null, b"")
+    }
+
+    fn start(&mut self, data_type: DataType, data_len: usize) -> Result<(), TNetStrError> {
+        self.put_header(data_len)?;
+        self.open.push(OpenContainer {
+            data_type,
+            data_start: self.written,
+            data_len
+        });
+        Ok(())
+    }
+
+    /// Starts a list whose items will take `data_len` bytes in total
+    pub fn start_list(&mut self, data_len: usize) -> Result<(), TNetStrError> {
+        self.start(DataType::List, data_len)
+    }
+
+    /// Starts a dictionary whose keys and values will take `data_len` bytes in total
+    pub fn start_dictionary(&mut self, data_len: usize) -> Result<(), TNetStrError> {
+        self.start(DataType::Dictionary, data_len)
+    }
+
+    /// Ends the most recently started container, checking that its stated length was written
+    pub fn end(&mut self) -> Result<(), TNetStrError> {
+        let container = match self.open.pop() {
+            None => return Err(TNetStrError::SerializationError("Attempt to end sequence without start".to_string())),
+            Some(c) => c
+        };
+        let actual = self.written - container.data_start;
+        if actual != container.data_len {
+            return Err(TNetStrError::LengthWasIncorrect(container.data_len as u64, actual as u64))
+        }
+        self.put(&[container.data_type.to_byte()])
+    }
+
+    fn write_data(&mut self, data: &TNetData, sizes: &mut core::slice::Iter<usize>) -> Result<(), TNetStrError> {
+        match data {
+            TNetData::Null => self.write_null(),
+            TNetData::Bytes(b) => self.write_bytes(b),
+            TNetData::Integer(n) => self.write_integer(*n),
+            TNetData::Float(n) => self.write_float(n),
+            TNetData::Boolean(b) => self.write_bool(*b),
+            TNetData::Extension { tag, payload } => self.write_scalar(DataType::Extension(*tag), payload),
+            TNetData::Dictionary(dict) => {
+                self.start_dictionary(*sizes.next().expect("Bug - missing dictionary size"))?;
+                for (key, value) in &dict.entries {
+                    self.write_data(&key.data, sizes)?;
+                    self.write_data(&value.data, sizes)?;
+                }
+                self.end()
+            },
+            TNetData::List(l) => {
+                self.start_list(*sizes.next().expect("Bug - missing list size"))?;
+                for entry in l {
+                    self.write_data(&entry.data, sizes)?;
+                }
+                self.end()
+            }
+        }
+    }
+
+    /// Writes data without allocating, by measuring each container with `data_len` as it is reached
+    /// rather than recording the lengths in a sizing pass. A container nested `n` deep is measured
+    /// `n + 1` times, so `write_entry` is faster when allocating is allowed.
+    pub(crate) fn write_data_measured(&mut self, data: &TNetData) -> Result<(), TNetStrError> {
+        match data {
+            TNetData::Dictionary(dict) => {
+                self.put_header(data_len(data))?;
+                for (key, value) in &dict.entries {
+                    self.write_data_measured(&key.data)?;
+                    self.write_data_measured(&value.data)?;
+                }
+                self.put(b"}")
+            },
+            TNetData::List(l) => {
+                self.put_header(data_len(data))?;
+                for entry in l {
+                    self.write_data_measured(&entry.data)?;
+                }
+                self.put(b"]")
+            },
+            _ => self.write_data(data, &mut [].iter())
+        }
+    }
+
+    /// Writes an entry as a tnetstring.
+    /// Lengths are calculated from the entry's data, and the type tag is taken from the data rather
+    /// than the entry's `data_type`, so entries built by hand do not need an accurate `size`.
+    pub fn write_entry(&mut self, entry: &TNetEntry) -> Result<(), TNetStrError> {
+        let mut sizes = vec![];
+        size_data(&entry.data, &mut sizes);
+        self.write_data(&entry.data, &mut sizes.iter())
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+    use super::super::parse;
+
+    #[test]
+    fn formats_decimals() {
+        let mut buffer = [0; 20];
+        assert_eq!(format_decimal(0, &mut buffer), b"0");
+        assert_eq!(format_decimal(1234, &mut buffer), b"1234");
+        assert_eq!(format_decimal(u64::MAX, &mut buffer), u64::MAX.to_string().as_bytes());
+        assert_eq!(decimal_len(0), 1);
+        assert_eq!(decimal_len(999), 3);
+        assert_eq!(decimal_len(1000), 4);
+        assert_eq!(integer_len(i64::MIN), i64::MIN.to_string().len());
+    }
+
+    #[test]
+    fn writes_nested_entries() {
+        let input = b"52:4:meta,24:4:skip,4:1:x,]2:id,2:42#}4:list,7:1:a,0:]]}";
+        let entry = parse(input).unwrap();
+        let mut writer = TNetWriter::new(vec![]);
+        writer.write_entry(&entry).unwrap();
+        assert_eq!(writer.written(), input.len());
+        assert_eq!(encoded_len(&entry), input.len());
+        assert_eq!(writer.into_inner(), &input[..]);
+    }
+
+    #[test]
+    fn writes_containers_piece_by_piece() {
+        let mut writer = TNetWriter::new(vec![]);
+        writer.start_dictionary(17).unwrap();
+        writer.write_bytes(b"a").unwrap();
+        writer.write_integer(-12).unwrap();
+        writer.write_bytes(b"b").unwrap();
+        writer.write_null().unwrap();
+        writer.end().unwrap();
+        assert_eq!(writer.into_inner(), b"17:1:a,3:-12#1:b,0:~}");
+    }
+
+    #[test]
+    fn checks_container_lengths() {
+        let mut writer = TNetWriter::new(vec![]);
+        writer.start_list(3).unwrap();
+        writer.write_bool(false).unwrap();
+        assert_eq!(writer.end(), Err(TNetStrError::LengthWasIncorrect(3, 8)));
+        assert!(writer.end().is_err());
+    }
+
+    #[test]
+    fn rejects_lengths_above_the_spec_maximum() {
+        let mut writer = TNetWriter::new(vec![]);
+        assert_eq!(writer.start_list(MAX_DATA_LENGTH + 1), Err(TNetStrError::LengthTooLong));
+        assert_eq!(writer.start_dictionary(usize::MAX), Err(TNetStrError::LengthTooLong));
+        assert_eq!(writer.written(), 0);
+        writer.start_list(MAX_DATA_LENGTH).unwrap();
+        assert_eq!(writer.into_inner(), b"999999999:");
+    }
+}
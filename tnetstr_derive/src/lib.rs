@@ -0,0 +1,481 @@
+//! `#[derive(ToTNet, FromTNet)]` for the `tnetstr` crate. Use it through the `derive` feature of
+//! `tnetstr` rather than depending on it directly.
+//!
+//! Structs with named fields are written as dictionaries, tuple structs as lists (or as their only
+//! field), and unit structs as null. Enums are externally tagged by default: a unit variant is
+//! written as the bytes of its name, and any other variant as a dictionary with one pair, from its
+//! name to its fields.
+//!
+//! Attributes on the type:
+//!
+//! * `#[tnet(tag = "t")]` writes enums as a dictionary with the variant name at key `t`. Struct
+//!   variants have their fields in the same dictionary, and only unit and struct variants are
+//!   allowed.
+//! * `#[tnet(tag = "t", content = "c")]` writes enums as a dictionary with the variant name at key
+//!   `t` and its fields at key `c`.
+//!
+//! Attributes on fields and variants:
+//!
+//! * `#[tnet(rename = "name")]` uses a different key or variant name.
+//! * `#[tnet(default)]` uses `Default::default()` when the key is missing, and
+//!   `#[tnet(default = "path")]` calls a function instead. Fields of type `Option` are `None`
+//!   when missing without this.
+//! * `#[tnet(skip)]` neither writes nor reads the field, which is set to its default.
+//! * `#[tnet(skip_if = "path")]` leaves the key out when a function given a reference to the field
+//!   returns true, such as `Option::is_none`.
+//! * `#[tnet(bytes)]` writes the field as bytes rather than a list, for types such as `Vec<u8>`
+//!   that implement `AsRef<[u8]>` and `From<Vec<u8>>`.
+
+use proc_macro::TokenStream;
+use proc_macro2::{
+    Span,
+    TokenStream as TokenStream2
+};
+use quote::{
+    format_ident,
+    quote
+};
+use syn::{
+    ext::IdentExt,
+    parse_macro_input,
+    parse_quote,
+    Attribute,
+    Data,
+    DeriveInput,
+    Error,
+    Fields,
+    Generics,
+    Ident,
+    LitByteStr,
+    LitStr,
+    Member,
+    Path,
+    Type
+};
+
+#[proc_macro_derive(ToTNet, attributes(tnet))]
+pub fn derive_to_tnet(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    to_tnet(&input).unwrap_or_else(Error::into_compile_error).into()
+}
+
+#[proc_macro_derive(FromTNet, attributes(tnet))]
+pub fn derive_from_tnet(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    from_tnet(&input).unwrap_or_else(Error::into_compile_error).into()
+}
+
+/// How the variants of an enum are written
+enum Tagging {
+    External,
+    Internal(String),
+    Adjacent(String, String)
+}
+
+#[derive(Default)]
+struct FieldAttributes {
+    rename: Option<String>,
+    /// `Some(None)` for `Default::default()`, `Some(Some(path))` for a function
+    default: Option<Option<Path>>,
+    skip: bool,
+    skip_if: Option<Path>,
+    bytes: bool
+}
+
+/// Calls `f` with each item of the `#[tnet(...)]` attributes
+fn parse_attributes<F>(attributes: &[Attribute], mut f: F) -> syn::Result<()>
+where
+    F: FnMut(syn::meta::ParseNestedMeta) -> syn::Result<()>
+{
+    for attribute in attributes.iter().filter(|a| a.path().is_ident("tnet")) {
+        attribute.parse_nested_meta(&mut f)?;
+    }
+    Ok(())
+}
+
+fn tagging(input: &DeriveInput) -> syn::Result<Tagging> {
+    let (mut tag, mut content) = (None, None);
+    parse_attributes(&input.attrs, |meta| {
+        if meta.path.is_ident("tag") {
+            tag = Some(meta.value()?.parse::<LitStr>()?.value());
+        } else if meta.path.is_ident("content") {
+            content = Some(meta.value()?.parse::<LitStr>()?.value());
+        } else {
+            return Err(meta.error("expected `tag` or `content`"))
+        }
+        Ok(())
+    })?;
+    match (tag, content) {
+        (None, None) => Ok(Tagging::External),
+        (Some(tag), None) => Ok(Tagging::Internal(tag)),
+        (Some(tag), Some(content)) => Ok(Tagging::Adjacent(tag, content)),
+        (None, Some(_)) => Err(Error::new_spanned(input, "`content` needs a `tag`"))
+    }
+}
+
+fn variant_name(variant: &syn::Variant) -> syn::Result<String> {
+    let mut name = variant.ident.unraw().to_string();
+    parse_attributes(&variant.attrs, |meta| {
+        if meta.path.is_ident("rename") {
+            name = meta.value()?.parse::<LitStr>()?.value();
+            Ok(())
+        } else {
+            Err(meta.error("expected `rename`"))
+        }
+    })?;
+    Ok(name)
+}
+
+/// A field of a struct or variant
+struct Field<'a> {
+    member: Member,
+    /// The variable the field is bound to when matching a variant
+    binding: Ident,
+    name: String,
+    ty: &'a Type,
+    attributes: FieldAttributes
+}
+
+fn fields(fields: &Fields) -> syn::Result<Vec<Field<'_>>> {
+    fields.iter()
+        .enumerate()
+        .map(|(i, field)| {
+            let mut attributes = FieldAttributes::default();
+            parse_attributes(&field.attrs, |meta| {
+                if meta.path.is_ident("rename") {
+                    attributes.rename = Some(meta.value()?.parse::<LitStr>()?.value());
+                } else if meta.path.is_ident("default") {
+                    attributes.default = Some(match meta.input.peek(syn::Token![=]) {
+                        true => Some(meta.value()?.parse::<LitStr>()?.parse()?),
+                        false => None
+                    });
+                } else if meta.path.is_ident("skip") {
+                    attributes.skip = true;
+                } else if meta.path.is_ident("skip_if") {
+                    attributes.skip_if = Some(meta.value()?.parse::<LitStr>()?.parse()?);
+                } else if meta.path.is_ident("bytes") {
+                    attributes.bytes = true;
+                } else {
+                    return Err(meta.error("expected `rename`, `default`, `skip`, `skip_if` or `bytes`"))
+                }
+                Ok(())
+            })?;
+            let (member, name) = match &field.ident {
+                Some(ident) => (Member::Named(ident.clone()), ident.unraw().to_string()),
+                None if attributes.rename.is_some() || attributes.default.is_some() || attributes.skip || attributes.skip_if.is_some() =>
+                    return Err(Error::new_spanned(field, "only `bytes` can be used on tuple fields")),
+                None => (Member::Unnamed(i.into()), i.to_string())
+            };
+            Ok(Field {
+                member,
+                binding: format_ident!("__field{}", i),
+                name: attributes.rename.clone().unwrap_or(name),
+                ty: &field.ty,
+                attributes
+            })
+        })
+        .collect()
+}
+
+/// Adds a bound on `bound` for every type parameter
+fn bounded(generics: &Generics, bound: Path) -> Generics {
+    let mut generics = generics.clone();
+    let parameters: Vec<Ident> = generics.type_params().map(|p| p.ident.clone()).collect();
+    let where_clause = generics.make_where_clause();
+    for parameter in parameters {
+        where_clause.predicates.push(parse_quote!(#parameter: #bound));
+    }
+    generics
+}
+
+fn byte_literal(name: &str) -> LitByteStr {
+    LitByteStr::new(name.as_bytes(), Span::call_site())
+}
+
+fn encode_value(field: &Field, access: &TokenStream2) -> TokenStream2 {
+    match field.attributes.bytes {
+        true => quote!(::tnetstr::__private::bytes(::core::convert::AsRef::<[u8]>::as_ref(#access))),
+        false => quote!(::tnetstr::ToTNet::to_tnet(#access)?)
+    }
+}
+
+/// A dictionary of the fields that are not skipped, after an optional tag pair
+fn encode_named<F>(fields: &[Field], access: F, tag: Option<(&str, &str)>) -> TokenStream2
+where
+    F: Fn(&Field) -> TokenStream2
+{
+    let tag = tag.map(|(key, name)| quote! {
+        __entries.push((::tnetstr::__private::key(#key), ::tnetstr::__private::key(#name)));
+    });
+    let pushes = fields.iter()
+        .filter(|field| !field.attributes.skip)
+        .map(|field| {
+            let name = &field.name;
+            let access = access(field);
+            let value = encode_value(field, &access);
+            let push = quote!(__entries.push((::tnetstr::__private::key(#name), #value)););
+            match &field.attributes.skip_if {
+                Some(path) => quote!(if !#path(#access) { #push }),
+                None => push
+            }
+        });
+    quote! {{
+        let mut __entries = ::tnetstr::__private::Vec::new();
+        #tag
+        #(#pushes)*
+        ::tnetstr::__private::dictionary(__entries)
+    }}
+}
+
+/// The only field, or a list of the fields
+fn encode_unnamed<F>(fields: &[Field], access: F) -> TokenStream2
+where
+    F: Fn(&Field) -> TokenStream2
+{
+    if let [field] = fields {
+        return encode_value(field, &access(field))
+    }
+    let pushes = fields.iter().map(|field| {
+        let value = encode_value(field, &access(field));
+        quote!(__items.push(#value);)
+    });
+    quote! {{
+        let mut __items = ::tnetstr::__private::Vec::new();
+        #(#pushes)*
+        ::tnetstr::__private::list(__items)
+    }}
+}
+
+fn default_value(field: &Field) -> TokenStream2 {
+    match &field.attributes.default {
+        Some(Some(path)) => quote!(#path()),
+        _ => quote!(::core::default::Default::default())
+    }
+}
+
+/// Constructs `constructor` from the fields in `__pairs`
+fn decode_named(fields: &[Field], constructor: TokenStream2) -> TokenStream2 {
+    let values = fields.iter().map(|field| {
+        let (member, name, ty) = (&field.member, &field.name, field.ty);
+        if field.attributes.skip {
+            let default = default_value(field);
+            return quote!(#member: #default)
+        }
+        let missing = match (&field.attributes.default, field.attributes.bytes) {
+            (Some(_), _) => default_value(field),
+            (None, true) => quote!(return ::core::result::Result::Err(::tnetstr::__private::missing_key(#name))),
+            (None, false) => quote!(::tnetstr::__private::missing::<#ty>(#name)?)
+        };
+        let value = match field.attributes.bytes {
+            true => quote! {
+                match ::tnetstr::__private::bytes_field(__pairs, #name)? {
+                    ::core::option::Option::Some(__bytes) => ::core::convert::From::from(__bytes),
+                    ::core::option::Option::None => #missing
+                }
+            },
+            false => quote! {
+                match ::tnetstr::__private::field::<_, #ty>(__pairs, #name)? {
+                    ::core::option::Option::Some(__value) => __value,
+                    ::core::option::Option::None => #missing
+                }
+            }
+        };
+        quote!(#member: #value)
+    });
+    quote!(#constructor { #(#values),* })
+}
+
+fn decode_value(field: &Field, value: TokenStream2) -> TokenStream2 {
+    let ty = field.ty;
+    match field.attributes.bytes {
+        true => quote!(::core::convert::From::from(::tnetstr::__private::read_bytes(#value)?)),
+        false => quote!(<#ty as ::tnetstr::FromTNet>::from_tnet(#value)?)
+    }
+}
+
+/// Constructs `constructor` from `value`, which is the only field or a list of the fields
+fn decode_unnamed(fields: &[Field], constructor: TokenStream2, value: TokenStream2) -> TokenStream2 {
+    if let [field] = fields {
+        let field = decode_value(field, value);
+        return quote!(#constructor(#field))
+    }
+    let length = fields.len();
+    let values = fields.iter().enumerate().map(|(i, field)| decode_value(field, quote!(&__items[#i])));
+    quote! {{
+        let __items = ::tnetstr::__private::items(#value, #length)?;
+        #constructor(#(#values),*)
+    }}
+}
+
+fn to_tnet(input: &DeriveInput) -> syn::Result<TokenStream2> {
+    let body = match &input.data {
+        Data::Struct(data) => {
+            let fields = fields(&data.fields)?;
+            let access = |field: &Field| {
+                let member = &field.member;
+                quote!(&self.#member)
+            };
+            let entry = match &data.fields {
+                // A newtype is written as its field, without rewrapping its result
+                Fields::Unnamed(_) if fields.len() == 1 && !fields[0].attributes.bytes =>
+                    return Ok(impl_to_tnet(input, quote!(::tnetstr::ToTNet::to_tnet(&self.0)))),
+                Fields::Named(_) => encode_named(&fields, access, None),
+                Fields::Unnamed(_) => encode_unnamed(&fields, access),
+                Fields::Unit => quote!(::tnetstr::__private::null())
+            };
+            quote!(::core::result::Result::Ok(#entry))
+        },
+        Data::Enum(data) => {
+            let tagging = tagging(input)?;
+            let arms = data.variants.iter()
+                .map(|variant| {
+                    let (ident, name) = (&variant.ident, variant_name(variant)?);
+                    let fields = fields(&variant.fields)?;
+                    let bindings = fields.iter().filter(|f| !f.attributes.skip).map(|f| {
+                        let (member, binding) = (&f.member, &f.binding);
+                        quote!(#member: #binding)
+                    });
+                    let access = |field: &Field| {
+                        let binding = &field.binding;
+                        quote!(#binding)
+                    };
+                    let (pattern, content) = match &variant.fields {
+                        Fields::Named(_) => (quote!(Self::#ident { #(#bindings,)* .. }), Some(encode_named(&fields, access, None))),
+                        Fields::Unnamed(_) => {
+                            let bindings = fields.iter().map(|f| &f.binding);
+                            (quote!(Self::#ident(#(#bindings),*)), Some(encode_unnamed(&fields, access)))
+                        },
+                        Fields::Unit => (quote!(Self::#ident), None)
+                    };
+                    let entry = match (&tagging, content) {
+                        (Tagging::External, None) => quote!(::tnetstr::__private::key(#name)),
+                        (Tagging::External, Some(content)) => quote! {{
+                            let mut __entries = ::tnetstr::__private::Vec::new();
+                            __entries.push((::tnetstr::__private::key(#name), #content));
+                            ::tnetstr::__private::dictionary(__entries)
+                        }},
+                        (Tagging::Internal(tag), _) => match &variant.fields {
+                            Fields::Unnamed(_) => return Err(Error::new_spanned(variant, "internally tagged enums can only have unit and struct variants")),
+                            _ => encode_named(&fields, access, Some((tag, &name)))
+                        },
+                        (Tagging::Adjacent(tag, _), None) => encode_named(&[], access, Some((tag, &name))),
+                        (Tagging::Adjacent(tag, content_key), Some(content)) => quote! {{
+                            let mut __entries = ::tnetstr::__private::Vec::new();
+                            __entries.push((::tnetstr::__private::key(#tag), ::tnetstr::__private::key(#name)));
+                            __entries.push((::tnetstr::__private::key(#content_key), #content));
+                            ::tnetstr::__private::dictionary(__entries)
+                        }}
+                    };
+                    Ok(quote!(#pattern => #entry,))
+                })
+                .collect::<syn::Result<Vec<_>>>()?;
+            quote!(::core::result::Result::Ok(match self { #(#arms)* }))
+        },
+        Data::Union(_) => return Err(Error::new_spanned(input, "unions cannot be converted to tnetstrings"))
+    };
+    Ok(impl_to_tnet(input, body))
+}
+
+fn impl_to_tnet(input: &DeriveInput, body: TokenStream2) -> TokenStream2 {
+    let name = &input.ident;
+    let generics = bounded(&input.generics, parse_quote!(::tnetstr::ToTNet));
+    let (impl_generics, type_generics, where_clause) = generics.split_for_impl();
+    quote! {
+        impl #impl_generics ::tnetstr::ToTNet for #name #type_generics #where_clause {
+            fn to_tnet(&self) -> ::core::result::Result<::tnetstr::TNetEntry, ::tnetstr::TNetStrError> {
+                #body
+            }
+        }
+    }
+}
+
+fn from_tnet(input: &DeriveInput) -> syn::Result<TokenStream2> {
+    let body = match &input.data {
+        Data::Struct(data) => {
+            let fields = fields(&data.fields)?;
+            let value = match &data.fields {
+                Fields::Named(_) => {
+                    let value = decode_named(&fields, quote!(Self));
+                    quote! {{
+                        let __pairs = ::tnetstr::__private::pairs(__value)?;
+                        #value
+                    }}
+                },
+                Fields::Unnamed(_) => decode_unnamed(&fields, quote!(Self), quote!(__value)),
+                Fields::Unit => quote! {{
+                    <() as ::tnetstr::FromTNet>::from_tnet(__value)?;
+                    Self
+                }}
+            };
+            quote!(::core::result::Result::Ok(#value))
+        },
+        Data::Enum(data) => {
+            let tagging = tagging(input)?;
+            let mut unit_arms = vec![];
+            let mut arms = vec![];
+            for variant in &data.variants {
+                let (ident, name) = (&variant.ident, byte_literal(&variant_name(variant)?));
+                let fields = fields(&variant.fields)?;
+                let content = match &tagging {
+                    Tagging::External => quote!(__content),
+                    Tagging::Internal(_) => quote!(__value),
+                    Tagging::Adjacent(_, content) => quote!(::tnetstr::__private::required(__pairs, #content)?)
+                };
+                let value = match &variant.fields {
+                    Fields::Named(_) => {
+                        let value = decode_named(&fields, quote!(Self::#ident));
+                        quote! {{
+                            let __pairs = ::tnetstr::__private::pairs(#content)?;
+                            #value
+                        }}
+                    },
+                    Fields::Unnamed(_) => match tagging {
+                        Tagging::Internal(_) => return Err(Error::new_spanned(variant, "internally tagged enums can only have unit and struct variants")),
+                        _ => decode_unnamed(&fields, quote!(Self::#ident), content)
+                    },
+                    Fields::Unit => quote!(Self::#ident)
+                };
+                let arm = quote!(#name => ::core::result::Result::Ok(#value),);
+                match (&tagging, &variant.fields) {
+                    (Tagging::External, Fields::Unit) => unit_arms.push(arm),
+                    _ => arms.push(arm)
+                }
+            }
+            let unknown = quote!(__name => ::core::result::Result::Err(::tnetstr::__private::unknown_variant(__name)));
+            match &tagging {
+                Tagging::External => quote! {
+                    if let ::core::option::Option::Some(__name) = ::tnetstr::TNetValue::as_bytes(__value) {
+                        return match __name {
+                            #(#unit_arms)*
+                            #unknown
+                        }
+                    }
+                    let (__key, __content) = ::tnetstr::__private::single_pair(__value)?;
+                    match ::tnetstr::__private::read_bytes(__key)?.as_slice() {
+                        #(#arms)*
+                        #unknown
+                    }
+                },
+                Tagging::Internal(tag) | Tagging::Adjacent(tag, _) => quote! {
+                    let __pairs = ::tnetstr::__private::pairs(__value)?;
+                    match ::tnetstr::__private::read_bytes(::tnetstr::__private::required(__pairs, #tag)?)?.as_slice() {
+                        #(#arms)*
+                        #unknown
+                    }
+                }
+            }
+        },
+        Data::Union(_) => return Err(Error::new_spanned(input, "unions cannot be read from tnetstrings"))
+    };
+
+    let name = &input.ident;
+    let generics = bounded(&input.generics, parse_quote!(::tnetstr::FromTNet));
+    let (impl_generics, type_generics, where_clause) = generics.split_for_impl();
+    Ok(quote! {
+        impl #impl_generics ::tnetstr::FromTNet for #name #type_generics #where_clause {
+            fn from_tnet<__V: ::tnetstr::TNetValue>(__value: &__V) -> ::core::result::Result<Self, ::tnetstr::TNetStrError> {
+                #body
+            }
+        }
+    })
+}
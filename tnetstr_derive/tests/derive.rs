@@ -0,0 +1,137 @@
+use std::collections::BTreeMap;
+use tnetstr::{
+    from_tnetstring,
+    to_tnetstring,
+    FromTNet,
+    TNetStrError,
+    ToTNet
+};
+
+fn check<T: ToTNet + FromTNet + PartialEq + std::fmt::Debug>(value: T, expected: &[u8]) {
+    assert_eq!(to_tnetstring(&value).unwrap(), expected);
+    assert_eq!(from_tnetstring::<T>(expected).unwrap(), value);
+}
+
+fn default_port() -> u16 {
+    8080
+}
+
+#[derive(ToTNet, FromTNet, PartialEq, Debug)]
+struct Server {
+    name: String,
+    #[tnet(default = "default_port")]
+    port: u16,
+    #[tnet(rename = "type")]
+    kind: Kind,
+    #[tnet(default)]
+    tags: Vec<String>,
+    weight: Option<f64>,
+    #[tnet(bytes)]
+    secret: Vec<u8>,
+    #[tnet(skip)]
+    connections: usize
+}
+
+#[derive(ToTNet, FromTNet, PartialEq, Debug)]
+enum Kind {
+    Primary,
+    #[tnet(rename = "backup")]
+    Replica { of: String },
+    Proxy(u8, bool)
+}
+
+#[derive(ToTNet, FromTNet, PartialEq, Debug)]
+struct Id(u32);
+
+#[derive(ToTNet, FromTNet, PartialEq, Debug)]
+struct Point(i64, i64);
+
+#[derive(ToTNet, FromTNet, PartialEq, Debug)]
+struct Marker;
+
+#[derive(ToTNet, FromTNet, PartialEq, Debug)]
+struct Wrapper<T> {
+    items: BTreeMap<String, T>
+}
+
+#[derive(ToTNet, FromTNet, PartialEq, Debug)]
+struct Note {
+    #[tnet(skip_if = "Option::is_none")]
+    text: Option<String>
+}
+
+#[derive(ToTNet, FromTNet, PartialEq, Debug)]
+#[tnet(tag = "op")]
+enum Internal {
+    Reset,
+    Move { x: i64, y: i64 }
+}
+
+#[derive(ToTNet, FromTNet, PartialEq, Debug)]
+#[tnet(tag = "t", content = "c")]
+enum Adjacent {
+    Empty,
+    Pair(u8, u8),
+    Named { value: String }
+}
+
+#[test]
+fn converts_structs() {
+    let server = Server {
+        name: "db".to_string(),
+        port: 5432,
+        kind: Kind::Primary,
+        tags: vec!["a".to_string()],
+        weight: Some(0.5),
+        secret: b"\x00\xff".to_vec(),
+        connections: 0
+    };
+    check(server, b"86:4:name,2:db,4:port,4:5432#4:type,7:Primary,4:tags,4:1:a,]6:weight,3:0.5^6:secret,2:\x00\xff,}");
+    check(Id(7), b"1:7#");
+    check(Point(1, -2), b"9:1:1#2:-2#]");
+    check(Marker, b"0:~");
+    let mut items = BTreeMap::new();
+    items.insert("x".to_string(), true);
+    check(Wrapper { items }, b"23:5:items,11:1:x,4:true!}}");
+}
+
+#[test]
+fn leaves_out_skipped_keys() {
+    check(Note { text: None }, b"0:}");
+    check(Note { text: Some("hi".to_string()) }, b"12:4:text,2:hi,}");
+}
+
+#[test]
+fn fills_in_missing_fields() {
+    let server: Server = from_tnetstring(b"40:4:name,1:a,4:type,7:Primary,6:secret,0:,}").unwrap();
+    assert_eq!(server, Server {
+        name: "a".to_string(),
+        port: 8080,
+        kind: Kind::Primary,
+        tags: vec![],
+        weight: None,
+        secret: vec![],
+        connections: 0
+    });
+    match from_tnetstring::<Server>(b"28:4:type,7:Primary,4:name,1:a,}") {
+        Err(TNetStrError::MissingKey(key)) => assert_eq!(key, "secret"),
+        other => panic!("{:?}", other)
+    }
+}
+
+#[test]
+fn converts_enums() {
+    check(Kind::Primary, b"7:Primary,");
+    check(Kind::Replica { of: "db".to_string() }, b"23:6:backup,10:2:of,2:db,}}");
+    check(Kind::Proxy(3, false), b"24:5:Proxy,12:1:3#5:false!]}");
+    check(Internal::Reset, b"13:2:op,5:Reset,}");
+    check(Internal::Move { x: 1, y: 2 }, b"28:2:op,4:Move,1:x,1:1#1:y,1:2#}");
+    check(Adjacent::Empty, b"12:1:t,5:Empty,}");
+    check(Adjacent::Pair(1, 2), b"26:1:t,4:Pair,1:c,8:1:1#1:2#]}");
+    check(Adjacent::Named { value: "v".to_string() }, b"32:1:t,5:Named,1:c,12:5:value,1:v,}}");
+    match from_tnetstring::<Kind>(b"7:Unknown,") {
+        Err(TNetStrError::UnknownVariant(name)) => assert_eq!(name, "Unknown"),
+        other => panic!("{:?}", other)
+    }
+    assert!(from_tnetstring::<Internal>(b"8:1:x,1:1#}").is_err());
+}